@@ -65,7 +65,24 @@ pub fn logging_setup(
     max_level: tracing::Level,
     log_file: Option<impl std::io::Write + Clone + Send + 'static>,
 ) {
-    use tracing_subscriber::{filter, prelude::*};
+    setup_logging(max_level, log_file, false)
+}
+
+/// Same as [`logging_setup`], but the terminal log is written to stderr. This
+/// is used when stdout is reserved for other purposes (e.g. DAP messages).
+pub fn logging_setup_stderr(
+    max_level: tracing::Level,
+    log_file: Option<impl std::io::Write + Clone + Send + 'static>,
+) {
+    setup_logging(max_level, log_file, true)
+}
+
+fn setup_logging(
+    max_level: tracing::Level,
+    log_file: Option<impl std::io::Write + Clone + Send + 'static>,
+    to_stderr: bool,
+) {
+    use tracing_subscriber::{filter, fmt::writer::BoxMakeWriter, prelude::*};
 
     let filter = filter::filter_fn(move |meta| {
         *meta.level() <= max_level // && !from_actix_session
@@ -77,7 +94,11 @@ pub fn logging_setup(
         .with_target(false)
         .without_time()
         .with_thread_names(false)
-        // .with_writer(std::io::stderr)
+        .with_writer(if to_stderr {
+            BoxMakeWriter::new(std::io::stderr)
+        } else {
+            BoxMakeWriter::new(std::io::stdout)
+        })
         .with_filter(filter.clone());
 
     let file_log = log_file.map(|file| {
@@ -0,0 +1,88 @@
+use std::{
+    io::{BufReader, BufWriter, Read, Write},
+    sync::{mpsc, Arc, Mutex},
+};
+
+use anyhow::Context;
+use dap::{
+    base_message::Sendable,
+    prelude::*,
+    server::{Server, ServerOutput},
+};
+
+/// Connection to the debug client. Requests are read on a separate thread, so
+/// that the server is able to handle requests (e.g. pause) while the program
+/// is running.
+pub struct Connection<W: Write> {
+    requests: mpsc::Receiver<anyhow::Result<Request>>,
+    output: Arc<Mutex<ServerOutput<W>>>,
+}
+
+impl<W: Write + Send + 'static> Connection<W> {
+    pub fn new<R: Read + Send + 'static>(input: R, output: W) -> Self {
+        let mut server = Server::new(BufReader::new(input), BufWriter::new(output));
+        let output = server.output.clone();
+        let (tx, requests) = mpsc::channel();
+
+        std::thread::spawn(move || loop {
+            let req = match server.poll_request() {
+                // end of input stream
+                Ok(None) => break,
+                Ok(Some(req)) => Ok(req),
+                Err(e) => Err(anyhow::anyhow!("fail to read request: {:?}", e)),
+            };
+            let is_err = req.is_err();
+            // the receiver is dropped when the session ends
+            if tx.send(req).is_err() || is_err {
+                break;
+            }
+        });
+
+        Self { requests, output }
+    }
+}
+
+impl<W: Write> Connection<W> {
+    /// Wait for the next request. Return `None` if the client closes the
+    /// connection.
+    pub fn poll_request(&mut self) -> anyhow::Result<Option<Request>> {
+        self.requests.recv().ok().transpose()
+    }
+
+    /// Wait for the next request until the timeout. Return `None` on timeout.
+    pub fn poll_request_timeout(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> anyhow::Result<Option<Request>> {
+        match self.requests.recv_timeout(timeout) {
+            Ok(req) => req.map(Some),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(mpsc::RecvTimeoutError::Disconnected) => anyhow::bail!("connection closed"),
+        }
+    }
+
+    /// Get the next request if there is any, without blocking.
+    pub fn try_request(&mut self) -> anyhow::Result<Option<Request>> {
+        match self.requests.try_recv() {
+            Ok(req) => req.map(Some),
+            Err(mpsc::TryRecvError::Empty) => Ok(None),
+            Err(mpsc::TryRecvError::Disconnected) => anyhow::bail!("connection closed"),
+        }
+    }
+
+    fn send(&mut self, body: Sendable) -> anyhow::Result<()> {
+        let mut output = self
+            .output
+            .lock()
+            .map_err(|_| anyhow::anyhow!("fail to lock server output"))?;
+        output.send(body).context("fail to send message")
+    }
+
+    pub fn respond(&mut self, response: Response) -> anyhow::Result<()> {
+        self.send(Sendable::Response(response))
+    }
+
+    pub fn send_event(&mut self, event: Event) -> anyhow::Result<()> {
+        self.send(Sendable::Event(event))
+    }
+}
@@ -1,8 +1,10 @@
+mod connection;
 mod server;
 
 use std::net::SocketAddr;
 
 use anyhow::Context;
+pub use server::DebugServer;
 
 #[derive(Debug, Clone)]
 pub struct SimOption {
@@ -10,6 +12,21 @@ pub struct SimOption {
     pub max_cpu_cycle: u64,
 }
 
+/// Serve a single debug session over stdin and stdout. This is how editors
+/// launch debug adapters directly.
+pub fn start_stdio(option: SimOption) -> anyhow::Result<()> {
+    let handle = std::thread::Builder::new()
+        .stack_size(32 << 20)
+        .spawn(move || {
+            let server = server::DebugServer::new(std::io::stdin(), std::io::stdout(), option);
+            let r = server.start();
+            tracing::trace!("session ended, result: {:?}", r);
+        })?;
+    handle
+        .join()
+        .map_err(|_| anyhow::anyhow!("debug session panicked"))
+}
+
 pub fn start_tcp_listener(port: u16, option: SimOption) -> anyhow::Result<()> {
     let addrs = [SocketAddr::from(([127, 0, 0, 1], port))];
     let listener = std::net::TcpListener::bind(&addrs[..]).context("failed to bind socket")?;
@@ -4,7 +4,7 @@ use binutils::{
 };
 use clap::Parser;
 use y86_dbg::SimOption;
-use y86_sim::architectures::arch_names;
+use y86_sim::architectures::{arch_names, check_arch};
 
 fn after_help() -> String {
     let extras = y86_sim::architectures::EXTRA_ARCH_NAMES;
@@ -43,7 +43,8 @@ fn after_help() -> String {
     arg_required_else_help = true,
 )]
 struct Args {
-    /// Specify the architecture to debug
+    /// Specify the default architecture to debug, which can be overridden by
+    /// the `arch` field in `launch.json`
     #[arg(long, default_value = "seq_std")]
     arch: Option<String>,
 
@@ -54,7 +55,13 @@ struct Args {
     #[arg(short = 'p', long, default_value = "2345")]
     port: Option<u16>,
 
-    /// Limit the maximum number of CPU cycles to prevent infinite loop
+    /// Serve a single debug session over stdin and stdout instead of
+    /// listening on a TCP port
+    #[arg(long, conflicts_with = "port")]
+    stdio: bool,
+
+    /// Limit the maximum number of CPU cycles to prevent infinite loop. It can
+    /// be overridden by the `maxCpuCycle` field in `launch.json`
     #[arg(long, default_value = "50000")]
     max_cpu_cycle: Option<u64>,
 }
@@ -62,25 +69,29 @@ struct Args {
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
     let log_level = binutils::verbose_level_to_trace(args.verbose.log_level());
-    binutils::logging_setup(log_level, None::<&std::fs::File>);
+    if args.stdio {
+        // stdout is used by DAP messages
+        binutils::logging_setup_stderr(log_level, None::<&std::fs::File>);
+    } else {
+        binutils::logging_setup(log_level, None::<&std::fs::File>);
+    }
 
     let arch = args.arch.expect("architecture is required");
-    if !arch_names().contains(&arch.as_str()) {
+    if let Err(e) = check_arch(&arch) {
         let mut cmd = Args::command();
-        cmd.error(
-            ErrorKind::InvalidValue,
-            format!("unknown architecture `{}`", arch),
-        )
-        .exit();
+        cmd.error(ErrorKind::InvalidValue, format!("{:#}", e))
+            .exit();
     }
 
-    y86_dbg::start_tcp_listener(
-        args.port.expect("port number is required"),
-        SimOption {
-            arch,
-            max_cpu_cycle: args.max_cpu_cycle.expect("max_cpu_cycle is required"),
-        },
-    )?;
+    let option = SimOption {
+        arch,
+        max_cpu_cycle: args.max_cpu_cycle.expect("max_cpu_cycle is required"),
+    };
+    if args.stdio {
+        y86_dbg::start_stdio(option)?;
+    } else {
+        y86_dbg::start_tcp_listener(args.port.expect("port number is required"), option)?;
+    }
 
     Ok(())
 }
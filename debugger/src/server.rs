@@ -1,6 +1,8 @@
 use std::{
-    io::{BufReader, BufWriter, Read, Write},
+    collections::BTreeMap,
+    io::{Read, Write},
     path::PathBuf,
+    time::{Duration, Instant, SystemTime},
 };
 
 use anyhow::{bail, ensure};
@@ -8,16 +10,47 @@ use dap::prelude::*;
 use serde::Deserialize;
 use y86_sim::{
     architectures::create_sim,
-    framework::{CpuSim, MemData},
+    framework::{next_to_retire, CpuSim, DataAccess, MemData, MEM_SIZE},
+    isa::{disassemble, inst_code, reg_code},
 };
 
-use crate::SimOption;
+use crate::{connection::Connection, SimOption};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum RunProgKind {
-    SingleStep,
+    /// Simulate exactly one CPU cycle
+    Cycle,
+    /// Run until the next instruction retires
+    StepIn,
+    /// Run until an instruction retires and the call depth is not deeper
+    /// than the given one
+    StepOver(i64),
+    /// Run until an instruction retires and the call depth is shallower than
+    /// the given one
+    StepOut(i64),
     Run,
     InitialRun,
+    /// Stop the running program on request of the client
+    Pause,
+    /// Terminate the debuggee on request of the client
+    Terminate,
+}
+
+/// Number of cycles simulated before the server checks for new requests
+const CYCLES_PER_SLICE: usize = 1000;
+/// How often the source file is checked for changes if it is watched
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The unit of a single step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Stepping {
+    /// Step by retired instructions. The current location is the next
+    /// instruction to retire.
+    #[default]
+    Instruction,
+    /// Step by CPU cycles. The current location is the program counter.
+    Cycle,
 }
 
 enum ServerStatus {
@@ -34,17 +67,234 @@ pub struct Inner {
     source_name: String,
     scopes: Vec<types::Scope>,
     stage_info: Vec<y86_sim::framework::StageInfo>,
+    /// Labels defined in the source file
+    symbols: BTreeMap<String, u64>,
+    mem: MemData,
+    stepping: Stepping,
+    /// Number of `call` minus number of `ret` instructions retired so far,
+    /// i.e. the call depth of the next instruction to retire
+    call_depth: i64,
+    /// Address of the most recently retired instruction
+    last_retired: Option<u64>,
+    max_cpu_cycle: u64,
+    stop_on_entry: bool,
+    stage_threads: bool,
+    /// Modification time of the source file when it was assembled, if the
+    /// file is watched.
+    watched_mtime: Option<SystemTime>,
     sim: Box<dyn CpuSim>,
 }
 
-pub struct DebugServer<R: Read, W: Write> {
+impl Inner {
+    /// Address of the instruction shown as the current location
+    fn current_pc(&self) -> u64 {
+        match self.stepping {
+            Stepping::Instruction => self.next_to_retire().unwrap_or(self.sim.program_counter()),
+            Stepping::Cycle => self.sim.program_counter(),
+        }
+    }
+
+    /// Address of the next instruction to retire.
+    fn next_to_retire(&self) -> Option<u64> {
+        next_to_retire(self.sim.as_ref(), &self.mem, self.last_retired)
+    }
+
+    /// Track the call depth and return the address of the instruction
+    /// retired in the current cycle.
+    fn retire(&mut self) -> Option<u64> {
+        let addr = self.sim.retired_instruction()?;
+        let icode = self.mem.read().get(addr as usize).map(|b| b >> 4);
+        if icode == Some(inst_code::CALL) {
+            self.call_depth += 1;
+        } else if icode == Some(inst_code::RET) {
+            self.call_depth -= 1;
+        }
+        self.last_retired = Some(addr);
+        Some(addr)
+    }
+
+    /// Source code of the instruction at the address, or the disassembly if
+    /// there is no source line.
+    fn describe_inst(&self, addr: u64) -> String {
+        let src = self
+            .source_info
+            .get_line_number_by_addr(addr)
+            .and_then(|ln| self.source_info.get_line(ln))
+            .map(|line| line.src.trim().to_string());
+        src.or_else(|| disassemble(&self.mem.read()[..], addr as usize).map(|(_, s)| s))
+            .unwrap_or_else(|| format!("{:#x}", addr))
+    }
+
+    /// Disassemble `count` instructions starting `inst_offset` instructions
+    /// away from `target` in the live memory. Instructions before the target
+    /// are found by decoding from address 0. Bytes that do not form an
+    /// instruction are shown as `.byte`, and no instructions are made up out
+    /// of the memory.
+    fn disassemble(
+        &self,
+        target: i64,
+        inst_offset: i64,
+        count: i64,
+        source: &types::Source,
+    ) -> Vec<types::DisassembledInstruction> {
+        let mem = self.mem.read();
+        let decode = |pc: usize, end: usize| {
+            let (len, text) = disassemble(&mem[..end], pc)
+                .unwrap_or_else(|| (1, format!(".byte {:#04x}", mem[pc])));
+            (pc, len, text)
+        };
+
+        let target = target.clamp(0, MEM_SIZE as i64) as usize;
+        let mut insts = Vec::new();
+        let mut pc = 0;
+        while pc < target {
+            let inst = decode(pc, target);
+            pc += inst.1;
+            insts.push(inst);
+        }
+        let before = insts.len();
+        let after = (inst_offset + count).max(0) as usize;
+        while pc < MEM_SIZE && insts.len() - before < after {
+            let inst = decode(pc, MEM_SIZE);
+            pc += inst.1;
+            insts.push(inst);
+        }
+
+        // instructions out of the memory are left out, so fewer than `count`
+        // instructions are returned near either end of the memory
+        let start = (before as i64 + inst_offset).max(0) as usize;
+        let end = (before as i64 + inst_offset + count).max(0) as usize;
+        insts
+            .get(start.min(insts.len())..end.min(insts.len()))
+            .unwrap_or_default()
+            .iter()
+            .map(|(pc, len, text)| {
+                let line = self.source_info.get_line_number_by_addr(*pc as u64);
+                types::DisassembledInstruction {
+                    address: format!("{:#x}", pc),
+                    instruction_bytes: Some(
+                        mem[*pc..pc + len]
+                            .iter()
+                            .map(|b| format!("{:02x}", b))
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                    ),
+                    instruction: text.clone(),
+                    symbol: self
+                        .symbols
+                        .iter()
+                        .find(|(_, addr)| **addr == *pc as u64)
+                        .map(|(name, _)| name.clone()),
+                    location: line.map(|_| source.clone()),
+                    line,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+}
+
+/// The data watched by a data breakpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WatchTarget {
+    /// The 8-byte memory word at the address
+    Mem(u64),
+    /// The register with the register code
+    Reg(u8),
+}
+
+impl WatchTarget {
+    /// Parse the target from a register name (e.g. `%rsp`), a label or an
+    /// address literal. Addresses out of the memory can not be watched.
+    fn parse(name: &str, symbols: &BTreeMap<String, u64>) -> Option<Self> {
+        let name = name.trim();
+        if let Some(reg) = reg_code::code_of(name.strip_prefix('%').unwrap_or(name)) {
+            if reg != reg_code::RNONE {
+                return Some(Self::Reg(reg));
+            }
+        }
+        let addr = match symbols.get(name) {
+            Some(addr) => *addr,
+            None => y86_sim::utils::parse_literal(name)?,
+        };
+        Self::mem(addr)
+    }
+
+    fn mem(addr: u64) -> Option<Self> {
+        (addr < MEM_SIZE as u64).then_some(Self::Mem(addr))
+    }
+
+    /// The `dataId` used in DAP requests
+    fn data_id(&self) -> String {
+        match self {
+            Self::Mem(addr) => format!("mem:{:#x}", addr),
+            Self::Reg(reg) => format!("reg:{}", reg_code::name_of(*reg)),
+        }
+    }
+
+    fn from_data_id(data_id: &str) -> Option<Self> {
+        if let Some(addr) = data_id.strip_prefix("mem:") {
+            y86_sim::utils::parse_literal(addr).and_then(Self::mem)
+        } else if let Some(reg) = data_id.strip_prefix("reg:") {
+            reg_code::code_of(reg).map(Self::Reg)
+        } else {
+            None
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::Mem(addr) => format!("memory word at {:#x}", addr),
+            Self::Reg(reg) => format!("register %{}", reg_code::name_of(*reg).to_lowercase()),
+        }
+    }
+
+    /// Whether the access touches the watched data. Memory accesses are
+    /// 8-byte wide, thus overlapping words are also considered.
+    fn matches(&self, access: &DataAccess, access_type: &types::DataBreakpointAccessType) -> bool {
+        use types::DataBreakpointAccessType as T;
+        let (is_write, hit) = match (*self, *access) {
+            (Self::Mem(w), DataAccess::MemRead(a)) => (false, overlaps(w, a)),
+            (Self::Mem(w), DataAccess::MemWrite(a)) => (true, overlaps(w, a)),
+            (Self::Reg(w), DataAccess::RegRead(r)) => (false, w == r),
+            (Self::Reg(w), DataAccess::RegWrite(r)) => (true, w == r),
+            _ => return false,
+        };
+        hit && match access_type {
+            T::Read => !is_write,
+            T::Write => is_write,
+            T::ReadWrite => true,
+        }
+    }
+}
+
+/// Whether the 8-byte words at the two addresses overlap
+fn overlaps(a: u64, b: u64) -> bool {
+    a < b.saturating_add(8) && b < a.saturating_add(8)
+}
+
+struct DataBreakpoint {
+    id: i64,
+    target: WatchTarget,
+    access_type: types::DataBreakpointAccessType,
+}
+
+pub struct DebugServer<W: Write> {
     inner: Option<Inner>,
     /// Lines of breakpoints
     breakpoints: Vec<types::Breakpoint>,
-    /// Path of the source file (this debugger only supports single source file)
-    server: dap::server::Server<R, W>,
+    data_breakpoints: Vec<DataBreakpoint>,
+    /// Ids and addresses of instruction breakpoints
+    instruction_breakpoints: Vec<(i64, u64)>,
+    /// The last id assigned to a breakpoint. Ids are unique among all kinds
+    /// of breakpoints and stay the same when the program is restarted.
+    breakpoint_id: i64,
+    server: Connection<W>,
     status: ServerStatus,
     sim_opt: SimOption,
+    /// Options of the last launch request, used for restarting
+    launch: Option<LaunchOption>,
+    last_watch_check: Instant,
 }
 
 const THREAD_ID: i64 = 1;
@@ -58,19 +308,70 @@ const VAR_PRESENTATION_HINT: types::VariablePresentationHint = types::VariablePr
     lazy: Some(false),
 };
 
-#[derive(Deserialize)]
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct LaunchOption {
     program: String,
+    /// Step by instructions (default) or by CPU cycles
+    #[serde(default)]
+    stepping: Stepping,
+    /// Architecture to simulate. Default to the `--arch` option of the server.
+    arch: Option<String>,
+    /// Default to the `--max-cpu-cycle` option of the server.
+    max_cpu_cycle: Option<u64>,
+    /// Initial values of registers, e.g. `{ "rsp": "0x200", "rdi": 5 }`
+    #[serde(default)]
+    registers: BTreeMap<String, serde_json::Value>,
+    /// Whether to stop at the beginning of the program. Default to true.
+    #[serde(default = "default_stop_on_entry")]
+    stop_on_entry: bool,
+    /// Show each pipeline stage as a thread, which points at the instruction
+    /// in that stage.
+    #[serde(default)]
+    stage_threads: bool,
+    /// Restart the program when the source file changes
+    #[serde(default)]
+    watch: bool,
+}
+
+fn default_stop_on_entry() -> bool {
+    true
+}
+
+/// Parse the initial value of a register, which is either a JSON number or a
+/// numeric literal string.
+fn parse_register_value(val: &serde_json::Value) -> Option<u64> {
+    match val {
+        serde_json::Value::Number(n) => n.as_u64().or(n.as_i64().map(|v| v as u64)),
+        serde_json::Value::String(s) => y86_sim::utils::parse_literal(s),
+        _ => None,
+    }
+}
+
+/// Decide how far a step request runs. The finest granularity a client can
+/// ask for (`instruction` in DAP) is a single CPU cycle of the simulator.
+fn step_kind(inner: &Inner, granularity: Option<&types::SteppingGranularity>) -> RunProgKind {
+    match (granularity, inner.stepping) {
+        (Some(types::SteppingGranularity::Instruction), _) | (_, Stepping::Cycle) => {
+            RunProgKind::Cycle
+        }
+        _ => RunProgKind::StepIn,
+    }
 }
 
-impl<R: Read, W: Write> DebugServer<R, W> {
-    pub fn new(input: R, output: W, option: SimOption) -> Self {
+impl<W: Write + Send + 'static> DebugServer<W> {
+    pub fn new<R: Read + Send + 'static>(input: R, output: W, option: SimOption) -> Self {
         Self {
             inner: None,
-            server: dap::server::Server::new(BufReader::new(input), BufWriter::new(output)),
+            server: Connection::new(input, output),
             breakpoints: Vec::new(),
+            data_breakpoints: Vec::new(),
+            instruction_breakpoints: Vec::new(),
+            breakpoint_id: 0,
             status: ServerStatus::ServeReq,
             sim_opt: option,
+            launch: None,
+            last_watch_check: Instant::now(),
         }
     }
 
@@ -80,14 +381,24 @@ impl<R: Read, W: Write> DebugServer<R, W> {
             .ok_or(anyhow::anyhow!("program data not initialized"))
     }
 
-    fn init_program(&mut self, program: PathBuf) -> anyhow::Result<()> {
+    fn init_program(&mut self, options: &LaunchOption) -> anyhow::Result<()> {
+        let program = PathBuf::from(&options.program);
         tracing::info!("initializing program: {}", program.display());
 
         let src = std::fs::read_to_string(&program)?;
         let a = y86_sim::assemble(&src, y86_sim::AssembleOption::default())?;
 
+        let arch = options.arch.as_ref().unwrap_or(&self.sim_opt.arch);
         let mem = MemData::init(a.obj.init_mem());
-        let sim = create_sim(self.sim_opt.arch.clone(), mem, false);
+        let mut sim = create_sim(arch.clone(), mem.clone(), false)?;
+        for (name, val) in &options.registers {
+            let reg = reg_code::code_of(name.strip_prefix('%').unwrap_or(name))
+                .filter(|r| *r != reg_code::RNONE)
+                .ok_or(anyhow::anyhow!("unknown register `{name}`"))?;
+            let val = parse_register_value(val)
+                .ok_or(anyhow::anyhow!("invalid value of register `{name}`: {val}"))?;
+            sim.set_register(reg, val);
+        }
         let source_path = program.clone();
         let source_info = a.source;
         let source_name = program
@@ -136,12 +447,138 @@ impl<R: Read, W: Write> DebugServer<R, W> {
             source_name,
             scopes,
             stage_info,
+            symbols: a.obj.symbols,
+            mem,
+            stepping: options.stepping,
+            call_depth: 0,
+            last_retired: None,
+            max_cpu_cycle: options.max_cpu_cycle.unwrap_or(self.sim_opt.max_cpu_cycle),
+            stop_on_entry: options.stop_on_entry,
+            stage_threads: options.stage_threads,
+            watched_mtime: if options.watch {
+                std::fs::metadata(&program)?.modified().ok()
+            } else {
+                None
+            },
             sim,
         });
 
         Ok(())
     }
 
+    fn next_breakpoint_id(&mut self) -> i64 {
+        self.breakpoint_id += 1;
+        self.breakpoint_id
+    }
+
+    /// A breakpoint on the source line. It is verified if there is an
+    /// instruction on that line.
+    fn line_breakpoint(&self, id: i64, line: i64) -> anyhow::Result<types::Breakpoint> {
+        let addr = self
+            .inner()?
+            .source_info
+            .get_line(line.max(1))
+            .filter(|_| line > 0)
+            .and_then(|ln| ln.addr);
+        Ok(types::Breakpoint {
+            id: Some(id),
+            verified: addr.is_some(),
+            message: addr.map(|a| format!("addr: {:#x}", a)),
+            source: Some(self.main_source()),
+            line: Some(line),
+            ..Default::default()
+        })
+    }
+
+    /// Re-assemble the source file and reset the simulator. Breakpoints are
+    /// kept by line, and the client is notified of their new state.
+    fn reload_program(&mut self, options: LaunchOption) -> anyhow::Result<()> {
+        self.init_program(&options)?;
+        self.launch = Some(options);
+
+        for index in 0..self.breakpoints.len() {
+            let (Some(id), Some(line)) = (self.breakpoints[index].id, self.breakpoints[index].line)
+            else {
+                continue;
+            };
+            let bp = self.line_breakpoint(id, line)?;
+            self.breakpoints[index] = bp.clone();
+            self.server
+                .send_event(Event::Breakpoint(events::BreakpointEventBody {
+                    reason: types::BreakpointEventReason::Changed,
+                    breakpoint: bp,
+                }))?;
+        }
+        Ok(())
+    }
+
+    /// Restart the program if the watched source file has changed since it
+    /// was assembled. If the new source fails to assemble, the error is
+    /// reported and the old program is kept.
+    fn check_source_change(&mut self) -> anyhow::Result<()> {
+        if self.last_watch_check.elapsed() < WATCH_INTERVAL {
+            return Ok(());
+        }
+        self.last_watch_check = Instant::now();
+
+        let Some(inner) = self.inner.as_mut() else {
+            return Ok(());
+        };
+        let Some(mtime) = inner.watched_mtime else {
+            return Ok(());
+        };
+        let Ok(new_mtime) = std::fs::metadata(&inner.source_path).and_then(|m| m.modified()) else {
+            return Ok(());
+        };
+        if new_mtime == mtime {
+            return Ok(());
+        }
+        // do not retry until the file changes again
+        inner.watched_mtime = Some(new_mtime);
+
+        let options = self
+            .launch
+            .clone()
+            .ok_or(anyhow::anyhow!("program not launched"))?;
+        let output = match self.reload_program(options) {
+            Ok(()) => {
+                self.status = ServerStatus::RunProg(self.initial_run()?);
+                format!(
+                    "{} changed, restarting\n",
+                    self.main_source().name.unwrap_or_default()
+                )
+            }
+            Err(e) => format!("fail to reload program: {:#}\n", e),
+        };
+        self.server
+            .send_event(Event::Output(events::OutputEventBody {
+                category: Some(types::OutputEventCategory::Console),
+                output,
+                ..Default::default()
+            }))?;
+        Ok(())
+    }
+
+    /// Threads are numbered from 1. There is a single thread unless each
+    /// stage is shown as a thread.
+    fn check_thread(&self, thread_id: i64) -> anyhow::Result<()> {
+        let count = match self.inner.as_ref().filter(|i| i.stage_threads) {
+            Some(inner) => inner.sim.stage_instructions().len() as i64,
+            None => 1,
+        };
+        ensure!((1..=count).contains(&thread_id), "invalid thread id");
+        Ok(())
+    }
+
+    /// How the program starts running after it is launched
+    fn initial_run(&self) -> anyhow::Result<RunProgKind> {
+        Ok(if self.inner()?.stop_on_entry {
+            RunProgKind::InitialRun
+        } else {
+            RunProgKind::Run
+        })
+    }
+
     fn main_source(&self) -> types::Source {
         types::Source {
             name: self.inner.as_ref().map(|i| i.source_name.clone()),
@@ -164,7 +601,8 @@ impl<R: Read, W: Write> DebugServer<R, W> {
                 };
                 let options: LaunchOption = serde_json::from_value(data.clone())?;
 
-                self.init_program(PathBuf::from(options.program))?;
+                self.init_program(&options)?;
+                self.launch = Some(options);
 
                 Ok((req.success(ResponseBody::Launch), ServerStatus::ServeReq))
             }
@@ -173,7 +611,7 @@ impl<R: Read, W: Write> DebugServer<R, W> {
             }
             Command::ConfigurationDone => Ok((
                 req.success(ResponseBody::ConfigurationDone),
-                ServerStatus::RunProg(RunProgKind::InitialRun),
+                ServerStatus::RunProg(self.initial_run()?),
             )),
             Command::SetBreakpoints(args) => {
                 let Some(breakpoints) = &args.breakpoints else {
@@ -190,43 +628,167 @@ impl<R: Read, W: Write> DebugServer<R, W> {
                     bail!("source path mismatch");
                 }
 
-                let inner = self.inner()?;
-
                 let bps: Vec<types::Breakpoint> = breakpoints
                     .iter()
                     .map(|b| {
-                        let verified = true;
-                        let ln = inner
-                            .source_info
-                            .get_line(b.line)
-                            .expect("invalid breakpoint line");
-                        let message = ln.addr.map(|a| format!("addr: {:#x}", a));
-
-                        let Some(addr) = ln.addr else {
-                            return types::Breakpoint::default();
-                        };
+                        let id = self.next_breakpoint_id();
+                        self.line_breakpoint(id, b.line)
+                    })
+                    .collect::<anyhow::Result<_>>()?;
 
+                self.breakpoints = bps.clone();
+
+                Ok((
+                    req.success(ResponseBody::SetBreakpoints(
+                        responses::SetBreakpointsResponse { breakpoints: bps },
+                    )),
+                    ServerStatus::ServeReq,
+                ))
+            }
+            Command::DataBreakpointInfo(args) => {
+                let inner = self.inner()?;
+                let target = match args.variables_reference {
+                    // registers are the only variables that can be watched
+                    Some(REG_SCOPE_VAR_REF) | None => {
+                        WatchTarget::parse(&args.name, &inner.symbols)
+                    }
+                    Some(_) => None,
+                };
+                let body = match target {
+                    Some(target) => responses::DataBreakpointInfoResponse {
+                        data_id: Some(target.data_id()),
+                        description: target.describe(),
+                        access_types: Some(vec![
+                            types::DataBreakpointAccessType::Write,
+                            types::DataBreakpointAccessType::Read,
+                            types::DataBreakpointAccessType::ReadWrite,
+                        ]),
+                        can_persist: Some(false),
+                    },
+                    None => responses::DataBreakpointInfoResponse {
+                        data_id: None,
+                        description: format!("`{}` can not be watched", args.name),
+                        access_types: None,
+                        can_persist: None,
+                    },
+                };
+                Ok((
+                    req.success(ResponseBody::DataBreakpointInfo(body)),
+                    ServerStatus::ServeReq,
+                ))
+            }
+            Command::SetDataBreakpoints(args) => {
+                self.data_breakpoints.clear();
+                let bps = args
+                    .breakpoints
+                    .iter()
+                    .map(|bp| {
+                        let Some(target) = WatchTarget::from_data_id(&bp.data_id) else {
+                            return types::Breakpoint {
+                                verified: false,
+                                message: Some(format!("invalid data id {}", bp.data_id)),
+                                ..Default::default()
+                            };
+                        };
+                        let id = self.next_breakpoint_id();
+                        self.data_breakpoints.push(DataBreakpoint {
+                            id,
+                            target,
+                            access_type: bp
+                                .access_type
+                                .clone()
+                                .unwrap_or(types::DataBreakpointAccessType::Write),
+                        });
                         types::Breakpoint {
-                            // we use the address as the id
-                            id: Some(addr as i64),
-                            verified,
-                            message,
-                            source: Some(source.clone()),
-                            line: Some(b.line),
+                            id: Some(id),
+                            verified: true,
+                            message: Some(target.describe()),
                             ..Default::default()
                         }
                     })
                     .collect();
 
-                self.breakpoints = bps.clone();
+                Ok((
+                    req.success(ResponseBody::SetDataBreakpoints(
+                        responses::SetDataBreakpointsResponse { breakpoints: bps },
+                    )),
+                    ServerStatus::ServeReq,
+                ))
+            }
+            Command::SetInstructionBreakpoints(args) => {
+                self.instruction_breakpoints.clear();
+                let bps = args
+                    .breakpoints
+                    .iter()
+                    .map(|bp| {
+                        let Some(addr) = y86_sim::utils::parse_literal(&bp.instruction_reference)
+                            .and_then(|a| a.checked_add_signed(bp.offset.unwrap_or_default()))
+                            .filter(|a| *a < MEM_SIZE as u64)
+                        else {
+                            return types::Breakpoint {
+                                verified: false,
+                                message: Some(format!(
+                                    "invalid instruction reference {}",
+                                    bp.instruction_reference
+                                )),
+                                ..Default::default()
+                            };
+                        };
+                        let id = self.next_breakpoint_id();
+                        self.instruction_breakpoints.push((id, addr));
+                        types::Breakpoint {
+                            id: Some(id),
+                            verified: true,
+                            message: Some(format!("addr: {:#x}", addr)),
+                            instruction_reference: Some(format!("{:#x}", addr)),
+                            ..Default::default()
+                        }
+                    })
+                    .collect::<Vec<_>>();
 
                 Ok((
-                    req.success(ResponseBody::SetBreakpoints(
-                        responses::SetBreakpointsResponse { breakpoints: bps },
+                    req.success(ResponseBody::SetInstructionBreakpoints(
+                        responses::SetInstructionBreakpointsResponse { breakpoints: bps },
                     )),
                     ServerStatus::ServeReq,
                 ))
             }
+            Command::Disassemble(args) => {
+                let target = y86_sim::utils::parse_literal(&args.memory_reference)
+                    .ok_or(anyhow::anyhow!("invalid memory reference"))?
+                    as i64
+                    + args.offset.unwrap_or_default();
+                let instructions = self.inner()?.disassemble(
+                    target,
+                    args.instruction_offset.unwrap_or_default(),
+                    args.instruction_count,
+                    &self.main_source(),
+                );
+                Ok((
+                    req.success(ResponseBody::Disassemble(responses::DisassembleResponse {
+                        instructions,
+                    })),
+                    ServerStatus::ServeReq,
+                ))
+            }
+            Command::Evaluate(args) => {
+                let inner = self.inner()?;
+                let ctx = y86_sim::eval::EvalContext {
+                    sim: inner.sim.as_ref(),
+                    mem: &inner.mem,
+                    symbols: &inner.symbols,
+                };
+                let val = ctx.eval(&args.expression)?;
+                Ok((
+                    req.success(ResponseBody::Evaluate(responses::EvaluateResponse {
+                        result: format!("{:#x} ({})", val, val as i64),
+                        presentation_hint: Some(VAR_PRESENTATION_HINT),
+                        variables_reference: 0,
+                        ..Default::default()
+                    })),
+                    ServerStatus::ServeReq,
+                ))
+            }
             Command::SetExceptionBreakpoints(args) => {
                 // todo: add support for exception breakpoints (e.g. Stat::Adr)
                 ensure!(args.filters.is_empty(), "filters not supported");
@@ -241,22 +803,36 @@ impl<R: Read, W: Write> DebugServer<R, W> {
                     ServerStatus::ServeReq,
                 ))
             }
-            Command::Threads => Ok((
-                req.success(ResponseBody::Threads(
+            Command::Threads => {
+                let threads = match self.inner.as_ref().filter(|i| i.stage_threads) {
+                    Some(inner) => inner
+                        .sim
+                        .stage_instructions()
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, (stage, addr))| types::Thread {
+                            id: index as i64 + 1,
+                            name: match addr {
+                                Some(addr) => format!("{stage}: {}", inner.describe_inst(addr)),
+                                None => format!("{stage}: (bubble)"),
+                            },
+                        })
+                        .collect(),
                     // we have only one thread
-                    responses::ThreadsResponse {
-                        threads: vec![types::Thread {
-                            id: THREAD_ID,
-                            name: "main".to_string(),
-                        }],
-                    },
-                )),
-                ServerStatus::ServeReq,
-            )),
+                    None => vec![types::Thread {
+                        id: THREAD_ID,
+                        name: "main".to_string(),
+                    }],
+                };
+                Ok((
+                    req.success(ResponseBody::Threads(responses::ThreadsResponse {
+                        threads,
+                    })),
+                    ServerStatus::ServeReq,
+                ))
+            }
             Command::StackTrace(args) => {
-                if args.thread_id != THREAD_ID {
-                    bail!("invalid thread id");
-                }
+                self.check_thread(args.thread_id)?;
                 // not the first frame, we don't have stack trace
                 if args.start_frame.unwrap_or_default() > 0 {
                     return Ok((
@@ -268,17 +844,30 @@ impl<R: Read, W: Write> DebugServer<R, W> {
                     ));
                 }
                 let inner = self.inner()?;
+                // each stage thread has a single frame pointing at the
+                // instruction in the stage, which is None for a bubble
+                let (id, name, pc) = if inner.stage_threads {
+                    let (stage, addr) = inner.sim.stage_instructions()[args.thread_id as usize - 1];
+                    (args.thread_id, stage.to_string(), addr)
+                } else {
+                    (
+                        STACK_FRAME_ID,
+                        "current".to_string(),
+                        Some(inner.current_pc()),
+                    )
+                };
+                // the program may run into memory without source line, in which
+                // case the client falls back to the disassembly view
+                let line = pc.and_then(|pc| inner.source_info.get_line_number_by_addr(pc));
                 // currently we don't have stack trace, thus we return a single frame
                 Ok((
                     req.success(ResponseBody::StackTrace(responses::StackTraceResponse {
                         stack_frames: vec![types::StackFrame {
-                            id: STACK_FRAME_ID,
-                            name: "current".to_string(),
-                            source: Some(self.main_source()),
-                            line: inner
-                                .source_info
-                                .get_line_number_by_addr(inner.sim.program_counter())
-                                .unwrap_or_default(),
+                            id,
+                            name,
+                            source: line.map(|_| self.main_source()),
+                            line: line.unwrap_or_default(),
+                            instruction_pointer_reference: pc.map(|pc| format!("{:#x}", pc)),
                             can_restart: Some(false),
                             presentation_hint: Some(types::StackFramePresentationhint::Normal),
                             ..Default::default()
@@ -289,9 +878,9 @@ impl<R: Read, W: Write> DebugServer<R, W> {
                 ))
             }
             Command::Scopes(args) => {
-                if args.frame_id != STACK_FRAME_ID {
-                    bail!("invalid frame id");
-                }
+                // frames of all threads share the same scopes
+                self.check_thread(args.frame_id)
+                    .map_err(|_| anyhow::anyhow!("invalid frame id"))?;
                 Ok((
                     req.success(ResponseBody::Scopes(responses::ScopesResponse {
                         scopes: self.inner()?.scopes.clone(),
@@ -346,19 +935,36 @@ impl<R: Read, W: Write> DebugServer<R, W> {
                 ))
             }
             Command::Next(args) => {
-                if args.thread_id != THREAD_ID {
-                    bail!("invalid thread id");
-                }
-                // we do not care about the granularity
+                self.check_thread(args.thread_id)?;
+                let inner = self.inner()?;
+                let kind = match step_kind(inner, args.granularity.as_ref()) {
+                    RunProgKind::StepIn => RunProgKind::StepOver(inner.call_depth),
+                    kind => kind,
+                };
+                Ok((req.success(ResponseBody::Next), ServerStatus::RunProg(kind)))
+            }
+            Command::StepIn(args) => {
+                self.check_thread(args.thread_id)?;
+                let kind = step_kind(self.inner()?, args.granularity.as_ref());
                 Ok((
-                    req.success(ResponseBody::Next),
-                    ServerStatus::RunProg(RunProgKind::SingleStep),
+                    req.success(ResponseBody::StepIn),
+                    ServerStatus::RunProg(kind),
+                ))
+            }
+            Command::StepOut(args) => {
+                self.check_thread(args.thread_id)?;
+                let inner = self.inner()?;
+                let kind = match step_kind(inner, args.granularity.as_ref()) {
+                    RunProgKind::StepIn => RunProgKind::StepOut(inner.call_depth),
+                    kind => kind,
+                };
+                Ok((
+                    req.success(ResponseBody::StepOut),
+                    ServerStatus::RunProg(kind),
                 ))
             }
             Command::Continue(args) => {
-                if args.thread_id != THREAD_ID {
-                    bail!("invalid thread id");
-                }
+                self.check_thread(args.thread_id)?;
                 Ok((
                     req.success(ResponseBody::Continue(responses::ContinueResponse {
                         // we have exactly one thread
@@ -367,6 +973,36 @@ impl<R: Read, W: Write> DebugServer<R, W> {
                     ServerStatus::RunProg(RunProgKind::Run),
                 ))
             }
+            Command::Pause(args) => {
+                self.check_thread(args.thread_id)?;
+                Ok((
+                    req.success(ResponseBody::Pause),
+                    ServerStatus::RunProg(RunProgKind::Pause),
+                ))
+            }
+            Command::Terminate(_) => Ok((
+                req.success(ResponseBody::Terminate),
+                ServerStatus::RunProg(RunProgKind::Terminate),
+            )),
+            Command::Restart(args) => {
+                // the client may send the updated launch options
+                let options = match args
+                    .arguments
+                    .as_ref()
+                    .and_then(|a| a.additional_data.as_ref())
+                {
+                    Some(data) => serde_json::from_value(data.clone())?,
+                    None => self
+                        .launch
+                        .clone()
+                        .ok_or(anyhow::anyhow!("program not launched"))?,
+                };
+                self.reload_program(options)?;
+                Ok((
+                    req.success(ResponseBody::Restart),
+                    ServerStatus::RunProg(self.initial_run()?),
+                ))
+            }
             _ => {
                 bail!("ydb: not implemented");
             }
@@ -385,6 +1021,13 @@ impl<R: Read, W: Write> DebugServer<R, W> {
         };
         let rsp = req.success(ResponseBody::Initialize(types::Capabilities {
             supports_configuration_done_request: Some(true),
+            supports_data_breakpoints: Some(true),
+            supports_stepping_granularity: Some(true),
+            supports_disassemble_request: Some(true),
+            supports_evaluate_for_hovers: Some(true),
+            supports_instruction_breakpoints: Some(true),
+            supports_terminate_request: Some(true),
+            supports_restart_request: Some(true),
             ..Default::default()
         }));
 
@@ -398,12 +1041,30 @@ impl<R: Read, W: Write> DebugServer<R, W> {
     }
 
     fn serve_req(&mut self) -> anyhow::Result<()> {
+        if self
+            .inner
+            .as_ref()
+            .is_some_and(|i| i.watched_mtime.is_some())
+        {
+            // wake up periodically to check the source file
+            match self.server.poll_request_timeout(WATCH_INTERVAL)? {
+                Some(req) => self.handle(req)?,
+                None => self.check_source_change()?,
+            }
+            return Ok(());
+        }
         let req = match self.server.poll_request()? {
             Some(req) => req,
             None => bail!("no request"),
         };
+        self.handle(req)
+    }
 
+    /// Respond to the request. Requests that do not run the program keep the
+    /// current status, thus the program keeps running if it is running.
+    fn handle(&mut self, req: Request) -> anyhow::Result<()> {
         if let Command::Disconnect(_) = req.command {
+            self.server.respond(req.success(ResponseBody::Disconnect))?;
             bail!("disconnect command received");
         }
 
@@ -411,7 +1072,9 @@ impl<R: Read, W: Write> DebugServer<R, W> {
 
         let rsp = match self.handle_request(req) {
             Ok((rsp, next_status)) => {
-                self.status = next_status;
+                if let ServerStatus::RunProg(_) = next_status {
+                    self.status = next_status;
+                }
                 rsp
             }
             Err(e) => {
@@ -438,21 +1101,57 @@ impl<R: Read, W: Write> DebugServer<R, W> {
                     thread_id: Some(THREAD_ID),
                     preserve_focus_hint: Some(false),
                     text: None,
-                    all_threads_stopped: None,
+                    all_threads_stopped: Some(true),
+                    hit_breakpoint_ids: None,
+                }))?;
+            self.status = ServerStatus::ServeReq;
+            return Ok(());
+        }
+        if kind == RunProgKind::Pause {
+            self.server
+                .send_event(Event::Stopped(events::StoppedEventBody {
+                    reason: types::StoppedEventReason::Pause,
+                    description: Some("Paused".to_string()),
+                    thread_id: Some(THREAD_ID),
+                    preserve_focus_hint: Some(false),
+                    text: None,
+                    all_threads_stopped: Some(true),
                     hit_breakpoint_ids: None,
                 }))?;
             self.status = ServerStatus::ServeReq;
             return Ok(());
         }
+        if kind == RunProgKind::Terminate {
+            tracing::info!("program terminated by client");
+            self.server.send_event(Event::Terminated(None))?;
+            self.status = ServerStatus::ServeReq;
+            return Ok(());
+        }
 
         let inner = self
             .inner
             .as_mut()
             .ok_or(anyhow::anyhow!("program data not initialized"))?;
-        let sim = &mut inner.sim;
 
-        // start the simulation loop
-        loop {
+        // Simulate a slice of cycles. If the program does not stop, the status
+        // is unchanged and the loop continues after pending requests are served.
+        for _ in 0..CYCLES_PER_SLICE {
+            let sim = &mut inner.sim;
+            if let Some(msg) = sim.failed_assertion() {
+                tracing::info!("{msg}");
+                self.server
+                    .send_event(Event::Stopped(events::StoppedEventBody {
+                        reason: types::StoppedEventReason::Exception,
+                        description: Some("Assertion failed".to_string()),
+                        thread_id: Some(THREAD_ID),
+                        preserve_focus_hint: None,
+                        text: Some(msg),
+                        all_threads_stopped: Some(true),
+                        hit_breakpoint_ids: None,
+                    }))?;
+                self.status = ServerStatus::ServeReq;
+                break;
+            }
             if sim.is_terminate() {
                 tracing::info!("program terminated");
                 self.server
@@ -466,7 +1165,7 @@ impl<R: Read, W: Write> DebugServer<R, W> {
                             sim.program_counter(),
                             sim.cycle_count()
                         )),
-                        all_threads_stopped: None,
+                        all_threads_stopped: Some(true),
                         hit_breakpoint_ids: None,
                     }))?;
                 self.status = ServerStatus::ServeReq;
@@ -477,13 +1176,24 @@ impl<R: Read, W: Write> DebugServer<R, W> {
             sim.initiate_next_cycle();
             sim.propagate_signals();
 
+            let retired = inner.retire();
+            let sim = &inner.sim;
             let pc = sim.program_counter();
 
+            // In instruction stepping mode a breakpoint is hit when the
+            // instructions before it have retired, so that the program stops
+            // before the line is executed.
+            let bp_addr = match inner.stepping {
+                Stepping::Instruction => retired.and_then(|_| inner.next_to_retire()),
+                Stepping::Cycle => Some(pc),
+            };
             if let Some(bp) = self.breakpoints.iter().find(|bp| {
-                let Some(bp_ln) = bp.line else { return false };
+                let (Some(bp_ln), Some(addr)) = (bp.line, bp_addr) else {
+                    return false;
+                };
                 inner
                     .source_info
-                    .get_line_number_by_addr(pc)
+                    .get_line_number_by_addr(addr)
                     .map(|ln| ln == bp_ln)
                     .unwrap_or(false)
             }) {
@@ -496,14 +1206,77 @@ impl<R: Read, W: Write> DebugServer<R, W> {
                         thread_id: Some(THREAD_ID),
                         preserve_focus_hint: Some(false),
                         text: Some(format!("pc = {pc:#x}, cycle count = {}", sim.cycle_count())),
-                        all_threads_stopped: None,
+                        all_threads_stopped: Some(true),
                         hit_breakpoint_ids: Some(vec![bp_id]),
                     }))?;
                 self.status = ServerStatus::ServeReq;
                 break;
             }
 
-            if kind == RunProgKind::SingleStep {
+            if let Some((id, addr)) = self
+                .instruction_breakpoints
+                .iter()
+                .find(|(_, addr)| Some(*addr) == bp_addr)
+            {
+                tracing::trace!("hit instruction breakpoint: addr = {addr:#x}");
+                self.server
+                    .send_event(Event::Stopped(events::StoppedEventBody {
+                        reason: types::StoppedEventReason::Instruction,
+                        description: Some("Stop at instruction breakpoint".to_string()),
+                        thread_id: Some(THREAD_ID),
+                        preserve_focus_hint: Some(false),
+                        text: Some(format!("pc = {pc:#x}, cycle count = {}", sim.cycle_count())),
+                        all_threads_stopped: Some(true),
+                        hit_breakpoint_ids: Some(vec![*id]),
+                    }))?;
+                self.status = ServerStatus::ServeReq;
+                break;
+            }
+
+            let accesses = sim.data_accesses();
+            let hit_data_bps: Vec<_> = self
+                .data_breakpoints
+                .iter()
+                .filter(|bp| {
+                    accesses
+                        .iter()
+                        .any(|access| bp.target.matches(access, &bp.access_type))
+                })
+                .collect();
+            if !hit_data_bps.is_empty() {
+                tracing::trace!("hit data breakpoint: {:?}", accesses);
+                self.server
+                    .send_event(Event::Stopped(events::StoppedEventBody {
+                        reason: types::StoppedEventReason::Data,
+                        description: Some(format!(
+                            "Stop at data breakpoint: {}",
+                            hit_data_bps
+                                .iter()
+                                .map(|bp| bp.target.describe())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )),
+                        thread_id: Some(THREAD_ID),
+                        preserve_focus_hint: Some(false),
+                        text: Some(format!("pc = {pc:#x}, cycle count = {}", sim.cycle_count())),
+                        all_threads_stopped: Some(true),
+                        hit_breakpoint_ids: Some(hit_data_bps.iter().map(|bp| bp.id).collect()),
+                    }))?;
+                self.status = ServerStatus::ServeReq;
+                break;
+            }
+
+            let step_done = match kind {
+                RunProgKind::Cycle => true,
+                RunProgKind::StepIn => retired.is_some(),
+                RunProgKind::StepOver(depth) => retired.is_some() && inner.call_depth <= depth,
+                RunProgKind::StepOut(depth) => retired.is_some() && inner.call_depth < depth,
+                RunProgKind::Run
+                | RunProgKind::InitialRun
+                | RunProgKind::Pause
+                | RunProgKind::Terminate => false,
+            };
+            if step_done {
                 self.server
                     .send_event(Event::Stopped(events::StoppedEventBody {
                         reason: types::StoppedEventReason::Step,
@@ -511,13 +1284,13 @@ impl<R: Read, W: Write> DebugServer<R, W> {
                         thread_id: Some(THREAD_ID),
                         preserve_focus_hint: Some(false),
                         text: Some(format!("pc = {pc:#x}, cycle count = {}", sim.cycle_count())),
-                        all_threads_stopped: None,
+                        all_threads_stopped: Some(true),
                         hit_breakpoint_ids: None,
                     }))?;
                 self.status = ServerStatus::ServeReq;
                 break;
             }
-            if sim.cycle_count() >= self.sim_opt.max_cpu_cycle {
+            if sim.cycle_count() >= inner.max_cpu_cycle {
                 self.server
                     .send_event(Event::Stopped(events::StoppedEventBody {
                         reason: types::StoppedEventReason::Step,
@@ -525,7 +1298,7 @@ impl<R: Read, W: Write> DebugServer<R, W> {
                         thread_id: Some(THREAD_ID),
                         preserve_focus_hint: Some(false),
                         text: Some(format!("pc = {pc:#x}, cycle count = {}", sim.cycle_count())),
-                        all_threads_stopped: None,
+                        all_threads_stopped: Some(true),
                         hit_breakpoint_ids: None,
                     }))?;
                 self.status = ServerStatus::ServeReq;
@@ -546,12 +1319,18 @@ impl<R: Read, W: Write> DebugServer<R, W> {
                 }
                 ServerStatus::RunProg(kind) => {
                     self.run_prog(kind)?;
+                    if let ServerStatus::RunProg(_) = self.status {
+                        // requests may arrive while the program is running
+                        if let Some(req) = self.server.try_request()? {
+                            self.handle(req)?;
+                        }
+                        self.check_source_change()?;
+                        continue;
+                    }
                     // After the program is stopped, we need to update the stage info
-                    let inner = self
-                        .inner
-                        .as_mut()
-                        .ok_or(anyhow::anyhow!("program data not initialized"))?;
-                    inner.stage_info = inner.sim.get_stage_info();
+                    if let Some(inner) = self.inner.as_mut() {
+                        inner.stage_info = inner.sim.get_stage_info();
+                    }
                 }
             }
         }
@@ -0,0 +1,631 @@
+//! End-to-end tests of the debug server. Each test drives a session through
+//! in-memory pipes (or the stdio of a `ydb` process) with a scripted sequence
+//! of DAP requests, and checks the responses and events sent back.
+
+use std::{
+    collections::VecDeque,
+    io::{Read, Write},
+    process::{Child, Command, Stdio},
+    sync::mpsc,
+    time::{Duration, SystemTime},
+};
+
+use serde_json::{json, Value};
+use y86_dbg::{DebugServer, SimOption};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+const PROGRAM: &str = r#"    irmovq stack, %rsp
+    irmovq $3, %rdi
+    call double
+    halt
+
+double: addq %rdi, %rdi
+    rrmovq %rdi, %rax
+    ret
+
+    .pos 0x100
+stack:
+"#;
+
+const LOOP_PROGRAM: &str = r#"    irmovq $1, %rax
+loop: addq %rax, %rbx
+    jmp loop
+"#;
+
+/// Read end of an in-memory pipe
+struct PipeReader {
+    rx: mpsc::Receiver<Vec<u8>>,
+    buf: VecDeque<u8>,
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.buf.is_empty() {
+            match self.rx.recv() {
+                Ok(data) => self.buf.extend(data),
+                // the write end is dropped
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = self.buf.len().min(buf.len());
+        for (dst, src) in buf.iter_mut().zip(self.buf.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+/// Write end of an in-memory pipe
+struct PipeWriter(mpsc::Sender<Vec<u8>>);
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .send(buf.to_vec())
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A scripted debug client talking to a server running on another thread, or
+/// in a `ydb --stdio` process.
+struct Client {
+    input: Box<dyn Write>,
+    output: mpsc::Receiver<Vec<u8>>,
+    buf: Vec<u8>,
+    seq: i64,
+    /// Events received while waiting for a response
+    events: VecDeque<Value>,
+    process: Option<Child>,
+    _source: tempfile::NamedTempFile,
+}
+
+impl Client {
+    /// Start a debug session, and launch the program after the initialize
+    /// handshake. Breakpoints can be set before calling
+    /// [`Client::configuration_done`].
+    fn launch(program: &str, launch_args: Value) -> Self {
+        let (in_tx, in_rx) = mpsc::channel();
+        let (out_tx, out_rx) = mpsc::channel();
+        std::thread::Builder::new()
+            .stack_size(32 << 20)
+            .spawn(move || {
+                let reader = PipeReader {
+                    rx: in_rx,
+                    buf: VecDeque::new(),
+                };
+                let option = SimOption {
+                    arch: "seq_std".to_string(),
+                    max_cpu_cycle: 50000,
+                };
+                let _ = DebugServer::new(reader, PipeWriter(out_tx), option).start();
+            })
+            .unwrap();
+        Self::start(
+            Box::new(PipeWriter(in_tx)),
+            out_rx,
+            None,
+            program,
+            launch_args,
+        )
+    }
+
+    /// Like [`Client::launch`], but the session is served by a `ydb --stdio`
+    /// process.
+    fn launch_stdio(program: &str, launch_args: Value) -> Self {
+        let mut process = Command::new(env!("CARGO_BIN_EXE_ydb"))
+            .args(["--stdio", "--arch", "seq_std"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdout = process.stdout.take().unwrap();
+        let (out_tx, out_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buf = [0; 4096];
+            while let Ok(n @ 1..) = stdout.read(&mut buf) {
+                if out_tx.send(buf[..n].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+        let input = process.stdin.take().unwrap();
+        Self::start(Box::new(input), out_rx, Some(process), program, launch_args)
+    }
+
+    fn start(
+        input: Box<dyn Write>,
+        output: mpsc::Receiver<Vec<u8>>,
+        process: Option<Child>,
+        program: &str,
+        launch_args: Value,
+    ) -> Self {
+        let mut source = tempfile::Builder::new().suffix(".ys").tempfile().unwrap();
+        source.write_all(program.as_bytes()).unwrap();
+
+        let mut client = Self {
+            input,
+            output,
+            buf: Vec::new(),
+            seq: 0,
+            events: VecDeque::new(),
+            process,
+            _source: source,
+        };
+
+        let caps = client.request("initialize", json!({ "adapterID": "y86" }));
+        assert_eq!(caps["supportsConfigurationDoneRequest"], true);
+        assert_eq!(caps["supportsDisassembleRequest"], true);
+        client.event("initialized");
+
+        let mut args = json!({ "program": client._source.path() });
+        if let (Some(args), Value::Object(extra)) = (args.as_object_mut(), launch_args) {
+            args.extend(extra);
+        }
+        client.request("launch", args);
+        client
+    }
+
+    fn configuration_done(&mut self) {
+        self.request("configurationDone", Value::Null);
+    }
+
+    fn send(&mut self, command: &str, arguments: Value) -> i64 {
+        self.seq += 1;
+        let mut msg = json!({
+            "seq": self.seq,
+            "type": "request",
+            "command": command,
+        });
+        // requests without arguments must omit the field
+        if !arguments.is_null() {
+            msg["arguments"] = arguments;
+        }
+        let msg = msg.to_string();
+        write!(self.input, "Content-Length: {}\r\n\r\n{}", msg.len(), msg).unwrap();
+        self.seq
+    }
+
+    /// Receive the next message sent by the server.
+    fn recv(&mut self) -> Value {
+        loop {
+            if let Some(msg) = self.parse_message() {
+                return msg;
+            }
+            let data = self
+                .output
+                .recv_timeout(TIMEOUT)
+                .expect("timeout waiting for the server");
+            self.buf.extend(data);
+        }
+    }
+
+    fn parse_message(&mut self) -> Option<Value> {
+        let text = std::str::from_utf8(&self.buf).ok()?;
+        let start = text.find("Content-Length: ")?;
+        let header_end = start + text[start..].find("\r\n\r\n")?;
+        let len: usize = text[start + 16..header_end].trim().parse().unwrap();
+        let body_start = header_end + 4;
+        if self.buf.len() < body_start + len {
+            return None;
+        }
+        let msg = serde_json::from_slice(&self.buf[body_start..body_start + len]).unwrap();
+        self.buf.drain(..body_start + len);
+        Some(msg)
+    }
+
+    /// Send a request and wait for its response.
+    fn response(&mut self, command: &str, arguments: Value) -> Value {
+        let seq = self.send(command, arguments);
+        loop {
+            let msg = self.recv();
+            match msg["type"].as_str() {
+                Some("event") => self.events.push_back(msg),
+                Some("response") if msg["request_seq"] == seq => return msg,
+                _ => panic!("unexpected message {msg}"),
+            }
+        }
+    }
+
+    /// Send a request, check that it succeeds and return the response body.
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        let resp = self.response(command, arguments);
+        assert_eq!(resp["success"], true, "{command} failed: {resp}");
+        resp["body"].clone()
+    }
+
+    /// Wait for an event and return its body. Other events before it are
+    /// discarded.
+    fn event(&mut self, name: &str) -> Value {
+        loop {
+            let msg = match self.events.pop_front() {
+                Some(msg) => msg,
+                None => self.recv(),
+            };
+            if msg["type"] == "event" && msg["event"] == name {
+                return msg["body"].clone();
+            }
+        }
+    }
+
+    fn stopped(&mut self) -> Value {
+        let body = self.event("stopped");
+        assert_eq!(body["threadId"], 1);
+        assert_eq!(body["allThreadsStopped"], true);
+        body
+    }
+
+    /// Line of the top stack frame
+    fn line(&mut self) -> i64 {
+        let body = self.request("stackTrace", json!({ "threadId": 1 }));
+        body["stackFrames"][0]["line"].as_i64().unwrap()
+    }
+
+    fn register(&mut self, name: &str) -> String {
+        let body = self.request("variables", json!({ "variablesReference": 1 }));
+        let vars = body["variables"].as_array().unwrap();
+        let var = vars.iter().find(|v| v["name"] == name).unwrap();
+        var["value"].as_str().unwrap().to_string()
+    }
+
+    fn step(&mut self, command: &str) -> i64 {
+        self.request(command, json!({ "threadId": 1 }));
+        assert_eq!(self.stopped()["reason"], "step");
+        self.line()
+    }
+
+    fn disconnect(mut self) {
+        self.request("disconnect", json!({}));
+        if let Some(mut process) = self.process.take() {
+            // the session ends with the input
+            self.input = Box::new(std::io::sink());
+            assert!(process.wait().unwrap().success());
+        }
+    }
+}
+
+#[test]
+fn test_launch_and_disconnect() {
+    let mut client = Client::launch(PROGRAM, json!({}));
+    client.configuration_done();
+    assert_eq!(client.stopped()["reason"], "entry");
+
+    let body = client.request("threads", Value::Null);
+    assert_eq!(body["threads"], json!([{ "id": 1, "name": "main" }]));
+    assert_eq!(client.line(), 1);
+
+    let body = client.request("scopes", json!({ "frameId": 1 }));
+    let scopes = body["scopes"].as_array().unwrap();
+    assert_eq!(scopes[0]["name"], "Registers");
+    assert_eq!(scopes[0]["variablesReference"], 1);
+    assert_eq!(client.register("RSP"), "0x0");
+
+    client.disconnect();
+}
+
+#[test]
+fn test_stdio() {
+    let mut client = Client::launch_stdio(PROGRAM, json!({}));
+    client.configuration_done();
+    assert_eq!(client.stopped()["reason"], "entry");
+    assert_eq!(client.step("next"), 2);
+    assert_eq!(client.register("RSP"), "0x100");
+
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped()["description"], "Pause on termination");
+    assert_eq!(client.register("RAX"), "0x6");
+    client.disconnect();
+}
+
+#[test]
+fn test_breakpoints() {
+    let mut client = Client::launch(PROGRAM, json!({}));
+    let body = client.request(
+        "setBreakpoints",
+        json!({
+            "source": { "path": client._source.path() },
+            "breakpoints": [{ "line": 6 }, { "line": 5 }],
+        }),
+    );
+    let bps = body["breakpoints"].as_array().unwrap();
+    assert_eq!(bps[0]["verified"], true);
+    assert_eq!(bps[0]["line"], 6);
+    // empty line
+    assert_eq!(bps[1]["verified"], false);
+    let bp_id = bps[0]["id"].clone();
+
+    client.configuration_done();
+    assert_eq!(client.stopped()["reason"], "entry");
+
+    client.request("continue", json!({ "threadId": 1 }));
+    let body = client.stopped();
+    assert_eq!(body["reason"], "breakpoint");
+    assert_eq!(body["hitBreakpointIds"], json!([bp_id]));
+    // stop before the line is executed
+    assert_eq!(client.line(), 6);
+    assert_eq!(client.register("RDI"), "0x3");
+    assert_eq!(client.register("RAX"), "0x0");
+
+    // run to the end of the program
+    client.request("continue", json!({ "threadId": 1 }));
+    let body = client.stopped();
+    assert_eq!(body["reason"], "pause");
+    assert_eq!(body["description"], "Pause on termination");
+    assert_eq!(client.register("RAX"), "0x6");
+
+    client.disconnect();
+}
+
+#[test]
+fn test_stepping() {
+    check_stepping("seq_std");
+}
+
+#[test]
+fn test_stepping_pipeline() {
+    check_stepping("pipe_std");
+}
+
+/// Step through [`PROGRAM`]. The current location is the next instruction to
+/// retire, whatever the architecture is.
+fn check_stepping(arch: &str) {
+    let mut client = Client::launch(PROGRAM, json!({ "arch": arch }));
+    client.configuration_done();
+    assert_eq!(client.stopped()["reason"], "entry");
+
+    assert_eq!(client.step("next"), 2);
+    assert_eq!(client.register("RSP"), "0x100");
+    assert_eq!(client.register("RDI"), "0x0");
+    assert_eq!(client.step("next"), 3);
+    // step over the function body
+    assert_eq!(client.step("next"), 4);
+    assert_eq!(client.register("RAX"), "0x6");
+
+    client.request("restart", json!({}));
+    assert_eq!(client.stopped()["reason"], "entry");
+    assert_eq!(client.step("next"), 2);
+    assert_eq!(client.step("stepIn"), 3);
+    assert_eq!(client.step("stepIn"), 6);
+    assert_eq!(client.register("RDI"), "0x3");
+    assert_eq!(client.step("stepIn"), 7);
+    assert_eq!(client.register("RDI"), "0x6");
+    // leave the function
+    assert_eq!(client.step("stepOut"), 4);
+    assert_eq!(client.register("RAX"), "0x6");
+
+    client.disconnect();
+}
+
+#[test]
+fn test_stage_threads() {
+    let mut client = Client::launch(
+        PROGRAM,
+        json!({ "arch": "pipe_std", "stageThreads": true, "stopOnEntry": false }),
+    );
+    client.request(
+        "setBreakpoints",
+        json!({
+            "source": { "path": client._source.path() },
+            "breakpoints": [{ "line": 6 }],
+        }),
+    );
+    client.configuration_done();
+    assert_eq!(client.stopped()["reason"], "breakpoint");
+
+    // one thread for each stage, named after the instruction in it
+    let body = client.request("threads", Value::Null);
+    let names: Vec<_> = body["threads"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "F: halt",
+            "D: ret",
+            "E: rrmovq %rdi, %rax",
+            "M: double: addq %rdi, %rdi",
+            "W: call double",
+        ]
+    );
+
+    // each thread has a single frame at the instruction in its stage
+    let mut lines = Vec::new();
+    for id in 1..=5 {
+        let body = client.request("stackTrace", json!({ "threadId": id }));
+        let frames = body["stackFrames"].as_array().unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0]["id"], id);
+        lines.push(frames[0]["line"].as_i64().unwrap());
+    }
+    // the halt fetched after `ret` is beyond the source
+    assert_eq!(lines, [0, 8, 7, 6, 3]);
+    assert_eq!(
+        client.response("stackTrace", json!({ "threadId": 6 }))["success"],
+        false
+    );
+
+    client.disconnect();
+}
+
+#[test]
+fn test_return_out_of_memory() {
+    let program = r#"    irmovq $0x7fffffff, %rax
+    pushq %rax
+    ret
+"#;
+    let mut client = Client::launch(program, json!({ "stopOnEntry": false }));
+    client.configuration_done();
+    let body = client.stopped();
+    assert_eq!(body["reason"], "pause");
+    assert_eq!(body["description"], "Pause on termination");
+
+    client.disconnect();
+}
+
+#[test]
+fn test_evaluate_and_data_breakpoints() {
+    let mut client = Client::launch(PROGRAM, json!({ "registers": { "rbx": 7 } }));
+    client.configuration_done();
+    assert_eq!(client.stopped()["reason"], "entry");
+
+    let body = client.request(
+        "evaluate",
+        json!({ "expression": "%rbx * 2 + stack", "context": "repl" }),
+    );
+    assert_eq!(body["result"], "0x10e (270)");
+    let resp = client.response("evaluate", json!({ "expression": "%foo" }));
+    assert_eq!(resp["success"], false);
+
+    let body = client.request(
+        "dataBreakpointInfo",
+        json!({ "name": "RAX", "variablesReference": 1 }),
+    );
+    assert_eq!(body["dataId"], "reg:RAX");
+    // out of the memory
+    let body = client.request(
+        "dataBreakpointInfo",
+        json!({ "name": "0xffffffffffffffff" }),
+    );
+    assert_eq!(body["dataId"], Value::Null);
+    let body = client.request(
+        "setDataBreakpoints",
+        json!({ "breakpoints": [{ "dataId": "mem:0xffffffffffffffff" }] }),
+    );
+    assert_eq!(body["breakpoints"][0]["verified"], false);
+    let body = client.request(
+        "setDataBreakpoints",
+        json!({ "breakpoints": [{ "dataId": "reg:RAX", "accessType": "write" }] }),
+    );
+    assert_eq!(body["breakpoints"][0]["verified"], true);
+    let bp_id = body["breakpoints"][0]["id"].clone();
+
+    client.request("continue", json!({ "threadId": 1 }));
+    let body = client.stopped();
+    assert_eq!(body["reason"], "data");
+    assert_eq!(body["hitBreakpointIds"], json!([bp_id]));
+    // stop after `rrmovq %rdi, %rax` is executed
+    assert_eq!(client.line(), 8);
+    let body = client.request("evaluate", json!({ "expression": "%rax" }));
+    assert_eq!(body["result"], "0x6 (6)");
+
+    client.disconnect();
+}
+
+#[test]
+fn test_disassemble() {
+    let mut client = Client::launch(PROGRAM, json!({}));
+    client.configuration_done();
+    assert_eq!(client.stopped()["reason"], "entry");
+
+    let body = client.request(
+        "disassemble",
+        json!({ "memoryReference": "0x0", "instructionCount": 3 }),
+    );
+    let insts = body["instructions"].as_array().unwrap();
+    assert_eq!(insts.len(), 3);
+    assert_eq!(insts[0]["address"], "0x0");
+    assert_eq!(insts[0]["instruction"], "irmovq $0x100, %rsp");
+    assert_eq!(insts[0]["line"], 1);
+    assert_eq!(insts[2]["instruction"], "call 0x1e");
+
+    // nothing before address 0 or past the end of the memory
+    let body = client.request(
+        "disassemble",
+        json!({ "memoryReference": "0x0", "instructionOffset": -2, "instructionCount": 3 }),
+    );
+    let insts = body["instructions"].as_array().unwrap();
+    assert_eq!(insts.len(), 1);
+    assert_eq!(insts[0]["address"], "0x0");
+    let body = client.request(
+        "disassemble",
+        json!({ "memoryReference": "0xffff", "instructionCount": 3 }),
+    );
+    let insts = body["instructions"].as_array().unwrap();
+    assert_eq!(insts.len(), 1);
+    assert_eq!(insts[0]["address"], "0xffff");
+
+    client.disconnect();
+}
+
+#[test]
+fn test_watch() {
+    let mut client = Client::launch(PROGRAM, json!({ "watch": true }));
+    client.request(
+        "setBreakpoints",
+        json!({
+            "source": { "path": client._source.path() },
+            "breakpoints": [{ "line": 6 }],
+        }),
+    );
+    client.configuration_done();
+    assert_eq!(client.stopped()["reason"], "entry");
+
+    // make sure the modification time changes
+    let mut mtime = SystemTime::now();
+    let mut edit = |client: &Client, source: &str| {
+        let path = client._source.path();
+        std::fs::write(path, source).unwrap();
+        mtime += Duration::from_secs(10);
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(mtime).unwrap();
+    };
+
+    // the old program is kept if the new source fails to assemble
+    edit(&client, "    foo %rax\n");
+    let output = client.event("output")["output"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(output.starts_with("fail to reload program"), "{output}");
+    assert_eq!(client.step("next"), 2);
+
+    edit(&client, &PROGRAM.replace("$3", "$5"));
+    let body = client.event("breakpoint");
+    assert_eq!(body["reason"], "changed");
+    assert_eq!(body["breakpoint"]["line"], 6);
+    assert_eq!(body["breakpoint"]["verified"], true);
+    let output = client.event("output")["output"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(output.ends_with("changed, restarting\n"), "{output}");
+    assert_eq!(client.stopped()["reason"], "entry");
+    assert_eq!(client.line(), 1);
+
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped()["reason"], "breakpoint");
+    assert_eq!(client.register("RDI"), "0x5");
+
+    client.disconnect();
+}
+
+#[test]
+fn test_pause_and_terminate() {
+    // without a cycle limit the program never stops by itself, so only the
+    // pause request can stop it
+    let mut client = Client::launch(
+        LOOP_PROGRAM,
+        json!({ "stopOnEntry": false, "maxCpuCycle": u64::MAX }),
+    );
+    client.configuration_done();
+
+    client.request("pause", json!({ "threadId": 1 }));
+    let body = client.stopped();
+    assert_eq!(body["reason"], "pause");
+    assert_eq!(body["description"], "Paused");
+    assert!(matches!(client.line(), 2 | 3));
+
+    client.request("continue", json!({ "threadId": 1 }));
+    client.request("terminate", json!({}));
+    client.event("terminated");
+
+    client.disconnect();
+}
@@ -22,4 +22,104 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_coverage() -> anyhow::Result<()> {
+        // pipe_std is too large for the stack of a test thread
+        std::thread::Builder::new()
+            .stack_size(32 << 20)
+            .spawn(coverage_of_pipe_std)?
+            .join()
+            .unwrap()
+    }
+
+    fn coverage_of_pipe_std() -> anyhow::Result<()> {
+        let tester = SimTester::new("pipe_std").unwrap();
+        tester.test_jm()?;
+        tester.test_isa(crate::asm::tests::RSUM_YS)?;
+        let coverage = tester.coverage();
+        let count = |signal: &str, case: usize| {
+            let switch = coverage.switches.iter().find(|s| s.signal == signal);
+            switch.unwrap().cases[case].count
+        };
+        // mispredicted branches and load/use hazards are exercised
+        assert!(count("f_pc", 0) > 0);
+        assert!(count("d_stall", 0) > 0);
+        // but no memory error
+        assert!(coverage
+            .untaken()
+            .any(|(s, c)| s == "m_stat" && c.source == "dmem.error : Adr"));
+        // constant signals are not branches
+        assert!(coverage.switches.iter().all(|s| s.signal != "f_bubble"));
+
+        // the cases are located in the embedded source of the module
+        let sim = crate::architectures::create_sim(
+            "pipe_std".to_string(),
+            crate::framework::MemData::init([0; crate::framework::MEM_SIZE]),
+            false,
+        )?;
+        let source: Vec<_> = sim.source().unwrap().lines().collect();
+        for switch in &coverage.switches {
+            assert!(switch.cases.iter().all(|c| c.line.is_some()));
+            // a condition may span several lines
+            let case = &switch.cases[0];
+            let line = source[case.line.unwrap() - 1];
+            let token = case.source.split_whitespace().next().unwrap();
+            assert!(line.contains(token), "{line}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_data_accesses() -> anyhow::Result<()> {
+        use crate::{
+            architectures::create_sim,
+            framework::{DataAccess, MemData},
+            isa::reg_code::RSP,
+        };
+
+        let src = r#"
+            irmovq $0x200, %rsp
+            pushq %rsp
+            halt
+        "#;
+        for arch in ["seq_std", "seq_plus_std", "pipe_std"] {
+            let a = crate::assemble(src, crate::AssembleOption::default())?;
+            let mut sim =
+                create_sim(arch.to_string(), MemData::init(a.obj.init_mem()), false).unwrap();
+            let mut accesses = Vec::new();
+            while !sim.is_terminate() {
+                sim.step();
+                accesses.extend(sim.data_accesses());
+            }
+            assert!(accesses.contains(&DataAccess::MemWrite(0x1f8)), "{arch}");
+            assert!(accesses.contains(&DataAccess::RegRead(RSP)), "{arch}");
+            assert!(accesses.contains(&DataAccess::RegWrite(RSP)), "{arch}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_retired_instructions() -> anyhow::Result<()> {
+        use crate::{architectures::create_sim, framework::MemData};
+
+        let mut traces = Vec::new();
+        for arch in ["seq_std", "seq_plus_std", "pipe_std"] {
+            let a = crate::assemble(crate::asm::tests::RSUM_YS, Default::default())?;
+            let mut sim =
+                create_sim(arch.to_string(), MemData::init(a.obj.init_mem()), false).unwrap();
+            let mut retired = Vec::new();
+            while !sim.is_terminate() {
+                sim.step();
+                retired.extend(sim.retired_instruction());
+            }
+            traces.push(retired);
+        }
+        assert!(!traces[0].is_empty());
+        assert_eq!(traces[0], traces[1]);
+        assert_eq!(traces[0], traces[2]);
+
+        Ok(())
+    }
 }
@@ -27,6 +27,46 @@ crate::define_stages! {
     }
 }
 
+// Datapath of this architecture, drawn with box-drawing characters. The
+// wires of each tunnel are located by `@tunnel(..)` below.
+const DATAPATH: &str = r#"
+                     ┏━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┓
+                     ┃      ┏━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┓┃
+W stat icode       valE   valM      dstE dstM              ┃┃
+   │     │           ┃      ┣━━━━━━━━│━━━━│━━━━━━━━━━━━━━━┓┃┃
+   │     ├───#Mem.##┄┃┄┄┄┄┄Data##    │    │               ┃┃┃
+   │     ├───Control┄┃┄┄┄┄┄memory    │    │               ┃┃┃
+   │     │           ┃  Addr┛  ┃     │    │               ┃┃┃
+   │     │           ┃  ┃ ┗━━━━┃━━━━━│━━━━│━━━━━━━━━━━━━━┓┃┃┃
+   │     │           ┗━━╋━━━━━━┃━━━━━│━━━━│━━━━━━━━━━━━━┓┃┃┃┃
+M stat icode    Cnd   valE   valA   dstE dstM           ┃┃┃┃┃
+   │     │       │      ┣━━━━━━┃━━━━━│━━━━│━━━━━━━━━━━━┓┃┃┃┃┃
+   │     │       CC─────ALU ┏━━┛     │    │            ┃┃┃┃┃┃
+   │     │          AluA┛ ┗━┃━━AluB  │    │            ┃┃┃┃┃┃
+   │     │           ┃┗━━━━━┫    ┃   │    │            ┃┃┃┃┃┃
+E stat icode   ifun valC  valA valB dstE dstM srcA srcB┃┃┃┃┃┃
+   │     │       │   ┃      ┃    ┃                     ┃┃┃┃┃┃
+   │     │       │   ┃  #######━###━━━━━━━━━━━━━━━━━━━━┛┃┃┃┃┃
+   │     │       │   ┃  #######━###━━━━━━━━━━━━━━━━━━━━━┛┃┃┃┃
+   │     │       │   ┃  Sel+Fwd━Fwd━━━━━━━━━━━━━━━━━━━━━━┃┛┃┃
+   │     │       │   ┃  ###A###━#B#━━━━━━━━━━━━━━━━━━━━━━┃━┫┃
+   │     │       │   ┃  #######━###━━━━━━━━━━━━━━━━━━━━━━┃━┃┫
+   │     │       │   ┃      ┃ ┃  ┃                       ┃ ┃┃
+   │     │       │   ┃      ┃ ┗Register━━━━━━━━━━━━━━━━━━┃━┫┃
+   │     │       │   ┃      ┗┓ ##file##━━━━━━━━━━━━━━━━━━┃━┃┛
+   │     │       │   ┗━━━━━┓ ┗━━━┓                       ┃ ┃
+D stat icode   ifun rA rB valC  valP                     ┃ ┃
+   │     │       │   │ │   ┣━━━━━┃━━━━━━━━━━Predict      ┃ ┃
+  Stat───┴───┐   │   │ │   ┃     ┣━━━━━━━━━━##PC###      ┃ ┃
+             Instruction━━━┛  ###PC####        ┃         ┃ ┃
+             ##memory###      increment        ┃         ┃ ┃
+                  ┣━━━━━━━━━━━━━━┛             ┃         ┃ ┃
+                Select━━━━━━━━━━━━━━━━━━━━━━━━━┃━━━━━━━━━┛ ┃
+                ##PC##━━━━━━━━━━━━━━━━━━━━━━━━━┃━━━━━━━━━━━┛
+F        predPC━┛                              ┃
+            ┗━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┛
+"#;
+
 sim_macro::hcl! {
 
 // Specify the CPU hardware devices set.
@@ -47,6 +87,10 @@ sim_macro::hcl! {
 // be terminated.
 #![termination = prog_term]
 
+// Specify the drawing of the datapath (optional). It is rendered by `ysim --tui`
+// and `ysim --datapath`.
+#![datapath = DATAPATH]
+
 // This attribute defines the identifiers for pipeline registers. For "F => f", the
 // identifier `f` is the short name in [`crate::define_stages`], and `F` can be
 // arbitrarily chosen.
@@ -63,13 +107,16 @@ use Stat::*;
 :==============================: Fetch Stage :================================:
 
 // What address should instruction be fetched at
+//
+// `#[tunnel(..)]` names the wire that carries the selected value. The tunnels
+// triggered in a cycle are lit up in the datapath of `ysim --tui`.
 u64 f_pc = [
     // Mispredicted branch. Fetch at incremented PC
-    M.icode == JX && !M.cnd : M.valA;
+    #[tunnel(M_valA)] M.icode == JX && !M.cnd : M.valA;
     // Completion of RET instruction
-    W.icode == RET : W.valM;
+    #[tunnel(W_valM)] W.icode == RET : W.valM;
     // Default: Use predicted value of PC (default to 0)
-     1 : F.pred_pc;
+    #[tunnel(F_predPC)] 1 : F.pred_pc;
 ];
 
 @set_input(imem, {
@@ -129,8 +176,8 @@ u8 f_rB = ialign.rB;
 
 // Predict next value of PC
 u64 f_pred_pc = [
-     f_icode in { JX, CALL } : f_valC;
-     1 : f_valP;
+    #[tunnel(f_valC)] f_icode in { JX, CALL } : f_valC;
+    #[tunnel(f_valP)] 1 : f_valP;
 ];
 
 @set_stage(f, {
@@ -182,22 +229,22 @@ u64 d_rvalB = reg_file.valB;
 // What should be the A value?
 // Forward into decode stage for valA
 u64 d_valA = [
-    D.icode in { CALL, JX } : D.valP; // Use incremented PC
-    d_srcA == e_dstE : e_valE; // Forward valE from execute
-    d_srcA == M.dstM : m_valM; // Forward valM from memory
-    d_srcA == M.dstE : M.valE; // Forward valE from memory
-    d_srcA == W.dstM : W.valM; // Forward valM from write back
-    d_srcA == W.dstE : W.valE; // Forward valE from write back
-    1 : d_rvalA; // Use value read from register file
+    #[tunnel(D_valP)] D.icode in { CALL, JX } : D.valP; // Use incremented PC
+    #[tunnel(e_valE)] d_srcA == e_dstE : e_valE; // Forward valE from execute
+    #[tunnel(m_valM)] d_srcA == M.dstM : m_valM; // Forward valM from memory
+    #[tunnel(M_valE)] d_srcA == M.dstE : M.valE; // Forward valE from memory
+    #[tunnel(W_valM)] d_srcA == W.dstM : W.valM; // Forward valM from write back
+    #[tunnel(W_valE)] d_srcA == W.dstE : W.valE; // Forward valE from write back
+    #[tunnel(d_rvalA)] 1 : d_rvalA; // Use value read from register file
 ];
 
 u64 d_valB = [
-    d_srcB == e_dstE : e_valE; // Forward valE from execute
-    d_srcB == M.dstM : m_valM; // Forward valM from memory
-    d_srcB == M.dstE : M.valE; // Forward valE from memory
-    d_srcB == W.dstM : W.valM; // Forward valM from write back
-    d_srcB == W.dstE : W.valE; // Forward valE from write back
-    1 : d_rvalB; // Use value read from register file
+    #[tunnel(e_valE)] d_srcB == e_dstE : e_valE; // Forward valE from execute
+    #[tunnel(m_valM)] d_srcB == M.dstM : m_valM; // Forward valM from memory
+    #[tunnel(M_valE)] d_srcB == M.dstE : M.valE; // Forward valE from memory
+    #[tunnel(W_valM)] d_srcB == W.dstM : W.valM; // Forward valM from write back
+    #[tunnel(W_valE)] d_srcB == W.dstE : W.valE; // Forward valE from write back
+    #[tunnel(d_rvalB)] 1 : d_rvalB; // Use value read from register file
 ];
 
 u64 d_valC = D.valC;
@@ -301,9 +348,9 @@ Stat e_stat = E.stat;
 
 // Select memory address
 u64 mem_addr = [
-    M.icode in { RMMOVQ, PUSHQ, CALL, MRMOVQ } : M.valE;
+    #[tunnel(M_valE)] M.icode in { RMMOVQ, PUSHQ, CALL, MRMOVQ } : M.valE;
     M.icode in { POPQ, RET } : M.valA;
-    // Other instructions don't need address
+    1 : 0; // Other instructions don't need address
 ];
 
 // Set read control signal
@@ -446,54 +493,21 @@ bool w_bubble = false;
     stall: w_stall,
     bubble: w_bubble,
 });
-}
 
-mod nofmt {
-    use super::*;
-    use crate::{
-        framework::PipeSim,
-        utils::{format_ctrl, format_icode},
-    };
-    impl PipeSim<Arch> {
-        // print state at the beginning of a cycle
-        pub fn print_state(&self) {
-            // For stage registers, outputs contains information for the following cycle
-
-            #[allow(non_snake_case)]
-            let PipeRegs {
-                f: _,
-                d: D,
-                e: E,
-                m: M,
-                w: W,
-            } = &self.cur_state;
-            let PipeRegs { f, d, e, m, w } = &self.nex_state;
-
-            println!(
-                r#"Stat    F {fstat}    D {dstat}    E {estat}    M {mstat}    W {wstat}
-icode   f {ficode} D {dicode} E {eicode} M {micode} W {wicode}
-Control F {fctrl:6} D {dctrl:6} E {ectrl:6} M {mctrl:6} W {wctrl:6}"#,
-                fstat = Aok,
-                dstat = D.stat,
-                estat = E.stat,
-                mstat = M.stat,
-                wstat = W.stat,
-                // stage control at the end of last cycle
-                // e.g. dctrl is computed in fetch stage. if dctrl is bubble,
-                // then in the next cycle, D.icode will be NOP.
-                // e. Controls are applied between cycles.
-                fctrl = format_ctrl(f.bubble, f.stall),
-                dctrl = format_ctrl(d.bubble, d.stall),
-                ectrl = format_ctrl(e.bubble, e.stall),
-                mctrl = format_ctrl(m.bubble, m.stall),
-                wctrl = format_ctrl(w.bubble, w.stall),
-                // ficode is actually computed value
-                ficode = format_icode(d.icode),
-                dicode = format_icode(D.icode),
-                eicode = format_icode(E.icode),
-                micode = format_icode(M.icode),
-                wicode = format_icode(W.icode),
-            );
-        }
-    }
+// Tunnel map. Each tunnel triggered by `#[tunnel(..)]` above is declared here. `from` is
+// the value it carries, and `to` lists every signal that selects the value
+// through it. `at` locates the wires of the tunnel in the datapath drawing by
+// (row, column), counted from the first line of the drawing.
+@tunnel(F_predPC, { from: F.pred_pc, to: [f_pc], at: [(34, 12)] });
+@tunnel(M_valA, { from: M.valA, to: [f_pc], at: [(7, 26)] });
+@tunnel(W_valM, { from: W.valM, to: [f_pc, d_valA, d_valB], at: [(1, 28)] });
+@tunnel(f_valC, { from: f_valC, to: [f_pred_pc], at: [(26, 27)] });
+@tunnel(f_valP, { from: f_valP, to: [f_pred_pc], at: [(26, 33)] });
+@tunnel(D_valP, { from: D.valP, to: [d_valA], at: [(21, 28)] });
+@tunnel(d_rvalA, { from: d_rvalA, to: [d_valA], at: [(21, 30)] });
+@tunnel(d_rvalB, { from: d_rvalB, to: [d_valB], at: [(21, 33)] });
+@tunnel(e_valE, { from: e_valE, to: [d_valA, d_valB], at: [(10, 24)] });
+@tunnel(m_valM, { from: m_valM, to: [d_valA, d_valB], at: [(3, 28)] });
+@tunnel(M_valE, { from: M.valE, to: [d_valA, d_valB, mem_addr], at: [(3, 21)] });
+@tunnel(W_valE, { from: W.valE, to: [d_valA, d_valB], at: [(0, 21)] });
 }
@@ -10,10 +10,41 @@ crate::define_stages! {
     }
 }
 
+// Datapath of this architecture, see `pipe_std` for details.
+const DATAPATH: &str = r#"
+                        valE   valA   valB   valC   valP   S.valC S.valM S.valP
+  ##S###valC━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┓
+  ######valM━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┃━━━━━━┓
+  ######valP━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┃━━━━━━┃━━━━━━┓
+                                                             ┃      ┃      ┃
+  ##Data##━━━━━━━━━━━━━━━━━━━━━━━┓                           ┃      ┃      ┃
+  ##mux###━━━━━━━━━━━━━━━━━━━━━━━┃━━━━━━━━━━━━━━━━━━━━┓      ┃      ┃      ┃
+  ##Addr##━━━━━━━━━━━━━━━━┓      ┃                    ┃      ┃      ┃      ┃
+  ##mux###━━━━━━━━━━━━━━━━┃━━━━━━┫                    ┃      ┃      ┃      ┃
+  Data memory             ┃      ┃                    ┃      ┃      ┃      ┃
+                          ┃      ┃                    ┃      ┃      ┃      ┃
+  ALU━━━━━━━━━━━━━━━━━━━━━┛      ┃                    ┃      ┃      ┃      ┃
+  ##aluA##━━━━━━━━━━━━━━━━━━━━━━━┫                    ┃      ┃      ┃      ┃
+  ##mux###━━━━━━━━━━━━━━━━━━━━━━━┃━━━━━━━━━━━━━┓      ┃      ┃      ┃      ┃
+  ##aluB##━━━━━━━━━━━━━━━━━━━━━━━┃━━━━━━┓      ┃      ┃      ┃      ┃      ┃
+                                 ┃      ┃      ┃      ┃      ┃      ┃      ┃
+  Register━━━━━━━━━━━━━━━━━━━━━━━┛      ┃      ┃      ┃      ┃      ┃      ┃
+  ##file##━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┛      ┃      ┃      ┃      ┃      ┃
+                                               ┃      ┃      ┃      ┃      ┃
+  Instruction━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┛      ┃      ┃      ┃      ┃
+  ##memory###                                         ┃      ┃      ┃      ┃
+  PC increment━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┛      ┃      ┃      ┃
+                                                             ┃      ┃      ┃
+    ####PC######━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┛      ┃      ┃
+    ####mux#####━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┛      ┃
+    ############━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┛
+"#;
+
 sim_macro::hcl! {
 #![hardware = crate::architectures::hardware_seq]
 #![program_counter = pc]
 #![termination = prog_term]
+#![datapath = DATAPATH]
 #![stage_alias(S => s)]
 
 use Stat::*;
@@ -23,13 +54,13 @@ use Stat::*;
 // What address should instruction be fetched at
 u64 pc = [
     // Call.  Use instruction constant
-    S.icode == CALL : S.valC;
+    #[tunnel(S_valC)] S.icode == CALL : S.valC;
     // Taken branch.  Use instruction constant
-    S.icode == JX && S.cnd : S.valC;
+    #[tunnel(S_valC)] S.icode == JX && S.cnd : S.valC;
     // Completion of RET instruction.  Use value from stack
-    S.icode == RET : S.valM;
+    #[tunnel(S_valM)] S.icode == RET : S.valM;
     // Default: Use incremented PC
-    true : S.valP;
+    #[tunnel(S_valP)] true : S.valP;
 ];
 
 @set_input(imem, {
@@ -114,19 +145,19 @@ u8 dstM = [
 
 // Select input A to ALU
 u64 aluA = [
-    icode in { CMOVX, OPQ } : reg_read.valA;
-    icode in { IRMOVQ, RMMOVQ, MRMOVQ } : ialign.valC;
+    #[tunnel(valA)] icode in { CMOVX, OPQ } : reg_read.valA;
+    #[tunnel(valC)] icode in { IRMOVQ, RMMOVQ, MRMOVQ } : ialign.valC;
     icode in { CALL, PUSHQ } : NEG_8;
     icode in { RET, POPQ } : 8;
-    // Other instructions don't need ALU
+    1 : 0; // Other instructions don't need ALU
 ];
 
 // Select input B to ALU
 u64 aluB = [
-    icode in { RMMOVQ, MRMOVQ, OPQ, CALL,
+    #[tunnel(valB)] icode in { RMMOVQ, MRMOVQ, OPQ, CALL,
               PUSHQ, RET, POPQ } : reg_read.valB;
     icode in { CMOVX, IRMOVQ } : 0;
-    // Other instructions don't need ALU
+    1 : 0; // Other instructions don't need ALU
 ];
 
 // Set the ALU function
@@ -173,18 +204,18 @@ bool mem_write = icode in { RMMOVQ, PUSHQ, CALL };
 
 // Select memory address
 u64 mem_addr = [
-    icode in { RMMOVQ, PUSHQ, CALL, MRMOVQ } : valE;
-    icode in { POPQ, RET } : reg_read.valA;
-    // Other instructions don't need address
+    #[tunnel(valE)] icode in { RMMOVQ, PUSHQ, CALL, MRMOVQ } : valE;
+    #[tunnel(valA)] icode in { POPQ, RET } : reg_read.valA;
+    1 : 0; // Other instructions don't need address
 ];
 
 // Select memory input data
 u64 mem_data = [
     // Value from register
-    icode in { RMMOVQ, PUSHQ } : reg_read.valA;
+    #[tunnel(valA)] icode in { RMMOVQ, PUSHQ } : reg_read.valA;
     // Return PC
-    icode == CALL : valP;
-    // Default: Don't write anything
+    #[tunnel(valP)] icode == CALL : valP;
+    1 : 0; // Default: Don't write anything
 ];
 
 @set_input(dmem, {
@@ -221,8 +252,14 @@ bool prog_term = stat in { Hlt, Adr, Ins };
     valM: valM,
 });
 
-}
 
-impl crate::framework::PipeSim<Arch> {
-    fn print_state(&self) {}
+// Tunnel map, see `pipe_std` for details.
+@tunnel(S_valC, { from: S.valC, to: [pc], at: [(1, 61)] });
+@tunnel(S_valM, { from: S.valM, to: [pc], at: [(2, 68)] });
+@tunnel(S_valP, { from: S.valP, to: [pc], at: [(3, 75)] });
+@tunnel(valA, { from: reg_read.valA, to: [aluA, mem_addr, mem_data], at: [(16, 33)] });
+@tunnel(valB, { from: reg_read.valB, to: [aluB], at: [(17, 40)] });
+@tunnel(valC, { from: ialign.valC, to: [aluA], at: [(19, 47)] });
+@tunnel(valE, { from: valE, to: [mem_addr], at: [(11, 26)] });
+@tunnel(valP, { from: valP, to: [mem_data], at: [(21, 54)] });
 }
@@ -7,6 +7,34 @@ crate::define_stages! {
     SEQstage s { pc: u64 = 0 }
 }
 
+// Datapath of this architecture, see `pipe_std` for details.
+const DATAPATH: &str = r#"
+                        valM   valE   valA   valB   valC   valP
+    ###New PC###━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┓
+    ####mux#####━━━━━━━━━━┓                           ┃
+    ############━━━━━━━━━━┃━━━━━━━━━━━━━━━━━━━━━━━━━━━┃━━━━━━┓
+                          ┃                           ┃      ┃
+  ##Data##━━━━━━━━━━━━━━━━┃━━━━━━━━━━━━━┓             ┃      ┃
+  ##mux###━━━━━━━━━━━━━━━━┃━━━━━━━━━━━━━┃━━━━━━━━━━━━━┃━━━━━━┫
+  ##Addr##━━━━━━━━━━━━━━━━┃━━━━━━┓      ┃             ┃      ┃
+  ##mux###━━━━━━━━━━━━━━━━┃━━━━━━┃━━━━━━┫             ┃      ┃
+  Data memory━━━━━━━━━━━━━┛      ┃      ┃             ┃      ┃
+                                 ┃      ┃             ┃      ┃
+  ALU━━━━━━━━━━━━━━━━━━━━━━━━━━━━┛      ┃             ┃      ┃
+  ##aluA##━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┫             ┃      ┃
+  ##mux###━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┃━━━━━━━━━━━━━┫      ┃
+  ##aluB##━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┃━━━━━━┓      ┃      ┃
+                                        ┃      ┃      ┃      ┃
+  Register━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┛      ┃      ┃      ┃
+  ##file##━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┛      ┃      ┃
+                                                      ┃      ┃
+  Instruction━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┛      ┃
+  ##memory###                                                ┃
+  PC increment━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┛
+
+  PC
+"#;
+
 sim_macro::hcl! {
 
 // Specify the CPU hardware devices set.
@@ -27,6 +55,9 @@ sim_macro::hcl! {
 // be terminated.
 #![termination = prog_term]
 
+// Specify the drawing of the datapath (optional).
+#![datapath = DATAPATH]
+
 // This attribute defines the identifiers for pipeline registers. For "F => f", the
 // identifier `f` is the short name in [`crate::define_stages`], and `F` can be
 // arbitrarily chosen.
@@ -125,19 +156,19 @@ u8 dstM = [
 
 // Select input A to ALU
 u64 aluA = [
-    icode in { CMOVX, OPQ } : reg_read.valA;
-    icode in { IRMOVQ, RMMOVQ, MRMOVQ } : ialign.valC;
+    #[tunnel(valA)] icode in { CMOVX, OPQ } : reg_read.valA;
+    #[tunnel(valC)] icode in { IRMOVQ, RMMOVQ, MRMOVQ } : ialign.valC;
     icode in { CALL, PUSHQ } : NEG_8;
     icode in { RET, POPQ } : 8;
-    // Other instructions don't need ALU
+    1 : 0; // Other instructions don't need ALU
 ];
 
 // Select input B to ALU
 u64 aluB = [
-    icode in { RMMOVQ, MRMOVQ, OPQ, CALL,
+    #[tunnel(valB)] icode in { RMMOVQ, MRMOVQ, OPQ, CALL,
               PUSHQ, RET, POPQ } : reg_read.valB;
     icode in { CMOVX, IRMOVQ } : 0;
-    // Other instructions don't need ALU
+    1 : 0; // Other instructions don't need ALU
 ];
 
 // Set the ALU function
@@ -184,18 +215,18 @@ bool mem_write = icode in { RMMOVQ, PUSHQ, CALL };
 
 // Select memory address
 u64 mem_addr = [
-    icode in { RMMOVQ, PUSHQ, CALL, MRMOVQ } : valE;
-    icode in { POPQ, RET } : reg_read.valA;
-    // Other instructions don't need address
+    #[tunnel(valE)] icode in { RMMOVQ, PUSHQ, CALL, MRMOVQ } : valE;
+    #[tunnel(valA)] icode in { POPQ, RET } : reg_read.valA;
+    1 : 0; // Other instructions don't need address
 ];
 
 // Select memory input data
 u64 mem_data = [
     // Value from register
-    icode in { RMMOVQ, PUSHQ } : reg_read.valA;
+    #[tunnel(valA)] icode in { RMMOVQ, PUSHQ } : reg_read.valA;
     // Return PC
-    icode == CALL : valP;
-    // Default: Don't write anything
+    #[tunnel(valP)] icode == CALL : valP;
+    1 : 0; // Default: Don't write anything
 ];
 
 @set_input(dmem, {
@@ -230,20 +261,24 @@ bool prog_term = stat in { Hlt, Adr, Ins };
 
 u64 new_pc = [
     // Call.  Use instruction constant
-    icode == CALL : ialign.valC;
+    #[tunnel(valC)] icode == CALL : ialign.valC;
     // Taken branch.  Use instruction constant
-    icode == JX && cnd : ialign.valC;
+    #[tunnel(valC)] icode == JX && cnd : ialign.valC;
     // Completion of RET instruction.  Use value from stack
-    icode == RET : valM;
+    #[tunnel(valM)] icode == RET : valM;
     // Default: Use incremented PC
-    true : valP;
+    #[tunnel(valP)] true : valP;
 ];
 
 @set_stage(s, {
     pc: new_pc,
 });
-}
 
-impl crate::framework::PipeSim<Arch> {
-    fn print_state(&self) {}
+// Tunnel map, see `pipe_std` for details.
+@tunnel(valA, { from: reg_read.valA, to: [aluA, mem_addr, mem_data], at: [(16, 40)] });
+@tunnel(valB, { from: reg_read.valB, to: [aluB], at: [(17, 47)] });
+@tunnel(valC, { from: ialign.valC, to: [aluA, new_pc], at: [(19, 54)] });
+@tunnel(valE, { from: valE, to: [mem_addr], at: [(11, 33)] });
+@tunnel(valM, { from: valM, to: [new_pc], at: [(9, 26)] });
+@tunnel(valP, { from: valP, to: [mem_data, new_pc], at: [(21, 61)] });
 }
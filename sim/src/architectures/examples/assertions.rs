@@ -0,0 +1,42 @@
+//! Invariants checked by `@assert(..)` in every cycle. The counter breaks its
+//! invariant in the third cycle, which stops the simulation.
+
+crate::define_stages! {
+    /// The whole cycle is a single stage.
+    SEQstage s { pc: u64 = 0 }
+}
+
+sim_macro::hcl! {
+#![hardware = crate::architectures::examples::expr_ops::hardware]
+#![program_counter = pc]
+#![termination = term]
+#![stage_alias(S => s)]
+
+u64 pc = S.pc + 1 -> s.pc;
+bool term = pc > 10;
+
+// the next state of a stage can be read by assertions
+@assert(s.pc == S.pc + 1, "the counter increases by one");
+@assert(pc < 3 || !ops.flag, "the counter stays below 3");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Arch;
+    use crate::framework::{CpuSim, MemData, PipeSim, MEM_SIZE};
+
+    #[test]
+    fn test_assertions() {
+        let mut sim = PipeSim::<Arch>::new(MemData::init([0; MEM_SIZE]), false);
+        sim.step();
+        sim.step();
+        assert_eq!(sim.failed_assertion(), None);
+        sim.step();
+        assert!(sim.is_terminate());
+        assert_eq!(
+            sim.failed_assertion().unwrap(),
+            "assertion `pc < 3 || !ops.flag` failed in cycle 3: the counter stays below 3\n    \
+             pc = 3\n    ops.flag = true"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Arithmetic, bitwise and ordering operators in HCL expressions. A single
+//! unit provides constant operands.
+
+/// Hardware of this architecture
+pub mod hardware {
+    use crate::{
+        define_units,
+        framework::{AccessLog, HardwareUnits, MemData},
+        isa::RegFile,
+    };
+
+    define_units! {
+        Operands ops {
+            .output(x: u64, y: u64, s: u8, flag: bool)
+            log: AccessLog
+        } {
+            *x = 0x1234;
+            *y = 3;
+            *s = 4;
+            *flag = true;
+        }
+    }
+
+    impl std::fmt::Display for Units {
+        fn fmt(&self, _: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            Ok(())
+        }
+    }
+
+    impl HardwareUnits for Units {
+        fn init(_: MemData) -> Self {
+            Self {
+                ops: Operands {
+                    log: AccessLog::default(),
+                },
+            }
+        }
+
+        fn register_file(&self) -> RegFile {
+            [0; 16]
+        }
+
+        fn set_register(&mut self, _: u8, _: u64) {}
+
+        fn access_log(&self) -> &AccessLog {
+            &self.ops.log
+        }
+    }
+}
+
+crate::define_stages! {
+    /// The whole cycle is a single stage.
+    SEQstage s { pc: u64 = 0 }
+}
+
+sim_macro::hcl! {
+#![hardware = crate::architectures::examples::expr_ops::hardware]
+#![program_counter = pc]
+#![termination = term]
+#![stage_alias(S => s)]
+
+u64 pc = S.pc + 1 -> s.pc;
+
+u64 sum = ops.x + 8 * ops.y;
+u64 wrapped = 0 - ops.y;
+u64 negated = -ops.y;
+u64 nibble = ops.x >> 4 & 0xf;
+u64 ones = ~ops.x ^ ops.x;
+u64 shifted = 1 << ops.s;
+bool ordered = ops.y < ops.x && ops.x <= 0x1234 && !(ops.y >= 4);
+// comparisons are signed
+bool signed = wrapped < ops.y && !(wrapped >= 0);
+// arithmetic wraps around at the width of the operands
+u8 narrow = (ops.s - 5) >> 4;
+u8 rotated = ops.s << 9;
+// `!` is logical on integers
+bool zero = !ops.s;
+
+// a branch target computed without a dedicated unit
+u64 target = [
+    ops.flag && ops.y != 0 : pc + ops.x;
+    1 : pc + 10;
+];
+
+bool term = pc > 2;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Arch;
+    use crate::framework::{CpuSim, MemData, PipeSim, MEM_SIZE};
+
+    #[test]
+    fn test_expr_ops() {
+        let mut sim = PipeSim::<Arch>::new(MemData::init([0; MEM_SIZE]), false);
+        sim.step();
+        assert_eq!(sim.signal("pc"), Some(1));
+        assert_eq!(sim.signal("sum"), Some(0x124c));
+        assert_eq!(sim.signal("wrapped"), Some(-3i64 as u64));
+        assert_eq!(sim.signal("negated"), Some(-3i64 as u64));
+        assert_eq!(sim.signal("nibble"), Some(3));
+        assert_eq!(sim.signal("ones"), Some(u64::MAX));
+        assert_eq!(sim.signal("shifted"), Some(16));
+        assert_eq!(sim.signal("ordered"), Some(1));
+        assert_eq!(sim.signal("signed"), Some(1));
+        assert_eq!(sim.signal("narrow"), Some(0xf));
+        assert_eq!(sim.signal("rotated"), Some(8));
+        assert_eq!(sim.signal("zero"), Some(0));
+        assert_eq!(sim.signal("target"), Some(0x1235));
+    }
+}
@@ -0,0 +1,4 @@
+//! Small architectures exercising features of the HCL language.
+
+mod assertions;
+mod expr_ops;
@@ -4,7 +4,7 @@ use std::{cell::RefCell, rc::Rc};
 
 use crate::{
     define_units,
-    framework::{HardwareUnits, MemData, MEM_SIZE},
+    framework::{AccessLog, DataAccess, HardwareUnits, MemData, MEM_SIZE},
     isa::{
         inst_code,
         reg_code::{self, *},
@@ -28,6 +28,8 @@ define_units! {
             /// (i.e. the address is out of the memory range)
             error: bool, icode: u8, ifun: u8, align: [u8; 9]
         )
+        // reading memory is much slower than other units
+        .latency(3)
         binary: MemData
     } {
         let binary: &[u8; MEM_SIZE] = &binary.read();
@@ -86,10 +88,16 @@ define_units! {
     RegisterFileRead reg_read {
         .input(srcA: u8, srcB: u8)
         .output(valA: u64, valB: u64)
-        state: Rc<RefCell<RegFile>>
+        state: Rc<RefCell<RegFile>>,
+        log: AccessLog
     } {
         // if RNONE, set to 0 for better debugging
         let state  = &mut state.borrow_mut();
+        for src in [srcA, srcB] {
+            if src != RNONE {
+                log.record(DataAccess::RegRead(src));
+            }
+        }
         *valA = if srcA != RNONE { state[srcA as usize] } else { 0 };
         *valB = if srcB != RNONE { state[srcB as usize] } else { 0 };
     }
@@ -97,15 +105,18 @@ define_units! {
     RegisterFileWrite reg_write {
         .input(dstE: u8, dstM: u8, valE: u64, valM: u64)
         .output()
-        state: Rc<RefCell<RegFile>>
+        state: Rc<RefCell<RegFile>>,
+        log: AccessLog
     } {
         let state  = &mut state.borrow_mut();
         if dstE != RNONE {
             tracing::info!("write back fron e: dstE = {}, valE = {:#x}", reg_code::name_of(dstE), valE);
+            log.record(DataAccess::RegWrite(dstE));
             state[dstE as usize] = valE;
         }
         if dstM != RNONE {
             tracing::info!("write back fron m: dstM = {}, valM = {:#x}", reg_code::name_of(dstM), valM);
+            log.record(DataAccess::RegWrite(dstM));
             state[dstM as usize] = valM;
         }
     }
@@ -150,9 +161,11 @@ define_units! {
             /// Indicate if the address is invalid.
             error: bool
         )
-        binary: MemData
+        .latency(3)
+        binary: MemData,
+        log: AccessLog
     } {
-        if addr + 8 >= MEM_SIZE as u64 {
+        if addr.saturating_add(8) >= MEM_SIZE as u64 {
             *dataout = 0;
             *error = true;
             return
@@ -160,10 +173,12 @@ define_units! {
         *error = false;
         if write {
             tracing::info!("write memory: addr = {:#x}, datain = {:#x}", addr, datain);
+            log.record(DataAccess::MemWrite(addr));
             let section: &mut [u8] = &mut binary.write()[(addr as usize)..];
             put_u64(section, datain);
             *dataout = 0;
         } else if read {
+            log.record(DataAccess::MemRead(addr));
             *dataout = get_u64(&binary.read()[(addr as usize)..]);
         }
     }
@@ -181,24 +196,46 @@ impl HardwareUnits for Units {
     /// Init CPU harewre with given memory.
     fn init(memory: MemData) -> Self {
         let reg = Rc::new(RefCell::new([0; 16]));
+        let log = AccessLog::default();
         Self {
             imem: InstructionMemory {
                 binary: memory.clone(),
             },
             ialign: Align {},
             pc_inc: PCIncrement {},
-            reg_read: RegisterFileRead { state: reg.clone() },
-            reg_write: RegisterFileWrite { state: reg.clone() },
+            reg_read: RegisterFileRead {
+                state: reg.clone(),
+                log: log.clone(),
+            },
+            reg_write: RegisterFileWrite {
+                state: reg.clone(),
+                log: log.clone(),
+            },
             alu: ArithmetcLogicUnit {},
             reg_cc: RegisterCC {
                 inner_cc: ConditionCode::default(),
             },
             cond: InstructionCondition {},
-            dmem: DataMemory { binary: memory },
+            dmem: DataMemory {
+                binary: memory,
+                log: log.clone(),
+            },
         }
     }
 
     fn register_file(&self) -> RegFile {
         *self.reg_read.state.borrow()
     }
+
+    fn set_register(&mut self, reg: u8, val: u64) {
+        self.reg_read.state.borrow_mut()[reg as usize] = val;
+    }
+
+    fn condition_code(&self) -> ConditionCode {
+        self.reg_cc.inner_cc
+    }
+
+    fn access_log(&self) -> &AccessLog {
+        &self.dmem.log
+    }
 }
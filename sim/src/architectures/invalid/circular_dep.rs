@@ -1,34 +1,82 @@
+//! A cycle through a unit. Cycles among signals are rejected by `hcl!`, but
+//! the ports of units are only known when the circuit is built.
+
+/// Hardware of this architecture
+pub mod hardware {
+    use crate::{
+        define_units,
+        framework::{AccessLog, HardwareUnits, MemData},
+        isa::RegFile,
+    };
+
+    define_units! {
+        Incrementer inc {
+            .input(a: u64)
+            .output(e: u64)
+            log: AccessLog
+        } {
+            *e = a + 1;
+        }
+    }
+
+    impl std::fmt::Display for Units {
+        fn fmt(&self, _: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            Ok(())
+        }
+    }
+
+    impl HardwareUnits for Units {
+        fn init(_: MemData) -> Self {
+            Self {
+                inc: Incrementer {
+                    log: AccessLog::default(),
+                },
+            }
+        }
+
+        fn register_file(&self) -> RegFile {
+            [0; 16]
+        }
+
+        fn set_register(&mut self, _: u8, _: u64) {}
+
+        fn access_log(&self) -> &AccessLog {
+            &self.inc.log
+        }
+    }
+}
+
 crate::define_stages! {
     /// The whole cycle is a single stage.
     SEQstage s { pc: u64 = 0 }
 }
 
 sim_macro::hcl! {
-#![hardware = crate::architectures::hardware_seq]
+#![hardware = crate::architectures::invalid::circular_dep::hardware]
 #![program_counter = a]
 #![termination = term]
 
 // Circular dependency is not allowed!
-u64 a = b;
+u64 a = b -> inc.a;
 
-u64 b = a;
+u64 b = inc.e;
 
 bool term = a == b;
 
 }
 
-impl crate::framework::PipeSim<Arch> {
-    fn print_state(&self) {}
-}
-
 #[cfg(test)]
 mod tests {
     use super::Arch;
     use crate::framework::CpuArch;
 
     #[test]
-    #[should_panic]
     fn test_invalid() {
-        Arch::build_circuit();
+        let err = Arch::build_circuit().err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "the computational graph of your CPU is not a DAG, found a cycle: \
+             a -> inc.a -> inc -> inc.e -> b -> a"
+        );
     }
 }
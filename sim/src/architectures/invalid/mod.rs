@@ -1,5 +1,5 @@
-//! Examples of invalid architectures. These architectures will fail on
-//! computational graph construction.
+//! Examples of invalid architectures. These architectures compile, but fail
+//! on computational graph construction.
 
 mod circular_dep;
 mod unused_unit_in;
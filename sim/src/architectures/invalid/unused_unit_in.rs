@@ -18,18 +18,14 @@ u64 aluA = S.pc -> alu.a;
 bool term = pc == 0;
 }
 
-impl crate::framework::PipeSim<Arch> {
-    fn print_state(&self) {}
-}
-
 #[cfg(test)]
 mod tests {
     use super::Arch;
     use crate::framework::CpuArch;
 
     #[test]
-    #[should_panic]
     fn test_invalid() {
-        Arch::build_circuit();
+        let err = Arch::build_circuit().err().unwrap();
+        assert_eq!(err.to_string(), "unit input `ialign.align` has no source");
     }
 }
@@ -4,12 +4,13 @@ pub mod hardware_seq;
 
 // Architectures
 mod builtin;
+mod examples;
 mod extra;
 mod invalid;
 
 pub use extra::ARCH_NAMES as EXTRA_ARCH_NAMES;
 
-use crate::framework::{CpuSim, MemData, PipeSim};
+use crate::framework::{CpuSim, MemData, PipeSim, MEM_SIZE};
 
 /// Get all architecture names
 pub fn arch_names() -> Vec<&'static str> {
@@ -18,11 +19,22 @@ pub fn arch_names() -> Vec<&'static str> {
     names
 }
 
-pub fn create_sim(kind: String, memory: MemData, tty_out: bool) -> Box<dyn CpuSim> {
-    match kind.as_str() {
+/// Check that the architecture exists. A path ending with `.hcl` is checked
+/// by loading the file, see [`crate::hcl`].
+pub fn check_arch(arch: &str) -> anyhow::Result<()> {
+    create_sim(arch.to_string(), MemData::init([0; MEM_SIZE]), false).map(|_| ())
+}
+
+/// Create the simulator of the architecture. `kind` is either the name of a
+/// builtin architecture or the path of a `.hcl` file. Fail if there is no such
+/// architecture or the file fails to load.
+pub fn create_sim(kind: String, memory: MemData, tty_out: bool) -> anyhow::Result<Box<dyn CpuSim>> {
+    Ok(match kind.as_str() {
+        path if crate::hcl::is_hcl_path(path) => crate::hcl::load(path, memory, tty_out)?,
         "seq_std" => Box::new(PipeSim::<builtin::seq_std::Arch>::new(memory, tty_out)),
         "seq_plus_std" => Box::new(PipeSim::<builtin::seq_plus_std::Arch>::new(memory, tty_out)),
         "pipe_std" => Box::new(PipeSim::<builtin::pipe_std::Arch>::new(memory, tty_out)),
-        _ => extra::create_sim(kind, memory, tty_out),
-    }
+        name if extra::ARCH_NAMES.contains(&name) => extra::create_sim(kind, memory, tty_out),
+        _ => anyhow::bail!("unknown architecture `{}`", kind),
+    })
 }
@@ -0,0 +1,67 @@
+use anyhow::{bail, Context, Result};
+use binutils::clap;
+use clap::{Parser, ValueEnum};
+use y86_sim::hcl2rs::translate;
+
+/// Output format of the translation
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Format {
+    /// Rust module to be put into `sim/src/architectures/extra`
+    Rs,
+    /// Runtime `.hcl` file to be loaded by `ysim -A`
+    Hcl,
+}
+
+/// Translate the HCL files of CS:APP (e.g. `seq-full.hcl`) into an
+/// architecture of this project.
+#[derive(Parser, Debug)]
+#[command(
+    author,
+    version,
+    long_about = None,
+    styles = binutils::get_styles(),
+    arg_required_else_help = true,
+)]
+struct Args {
+    /// Path to the CS:APP .hcl file
+    input: String,
+
+    /// Output filename (default is stdout)
+    #[arg(short = 'o', long)]
+    output: Option<String>,
+
+    /// Format of the output
+    #[arg(long, value_enum, default_value = "rs")]
+    format: Format,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let content = std::fs::read_to_string(&args.input)
+        .with_context(|| format!("could not read file `{}`", args.input))?;
+    let t = translate(&content).with_context(|| format!("translate {}", args.input))?;
+    let output = match args.format {
+        Format::Rs => t.rust_module(),
+        Format::Hcl => t.output.clone(),
+    };
+
+    match &args.output {
+        Some(path) => {
+            std::fs::write(path, &output)
+                .with_context(|| format!("could not write file `{}`", path))?;
+            eprintln!("writing to file `{}`", path);
+        }
+        None => print!("{}", output),
+    }
+    for problem in &t.problems {
+        eprintln!("warning: {}", problem);
+    }
+    if !t.problems.is_empty() {
+        bail!(
+            "{} construct(s) of `{}` can not be translated",
+            t.problems.len(),
+            args.input
+        );
+    }
+    Ok(())
+}
@@ -1,10 +1,13 @@
+use std::io::Write;
+
 use anyhow::{Context, Result};
 use binutils::{clap, verbose};
-use clap::{error::ErrorKind, CommandFactory, Parser};
+use clap::{error::ErrorKind, CommandFactory, Parser, ValueEnum};
 use y86_sim::{
-    architectures::{arch_names, create_sim},
+    architectures::{arch_names, check_arch, create_sim},
     assemble,
     framework::{MemData, MEM_SIZE},
+    repl::Repl,
     utils, AssembleOption,
 };
 
@@ -35,6 +38,18 @@ fn after_help() -> String {
     )
 }
 
+/// Output format of the computational graph
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum GraphFormat {
+    /// Interactive Sankey diagram
+    Html,
+    /// Graphviz DOT
+    Dot,
+    /// Mermaid flowchart
+    Mermaid,
+    Json,
+}
+
 /// Y86 pipeline simulator written in Rust
 #[derive(Parser, Debug)]
 #[command(
@@ -53,10 +68,44 @@ struct Args {
     #[arg(short = 'I', long)]
     info: bool,
 
-    /// Specify the pipeline architecture to run
+    /// Format of the computational graph generated by `-I`
+    #[arg(long, value_enum, default_value = "html", requires = "info")]
+    graph_format: GraphFormat,
+
+    /// Specify the pipeline architecture to run, either a builtin one or the
+    /// path of a `.hcl` file
     #[arg(short = 'A', long, default_value = "seq_std")]
     arch: Option<String>,
 
+    /// Check the architecture for likely mistakes, such as incomplete
+    /// switches, unused signals and stages that can stall and take a bubble
+    /// at the same time. Only unread unit outputs are found in a builtin
+    /// architecture
+    #[arg(long, conflicts_with = "info")]
+    check: bool,
+
+    /// Debug the program in a gdb-like prompt. Type `help` in the prompt for
+    /// the commands
+    #[arg(short = 'i', long, conflicts_with = "info")]
+    interactive: bool,
+
+    /// Visualize the pipeline in a full-screen terminal UI, stepping forward
+    /// and backward cycle by cycle
+    #[arg(long, conflicts_with_all = ["info", "interactive"])]
+    tui: bool,
+
+    /// Write the datapath diagram of each cycle to an HTML file, with the
+    /// active tunnels highlighted
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["info", "interactive", "tui"])]
+    datapath: Option<String>,
+
+    /// Write how often each case of each switch signal is taken to a file,
+    /// as HTML if FILE ends with `.html` and as plain text otherwise. The HTML
+    /// report highlights the cases in the `.hcl` file, or in the Rust module
+    /// of a compiled architecture
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["info", "interactive", "tui"])]
+    coverage: Option<String>,
+
     /// Limit the maximum number of CPU cycles to prevent infinite loop
     #[arg(long, default_value = "100000")]
     max_cpu_cycle: Option<u64>,
@@ -85,37 +134,127 @@ fn main() -> Result<()> {
     };
 
     let arch = args.arch.expect("architecture is required");
-    if !arch_names().contains(&arch.as_str()) {
+    if let Err(e) = check_arch(&arch) {
         let mut cmd = Args::command();
-        cmd.error(
-            ErrorKind::InvalidValue,
-            format!("unknown architecture `{}`", arch),
-        )
-        .exit();
+        cmd.error(ErrorKind::InvalidValue, format!("{:#}", e))
+            .exit();
     }
 
-    if args.info {
-        let empty_sim = create_sim(arch.clone(), MemData::init([0; MEM_SIZE]), false);
+    if args.check {
+        let lints = y86_sim::hcl::check(&arch)?;
+        for lint in &lints {
+            println!("warning: {}", lint);
+        }
+        if !lints.is_empty() {
+            anyhow::bail!("{} problem(s) found in `{}`", lints.len(), arch);
+        }
+        println!("no problems found in `{}`", arch);
+    } else if args.info {
+        let empty_sim = create_sim(arch.clone(), MemData::init([0; MEM_SIZE]), false)?;
 
         print!("{}", empty_sim);
 
-        utils::render_arch_dependency_graph(&arch, empty_sim.proporder())?;
+        let order = empty_sim.proporder();
+        // a `.hcl` file is named after its file stem
+        let name = std::path::Path::new(&arch)
+            .file_stem()
+            .map_or(arch.clone(), |s| s.to_string_lossy().to_string());
+        let (content, ext) = match args.graph_format {
+            GraphFormat::Html => return utils::render_arch_dependency_graph(&name, order),
+            GraphFormat::Dot => (order.to_dot(&name), "dot"),
+            GraphFormat::Mermaid => (order.to_mermaid(&name), "mmd"),
+            GraphFormat::Json => (order.to_json(&name), "json"),
+        };
+        let outpath = format!("{}_dependency_graph.{}", name, ext);
+        std::fs::write(&outpath, content)
+            .with_context(|| format!("could not write file `{}`", outpath))?;
+        println!("dependency graph is generated at: {}", outpath);
+    } else if args.tui {
+        let a = maybe_a.ok_or(anyhow::anyhow!("no input file"))?;
+        let max_cpu_cycle = args.max_cpu_cycle.expect("max_cpu_cycle is required");
+        y86_sim::tui::run(&arch, a, max_cpu_cycle)?;
+    } else if args.interactive {
+        let a = maybe_a.ok_or(anyhow::anyhow!("no input file"))?;
+        let max_cpu_cycle = args.max_cpu_cycle.expect("max_cpu_cycle is required");
+        let mut repl = Repl::new(&arch, a, max_cpu_cycle)?;
+        let mut stdout = std::io::stdout();
+        let mut lines = std::io::stdin().lines();
+        loop {
+            print!("(ysim) ");
+            stdout.flush()?;
+            let Some(line) = lines.next().transpose()? else {
+                println!();
+                break;
+            };
+            match repl.execute(&line, &mut stdout) {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) => println!("error: {:#}", e),
+            }
+        }
     } else {
         let a = maybe_a.ok_or(anyhow::anyhow!("no input file"))?;
         let mem = MemData::init(a.obj.init_mem());
-        let mut pipe = create_sim(arch, mem.clone(), true);
+        let mut pipe = create_sim(arch.clone(), mem.clone(), true)?;
+        let datapath = match &args.datapath {
+            Some(_) => Some(
+                pipe.datapath()
+                    .with_context(|| format!("no datapath drawing for `{}`", arch))?,
+            ),
+            None => None,
+        };
+        let mut cycles = datapath.map(|_| Vec::new());
 
         let max_cpu_cycle = args.max_cpu_cycle.expect("max_cpu_cycle is required");
         while !pipe.is_terminate() {
             pipe.step();
+            if let Some(cycles) = &mut cycles {
+                cycles.push(pipe.active_tunnels());
+            }
             if pipe.cycle_count() > max_cpu_cycle {
                 anyhow::bail!(
                     "exceed maximum CPU cycle limit (use --max-cpu-cycle to change the limit)"
                 );
             }
         }
+        if let Some(msg) = pipe.failed_assertion() {
+            anyhow::bail!(msg);
+        }
 
         utils::mem_diff(&a.obj.init_mem(), &mem.read());
+        println!(
+            "{} cycles, clock period {}, total time {}",
+            pipe.cycle_count(),
+            pipe.cycle_cost(),
+            pipe.cycle_count() * pipe.cycle_cost()
+        );
+        if let (Some(path), Some(datapath), Some(cycles)) = (&args.datapath, datapath, cycles) {
+            let title = format!("{} on {}", args.input.as_deref().unwrap_or_default(), arch);
+            std::fs::write(path, datapath.render_html(&title, &cycles))
+                .with_context(|| format!("could not write file `{}`", path))?;
+            println!("datapath diagram is generated at: {}", path);
+        }
+        if let Some(path) = &args.coverage {
+            let coverage = pipe.coverage();
+            let report = if path.ends_with(".html") {
+                let title = format!("{} on {}", args.input.as_deref().unwrap_or_default(), arch);
+                // compiled architectures embed the source of their module
+                let source = if y86_sim::hcl::is_hcl_path(&arch) {
+                    Some(
+                        std::fs::read_to_string(&arch)
+                            .with_context(|| format!("could not read file `{}`", arch))?,
+                    )
+                } else {
+                    pipe.source().map(str::to_string)
+                };
+                coverage.render_html(&title, source.as_deref())
+            } else {
+                coverage.render_text()
+            };
+            std::fs::write(path, report)
+                .with_context(|| format!("could not write file `{}`", path))?;
+            println!("coverage report is generated at: {}", path);
+        }
         // mem_print(&pipe.mem());
     }
     Ok(())
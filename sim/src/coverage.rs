@@ -0,0 +1,254 @@
+//! Branch coverage of HCL switches. Every case of a switch signal counts how
+//! often it is taken, i.e. it is the first case whose condition holds. A
+//! boolean signal defined by a non-constant expression, such as `d_stall`,
+//! counts as a switch with the cases `.. : true` and `1 : false`, so that the
+//! report shows whether stalls and bubbles are ever triggered.
+
+/// A case of a switch signal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseCoverage {
+    /// Source of the case, e.g. `imem.error : Adr`
+    pub source: String,
+    /// Line of the case in the source of the architecture, i.e. the `.hcl`
+    /// file or the Rust module with the `hcl!` block, if known
+    pub line: Option<usize>,
+    /// Number of cycles in which the case is taken
+    pub count: u64,
+}
+
+/// Switch signals given by (signal, (source, line) of the cases), see
+/// [`crate::framework::CpuArch::SWITCHES`].
+pub type Switches<'a> = [(&'a str, &'a [(&'a str, Option<usize>)])];
+
+/// The cases of a switch signal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwitchCoverage {
+    pub signal: String,
+    pub cases: Vec<CaseCoverage>,
+}
+
+/// Coverage of all switch signals of an architecture, in the order of the
+/// source. It is collected over a run by [`crate::framework::CpuSim`], and
+/// runs of several programs can be combined by [`Coverage::merge`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    pub switches: Vec<SwitchCoverage>,
+}
+
+impl Coverage {
+    /// Coverage of the switches, where no case is taken yet.
+    pub fn new(switches: &Switches) -> Self {
+        Self {
+            switches: switches
+                .iter()
+                .map(|(signal, cases)| SwitchCoverage {
+                    signal: signal.to_string(),
+                    cases: cases
+                        .iter()
+                        .map(|(source, line)| CaseCoverage {
+                            source: source.to_string(),
+                            line: *line,
+                            count: 0,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Count the cases taken in a cycle, given by (index of the switch,
+    /// index of the case).
+    pub fn record(&mut self, taken: &[(usize, usize)]) {
+        for &(switch, case) in taken {
+            self.switches[switch].cases[case].count += 1;
+        }
+    }
+
+    /// Add the counts of another run of the same architecture. Switches are
+    /// matched by signal name.
+    pub fn merge(&mut self, other: &Coverage) {
+        for switch in &other.switches {
+            match self.switches.iter_mut().find(|s| s.signal == switch.signal) {
+                Some(s) => {
+                    for (case, other) in s.cases.iter_mut().zip(&switch.cases) {
+                        case.count += other.count;
+                    }
+                }
+                None => self.switches.push(switch.clone()),
+            }
+        }
+    }
+
+    /// (taken, total) number of cases
+    pub fn summary(&self) -> (usize, usize) {
+        let cases = self.switches.iter().flat_map(|s| &s.cases);
+        (cases.clone().filter(|c| c.count > 0).count(), cases.count())
+    }
+
+    /// Cases that are never taken, with their signals
+    pub fn untaken(&self) -> impl Iterator<Item = (&str, &CaseCoverage)> {
+        self.switches.iter().flat_map(|s| {
+            s.cases
+                .iter()
+                .filter(|c| c.count == 0)
+                .map(|c| (s.signal.as_str(), c))
+        })
+    }
+
+    /// Plain text report. Each case is prefixed by the times it is taken, and
+    /// cases never taken are marked by `!`.
+    pub fn render_text(&self) -> String {
+        let mut out = String::new();
+        for switch in &self.switches {
+            let taken = switch.cases.iter().filter(|c| c.count > 0).count();
+            out += &format!(
+                "{} ({}/{} cases taken)\n",
+                switch.signal,
+                taken,
+                switch.cases.len()
+            );
+            for case in &switch.cases {
+                let mark = if case.count == 0 { '!' } else { ' ' };
+                let line = case.line.map(|l| format!("line {l}: ")).unwrap_or_default();
+                out += &format!("{mark} {:>8}  {line}{}\n", case.count, case.source);
+            }
+        }
+        let (taken, total) = self.summary();
+        out += &format!("{taken}/{total} cases taken\n");
+        out
+    }
+
+    /// HTML report, where cases never taken are highlighted. If the HCL
+    /// `source` is given, the cases are highlighted in the listing of the
+    /// source by their lines. Otherwise they are listed by signal.
+    pub fn render_html(&self, title: &str, source: Option<&str>) -> String {
+        let mut body = String::new();
+        match source {
+            Some(source) => {
+                body += "<table>\n";
+                for (i, text) in source.lines().enumerate() {
+                    let cases: Vec<_> = self
+                        .switches
+                        .iter()
+                        .flat_map(|s| &s.cases)
+                        .filter(|c| c.line == Some(i + 1))
+                        .collect();
+                    let count = cases.iter().map(|c| c.count).sum::<u64>();
+                    let (class, count) = if cases.is_empty() {
+                        ("", String::new())
+                    } else if cases.iter().any(|c| c.count == 0) {
+                        ("untaken", count.to_string())
+                    } else {
+                        ("taken", count.to_string())
+                    };
+                    body += &format!(
+                        "<tr class=\"{class}\"><td class=\"line\">{}</td>\
+                         <td class=\"count\">{count}</td><td><pre>{}</pre></td></tr>\n",
+                        i + 1,
+                        escape(text)
+                    );
+                }
+                body += "</table>\n";
+            }
+            None => {
+                for switch in &self.switches {
+                    body += &format!("<h4>{}</h4>\n<table>\n", escape(&switch.signal));
+                    for case in &switch.cases {
+                        let class = if case.count == 0 { "untaken" } else { "taken" };
+                        let line = case.line.map(|l| l.to_string()).unwrap_or_default();
+                        body += &format!(
+                            "<tr class=\"{class}\"><td class=\"line\">{line}</td>\
+                             <td class=\"count\">{}</td><td><pre>{}</pre></td></tr>\n",
+                            case.count,
+                            escape(&case.source)
+                        );
+                    }
+                    body += "</table>\n";
+                }
+            }
+        }
+        let (taken, total) = self.summary();
+        format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; }}
+table {{ border-collapse: collapse; }}
+td {{ padding: 0 8px; }}
+pre {{ margin: 0; }}
+.line, .count {{ color: gray; text-align: right; }}
+.taken {{ background: #e6ffec; }}
+.untaken {{ background: #ffd7d5; }}
+</style>
+</head>
+<body>
+<h3>{title}</h3>
+<p>{taken}/{total} cases taken</p>
+{body}</body>
+</html>
+"#,
+            title = escape(title),
+        )
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Coverage, Switches};
+
+    #[test]
+    fn test_coverage() {
+        let switches: &Switches = &[
+            ("a", &[("x : 1", None), ("1 : 0", None)]),
+            ("b", &[("y < 2 : 3", None)]),
+        ];
+        let mut cov = Coverage::new(switches);
+        cov.record(&[(0, 1), (1, 0)]);
+        cov.record(&[(0, 1)]);
+        let mut other = Coverage::new(switches);
+        other.record(&[(1, 0)]);
+        cov.merge(&other);
+        assert_eq!(cov.summary(), (2, 3));
+        assert_eq!(
+            cov.untaken()
+                .map(|(s, c)| (s, c.source.as_str()))
+                .collect::<Vec<_>>(),
+            [("a", "x : 1")]
+        );
+        let text = [
+            "a (1/2 cases taken)",
+            "!        0  x : 1",
+            "         2  1 : 0",
+            "b (1/1 cases taken)",
+            "         2  y < 2 : 3",
+            "2/3 cases taken",
+        ];
+        assert_eq!(cov.render_text(), text.join("\n") + "\n");
+        let html = cov.render_html("t", None);
+        assert!(html.contains(
+            "<tr class=\"untaken\"><td class=\"line\"></td><td class=\"count\">0</td>\
+             <td><pre>x : 1</pre>"
+        ));
+        assert!(html.contains("<pre>y &lt; 2 : 3</pre>"));
+
+        cov.switches[0].cases[0].line = Some(2);
+        cov.switches[0].cases[1].line = Some(2);
+        cov.switches[1].cases[0].line = Some(3);
+        let html = cov.render_html("t", Some("u8 a = [\n  x : 1; 1 : 0\n];"));
+        assert!(html.contains("<tr class=\"\"><td class=\"line\">1</td>"));
+        assert!(html
+            .contains("<tr class=\"untaken\"><td class=\"line\">2</td><td class=\"count\">2</td>"));
+        assert!(html
+            .contains("<tr class=\"taken\"><td class=\"line\">3</td><td class=\"count\">2</td>"));
+    }
+}
@@ -0,0 +1,403 @@
+//! Text drawings of datapaths, the tunnels on them, and rendering of the
+//! drawings to SVG and HTML.
+//!
+//! An architecture declares its drawing with `#![datapath = ..]` and its
+//! tunnels with `@tunnel(..)` in HCL. A tunnel is located by a seed cell on
+//! each of its wires. The whole wire is found by following the box-drawing
+//! characters from the seed. A straight wire drawn through a perpendicular one
+//! (e.g. `━┃━`) is a crossing, while `╋` joins the wires.
+
+use std::collections::{BTreeSet, HashSet};
+
+/// A tunnel declared by `@tunnel(..)` in HCL.
+#[derive(Debug, Clone, Copy)]
+pub struct Tunnel {
+    pub name: &'static str,
+    /// The value carried by the tunnel, e.g. `W.valM`
+    pub from: &'static str,
+    /// Signals (or unit inputs) that select the value through this tunnel
+    pub to: &'static [&'static str],
+    /// (row, column) of a cell on each wire of the tunnel in the drawing
+    pub seeds: &'static [(usize, usize)],
+}
+
+/// A datapath drawing of an architecture
+#[derive(Debug, Clone, Copy)]
+pub struct Datapath {
+    pub drawing: &'static str,
+    pub tunnels: &'static [Tunnel],
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Dir {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Dir {
+    fn opposite(self) -> Self {
+        match self {
+            Dir::Up => Dir::Down,
+            Dir::Down => Dir::Up,
+            Dir::Left => Dir::Right,
+            Dir::Right => Dir::Left,
+        }
+    }
+
+    fn step(self, (row, col): (usize, usize)) -> Option<(usize, usize)> {
+        Some(match self {
+            Dir::Up => (row.checked_sub(1)?, col),
+            Dir::Down => (row + 1, col),
+            Dir::Left => (row, col.checked_sub(1)?),
+            Dir::Right => (row, col + 1),
+        })
+    }
+}
+
+/// Directions that a wire character connects to
+fn connections(c: char) -> &'static [Dir] {
+    use Dir::*;
+    match c {
+        '━' | '─' | '┄' => &[Left, Right],
+        '┃' | '│' => &[Up, Down],
+        '┏' | '┌' => &[Right, Down],
+        '┓' | '┐' => &[Left, Down],
+        '┗' | '└' => &[Right, Up],
+        '┛' | '┘' => &[Left, Up],
+        '┣' | '├' => &[Up, Down, Right],
+        '┫' | '┤' => &[Up, Down, Left],
+        '┳' | '┬' => &[Left, Right, Down],
+        '┻' | '┴' => &[Left, Right, Up],
+        '╋' | '┼' => &[Up, Down, Left, Right],
+        _ => &[],
+    }
+}
+
+/// Width and height of a character cell in SVG
+const CELL: (usize, usize) = (10, 20);
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Datapath {
+    /// Lines of the drawing as characters
+    pub fn grid(&self) -> Vec<Vec<char>> {
+        self.drawing
+            .trim_start_matches('\n')
+            .lines()
+            .map(|line| line.chars().collect())
+            .collect()
+    }
+
+    /// Cells of the wires that carry the given tunnels. Unknown tunnels are
+    /// ignored.
+    pub fn wire_cells(&self, tunnels: &[&str]) -> HashSet<(usize, usize)> {
+        let grid = self.grid();
+        let mut cells = HashSet::new();
+        for tunnel in self.tunnels.iter().filter(|t| tunnels.contains(&t.name)) {
+            for seed in tunnel.seeds {
+                trace_wire(&grid, *seed, &mut cells);
+            }
+        }
+        cells
+    }
+
+    /// Names of the tunnels drawn in the datapath
+    pub fn tunnel_names(&self) -> BTreeSet<&'static str> {
+        self.tunnels
+            .iter()
+            .filter(|t| !t.seeds.is_empty())
+            .map(|t| t.name)
+            .collect()
+    }
+
+    /// Render the drawing to SVG, highlighting the wires of the given tunnels.
+    pub fn render_svg(&self, tunnels: &[&str]) -> String {
+        let grid = self.grid();
+        let lit = self.wire_cells(tunnels);
+        let wires = wire_path(&grid, |cell| !lit.contains(&cell));
+        let lit_wires = format!(
+            "<path class=\"tunnel\" d=\"{}\" {TUNNEL_STYLE}/>",
+            wire_path(&grid, |cell| lit.contains(&cell))
+        );
+        render(&grid, &wires, &lit_wires)
+    }
+
+    /// Render the datapath of each cycle to an HTML page. `cycles` holds the
+    /// active tunnels of each cycle. Use the buttons or arrow keys to switch
+    /// between cycles.
+    ///
+    /// The drawing is rendered once with a hidden path for each tunnel, and
+    /// each cycle only lists the indices of its active tunnels.
+    pub fn render_html(&self, title: &str, cycles: &[Vec<&str>]) -> String {
+        let grid = self.grid();
+        let mut names: Vec<&str> = self.tunnels.iter().map(|t| t.name).collect();
+        let lit_wires: String = self
+            .tunnels
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| !t.seeds.is_empty())
+            .map(|(i, t)| {
+                let cells = self.wire_cells(&[t.name]);
+                format!(
+                    "<path class=\"tunnel\" data-tunnel=\"{i}\" d=\"{}\" {TUNNEL_STYLE}/>",
+                    wire_path(&grid, |cell| cells.contains(&cell))
+                )
+            })
+            .collect();
+        let svg = render(&grid, &wire_path(&grid, |_| true), &lit_wires);
+
+        let mut frames = String::new();
+        for tunnels in cycles {
+            let ids: Vec<String> = tunnels
+                .iter()
+                .map(|name| {
+                    let id = names.iter().position(|n| n == name).unwrap_or_else(|| {
+                        // active but not declared in the drawing
+                        names.push(name);
+                        names.len() - 1
+                    });
+                    id.to_string()
+                })
+                .collect();
+            frames += &format!("[{}],\n", ids.join(","));
+        }
+        let names: Vec<String> = names.iter().map(|n| format!("{n:?}")).collect();
+        format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; }}
+path[data-tunnel] {{ display: none; }}
+path[data-tunnel].active {{ display: inline; }}
+</style>
+</head>
+<body>
+<h3>{title}</h3>
+<p><button id="prev">&lt;</button> <button id="next">&gt;</button> <span id="status"></span></p>
+{svg}
+<script>
+const names = [{names}];
+const cycles = [
+{frames}];
+const paths = document.querySelectorAll("path[data-tunnel]");
+let current = 0;
+function show(i) {{
+  if (cycles.length == 0) return;
+  current = Math.max(0, Math.min(cycles.length - 1, i));
+  const active = cycles[current];
+  paths.forEach(p => p.classList.toggle("active", active.includes(Number(p.dataset.tunnel))));
+  const tunnels = active.map(j => names[j]).join(" ");
+  document.getElementById("status").textContent = `Cycle ${{current + 1}}: ${{tunnels}}`;
+}}
+document.getElementById("prev").onclick = () => show(current - 1);
+document.getElementById("next").onclick = () => show(current + 1);
+document.addEventListener("keydown", e => {{
+  if (e.key == "ArrowLeft") show(current - 1);
+  if (e.key == "ArrowRight") show(current + 1);
+}});
+show(0);
+</script>
+</body>
+</html>
+"#,
+            title = escape(title),
+            svg = svg,
+            names = names.join(", "),
+            frames = frames,
+        )
+    }
+}
+
+/// Stroke of the highlighted wires in SVG
+const TUNNEL_STYLE: &str =
+    "stroke=\"#e8a400\" stroke-width=\"4\" stroke-linecap=\"round\" fill=\"none\"";
+
+/// SVG path data of the wires in the cells accepted by `filter`
+fn wire_path(grid: &[Vec<char>], filter: impl Fn((usize, usize)) -> bool) -> String {
+    let (w, h) = CELL;
+    let mut path = String::new();
+    for (row, chars) in grid.iter().enumerate() {
+        let (cy, y) = (row * h + h / 2, row * h);
+        for (col, &c) in chars.iter().enumerate() {
+            if !filter((row, col)) {
+                continue;
+            }
+            let cx = col * w + w / 2;
+            for dir in connections(c) {
+                let (x2, y2) = match dir {
+                    Dir::Up => (cx, y),
+                    Dir::Down => (cx, y + h),
+                    Dir::Left => (col * w, cy),
+                    Dir::Right => (col * w + w, cy),
+                };
+                path += &format!("M{cx} {cy}L{x2} {y2}");
+            }
+        }
+    }
+    path
+}
+
+/// Render the drawing to SVG with the path data of the plain wires and the
+/// SVG elements of the highlighted ones.
+fn render(grid: &[Vec<char>], wires: &str, lit_wires: &str) -> String {
+    let (w, h) = CELL;
+    let width = grid.iter().map(Vec::len).max().unwrap_or(0) * w;
+    let height = grid.len() * h;
+
+    let mut texts = String::new();
+    for (row, chars) in grid.iter().enumerate() {
+        let y = row * h;
+        let mut run = String::new();
+        let mut run_start = 0;
+        for (col, &c) in chars.iter().chain([' '].iter()).enumerate() {
+            if connections(c).is_empty() && c != ' ' && c != '#' {
+                if run.is_empty() {
+                    run_start = col;
+                }
+                run.push(c);
+                continue;
+            }
+            if !run.is_empty() {
+                texts += &format!(
+                    "<text x=\"{}\" y=\"{}\" textLength=\"{}\">{}</text>",
+                    run_start * w,
+                    y + h * 3 / 4,
+                    run.chars().count() * w,
+                    escape(&run)
+                );
+                run.clear();
+            }
+        }
+    }
+
+    format!(
+        concat!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" ",
+            "viewBox=\"0 0 {width} {height}\" font-family=\"monospace\" font-size=\"14\">",
+            "<path class=\"wire\" d=\"{wires}\" stroke=\"#888\" stroke-width=\"2\" fill=\"none\"/>",
+            "{lit_wires}",
+            "<g fill=\"currentColor\">{texts}</g>",
+            "</svg>"
+        ),
+        width = width,
+        height = height,
+        wires = wires,
+        lit_wires = lit_wires,
+        texts = texts,
+    )
+}
+
+/// Collect the cells of the wire starting from the seed.
+fn trace_wire(grid: &[Vec<char>], seed: (usize, usize), cells: &mut HashSet<(usize, usize)>) {
+    let get = |(row, col): (usize, usize)| {
+        grid.get(row)
+            .and_then(|line| line.get(col))
+            .copied()
+            .unwrap_or(' ')
+    };
+    let mut stack = vec![seed];
+    cells.insert(seed);
+    while let Some(pos) = stack.pop() {
+        for &dir in connections(get(pos)) {
+            let mut next = dir.step(pos);
+            while let Some(cell) = next {
+                let conn = connections(get(cell));
+                if conn.contains(&dir.opposite()) {
+                    if cells.insert(cell) {
+                        stack.push(cell);
+                    }
+                    break;
+                }
+                // cross a perpendicular wire
+                if conn.is_empty() || conn.contains(&dir) {
+                    break;
+                }
+                next = dir.step(cell);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        architectures::{arch_names, create_sim},
+        framework::{MemData, MEM_SIZE},
+    };
+
+    #[test]
+    fn test_tunnel_wires() {
+        for arch in arch_names() {
+            let sim = create_sim(arch.to_string(), MemData::init([0; MEM_SIZE]), false).unwrap();
+            let Some(datapath) = sim.datapath() else {
+                continue;
+            };
+            let grid = datapath.grid();
+            let mut all = Vec::new();
+            for tunnel in datapath.tunnels {
+                for (row, col) in tunnel.seeds {
+                    let c = grid[*row][*col];
+                    assert!(
+                        !super::connections(c).is_empty(),
+                        "{arch}: seed of {} is not a wire: {c:?}",
+                        tunnel.name
+                    );
+                }
+                all.push(datapath.wire_cells(&[tunnel.name]));
+            }
+            // tunnels are on different wires
+            for (i, a) in all.iter().enumerate() {
+                for b in &all[i + 1..] {
+                    assert!(a.is_disjoint(b), "{arch}");
+                }
+            }
+        }
+
+        // crossing a wire does not join them
+        let sim = create_sim("pipe_std".to_string(), MemData::init([0; MEM_SIZE]), false).unwrap();
+        let w_vale = sim.datapath().unwrap().wire_cells(&["W_valE"]);
+        assert!(w_vale.contains(&(20, 56)) && w_vale.contains(&(20, 58)));
+        assert!(!w_vale.contains(&(20, 57)));
+    }
+
+    #[test]
+    fn test_render() {
+        for arch in ["seq_std", "seq_plus_std", "pipe_std"] {
+            let sim = create_sim(arch.to_string(), MemData::init([0; MEM_SIZE]), false).unwrap();
+            let datapath = sim.datapath().unwrap();
+            assert!(!datapath.tunnel_names().is_empty());
+
+            let dark = datapath.render_svg(&[]);
+            assert!(dark.starts_with("<svg") && dark.contains("<path class=\"tunnel\" d=\"\""));
+            let lit = datapath.render_svg(
+                &datapath.tunnels[..1]
+                    .iter()
+                    .map(|t| t.name)
+                    .collect::<Vec<_>>(),
+            );
+            assert!(!lit.contains("<path class=\"tunnel\" d=\"\""));
+
+            let html =
+                datapath.render_html(arch, &[vec![], vec![datapath.tunnels[0].name], vec!["foo"]]);
+            // the drawing is rendered once for all cycles
+            assert_eq!(html.matches("<svg").count(), 1);
+            assert_eq!(
+                html.matches("<path class=\"tunnel\" data-tunnel=").count(),
+                datapath.tunnel_names().len()
+            );
+            let foo = datapath.tunnels.len();
+            assert!(html.contains(&format!("const cycles = [\n[],\n[0],\n[{foo}],\n];")));
+            assert!(html.contains(&format!("\"{}\"", datapath.tunnels[0].name)));
+        }
+    }
+}
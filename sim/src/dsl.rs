@@ -12,6 +12,56 @@
 ///    update its output signals, while stage units just store the inputs.
 /// 4. After all signals reaching their destinations, the cycle ends. The inputs
 ///    of stage units will become the starting signals of the next cycle.
+///
+/// A unit may declare its latency with `.latency(n)` after its outputs. The
+/// default latency is 1. Latencies are used to find the critical path, which
+/// determines the clock period of the CPU.
+/// In [`sim_macro::hcl`], `a < b` is transformed into `lt(a, b)`. Like the
+/// other comparisons, the operands are compared as signed 64-bit integers.
+pub(crate) fn lt<T: Word>(a: T, b: T) -> bool {
+    a.as_i64() < b.as_i64()
+}
+
+/// In [`sim_macro::hcl`], `a <= b` is transformed into `le(a, b)`.
+pub(crate) fn le<T: Word>(a: T, b: T) -> bool {
+    a.as_i64() <= b.as_i64()
+}
+
+/// In [`sim_macro::hcl`], `a > b` is transformed into `gt(a, b)`.
+pub(crate) fn gt<T: Word>(a: T, b: T) -> bool {
+    a.as_i64() > b.as_i64()
+}
+
+/// In [`sim_macro::hcl`], `a >= b` is transformed into `ge(a, b)`.
+pub(crate) fn ge<T: Word>(a: T, b: T) -> bool {
+    a.as_i64() >= b.as_i64()
+}
+
+/// Operands of the logical `!` in HCL. An integer is true unless it is zero.
+pub(crate) trait Truth: Copy {
+    fn truth(self) -> bool;
+}
+
+impl Truth for bool {
+    fn truth(self) -> bool {
+        self
+    }
+}
+
+macro_rules! impl_truth {
+    ($($t:ty),*) => {$(
+        impl Truth for $t {
+            fn truth(self) -> bool { self != 0 }
+        }
+    )*};
+}
+impl_truth!(u8, u16, u32, u64, usize, i8, i16, i32, i64);
+
+/// In [`sim_macro::hcl`], `!a` is transformed into `not(a)`.
+pub(crate) fn not(a: impl Truth) -> bool {
+    !a.truth()
+}
+
 #[macro_export]
 macro_rules! define_units {
     ($(
@@ -19,6 +69,7 @@ macro_rules! define_units {
         $unit_name:ident $unit_short_name:ident {
             $(.input( $($(#[$input_att:meta])* $iname:ident : $itype:ty),* ))?
             $(.output( $($(#[$output_att:meta])* $oname:ident : $otype:ty),* ))?
+            $(.latency( $latency:expr ))?
             $($sname:ident : $stype:ty),* $(,)?
         } $($body:block)?
     )*) => {
@@ -98,7 +149,7 @@ macro_rules! define_units {
                     $( stringify!($unit_short_name) =>
                         self.$unit_short_name.run(sigs),
                     )*
-                    _ => panic!("invalid name")
+                    _ => panic!("unknown unit `{}`", name)
                 }
             }
         }
@@ -110,8 +161,103 @@ macro_rules! define_units {
             builder.add_unit_node(stringify!($unit_short_name));
             $( $( builder.add_unit_input(stringify!($unit_short_name), stringify!($iname)); )* )?
             $( $( builder.add_unit_output(stringify!($unit_short_name), stringify!($oname)); )* )?
+            $( builder.set_unit_latency(stringify!($unit_short_name), $latency); )?
             )*
         }
+
+        impl UnitInputSignal {
+            /// Get an input signal by the unit name and the field name.
+            #[allow(unused)]
+            pub fn get(&self, unit: &str, field: &str) -> Option<$crate::hcl::Value> {
+                use $crate::hcl::Signal;
+                match (unit, field) {
+                    $($($( (stringify!($unit_short_name), stringify!($iname)) =>
+                        Some(self.$unit_short_name.$iname.to_value()), )*)?)*
+                    _ => None,
+                }
+            }
+
+            /// Set an input signal by the unit name and the field name. Return
+            /// `false` if the signal does not exist or the value does not fit.
+            #[allow(unused)]
+            pub fn set(&mut self, unit: &str, field: &str, value: &$crate::hcl::Value) -> bool {
+                use $crate::hcl::Signal;
+                match (unit, field) {
+                    $($($( (stringify!($unit_short_name), stringify!($iname)) =>
+                        Signal::from_value(value)
+                            .map(|v| self.$unit_short_name.$iname = v)
+                            .is_some(), )*)?)*
+                    _ => false,
+                }
+            }
+        }
+
+        impl UnitOutputSignal {
+            /// Get an output signal by the unit name and the field name.
+            #[allow(unused)]
+            pub fn get(&self, unit: &str, field: &str) -> Option<$crate::hcl::Value> {
+                use $crate::hcl::Signal;
+                match (unit, field) {
+                    $($($( (stringify!($unit_short_name), stringify!($oname)) =>
+                        Some(self.$unit_short_name.$oname.to_value()), )*)?)*
+                    _ => None,
+                }
+            }
+
+            /// Set an output signal by the unit name and the field name.
+            /// Return `false` if the signal does not exist or the value does
+            /// not fit.
+            #[allow(unused)]
+            pub fn set(&mut self, unit: &str, field: &str, value: &$crate::hcl::Value) -> bool {
+                use $crate::hcl::Signal;
+                match (unit, field) {
+                    $($($( (stringify!($unit_short_name), stringify!($oname)) =>
+                        Signal::from_value(value)
+                            .map(|v| self.$unit_short_name.$oname = v)
+                            .is_some(), )*)?)*
+                    _ => false,
+                }
+            }
+        }
+
+        impl $crate::hcl::HclUnits for Units {
+            type UnitIn = UnitInputSignal;
+            type UnitOut = UnitOutputSignal;
+
+            fn setup(builder: &mut $crate::framework::PropOrderBuilder) {
+                hardware_setup(builder)
+            }
+
+            fn run(&mut self, name: &'static str, sigs: (&UnitInputSignal, &mut UnitOutputSignal)) {
+                Units::run(self, name, sigs)
+            }
+
+            fn input(unit_in: &UnitInputSignal, unit: &str, field: &str) -> Option<$crate::hcl::Value> {
+                unit_in.get(unit, field)
+            }
+
+            fn set_input(
+                unit_in: &mut UnitInputSignal,
+                unit: &str,
+                field: &str,
+                value: &$crate::hcl::Value,
+            ) -> bool {
+                unit_in.set(unit, field, value)
+            }
+
+            fn output(unit_out: &UnitOutputSignal, unit: &str, field: &str) -> Option<$crate::hcl::Value> {
+                unit_out.get(unit, field)
+            }
+
+            fn set_output(
+                unit_out: &mut UnitOutputSignal,
+                unit: &str,
+                field: &str,
+                value: &$crate::hcl::Value,
+            ) -> bool {
+                unit_out.set(unit, field, value)
+            }
+        }
     };
 }
 
@@ -126,6 +272,65 @@ pub(crate) fn mtc<T: Eq>(sig: T, choice: impl AsRef<[T]>) -> bool {
     false
 }
 
+/// Integers used in HCL arithmetic. Like the hardware, arithmetic wraps around
+/// on overflow, and the shift amount is taken modulo the bit width.
+pub(crate) trait Word: Copy {
+    fn wrapping_add(self, rhs: Self) -> Self;
+    fn wrapping_sub(self, rhs: Self) -> Self;
+    fn wrapping_mul(self, rhs: Self) -> Self;
+    fn wrapping_neg(self) -> Self;
+    fn wrapping_shl(self, rhs: u32) -> Self;
+    fn wrapping_shr(self, rhs: u32) -> Self;
+    fn as_u32(self) -> u32;
+    fn as_i64(self) -> i64;
+}
+
+macro_rules! impl_word {
+    ($($t:ty),*) => {$(
+        impl Word for $t {
+            fn wrapping_add(self, rhs: Self) -> Self { <$t>::wrapping_add(self, rhs) }
+            fn wrapping_sub(self, rhs: Self) -> Self { <$t>::wrapping_sub(self, rhs) }
+            fn wrapping_mul(self, rhs: Self) -> Self { <$t>::wrapping_mul(self, rhs) }
+            fn wrapping_neg(self) -> Self { <$t>::wrapping_neg(self) }
+            fn wrapping_shl(self, rhs: u32) -> Self { <$t>::wrapping_shl(self, rhs) }
+            fn wrapping_shr(self, rhs: u32) -> Self { <$t>::wrapping_shr(self, rhs) }
+            fn as_u32(self) -> u32 { self as u32 }
+            fn as_i64(self) -> i64 { self as i64 }
+        }
+    )*};
+}
+impl_word!(u8, u16, u32, u64, usize, i8, i16, i32, i64);
+
+/// In [`sim_macro::hcl`], `a + b` is transformed into `add(a, b)`.
+pub(crate) fn add<T: Word>(a: T, b: T) -> T {
+    a.wrapping_add(b)
+}
+
+/// In [`sim_macro::hcl`], `a - b` is transformed into `sub(a, b)`.
+pub(crate) fn sub<T: Word>(a: T, b: T) -> T {
+    a.wrapping_sub(b)
+}
+
+/// In [`sim_macro::hcl`], `a * b` is transformed into `mul(a, b)`.
+pub(crate) fn mul<T: Word>(a: T, b: T) -> T {
+    a.wrapping_mul(b)
+}
+
+/// In [`sim_macro::hcl`], `-a` is transformed into `neg(a)`.
+pub(crate) fn neg<T: Word>(a: T) -> T {
+    a.wrapping_neg()
+}
+
+/// In [`sim_macro::hcl`], `a << b` is transformed into `shl(a, b)`.
+pub(crate) fn shl<T: Word>(a: T, b: impl Word) -> T {
+    a.wrapping_shl(b.as_u32())
+}
+
+/// In [`sim_macro::hcl`], `a >> b` is transformed into `shr(a, b)`.
+pub(crate) fn shr<T: Word>(a: T, b: impl Word) -> T {
+    a.wrapping_shr(b.as_u32())
+}
+
 #[macro_export]
 macro_rules! define_stages {
     ($(
@@ -184,6 +389,35 @@ macro_rules! define_stages {
                     // (stall) otherwise we keep the old state
                     // the same as the previous cycle
                 }
+
+                /// Get the value of a field (including `bubble` and `stall`)
+                /// by its name.
+                #[allow(unused)]
+                pub fn field(&self, name: &str) -> Option<u64> {
+                    use $crate::framework::AsWord;
+                    match name {
+                        $( stringify!($pname) => self.$pname.as_word(), )*
+                        "bubble" => Some(self.bubble as u64),
+                        "stall" => Some(self.stall as u64),
+                        _ => None,
+                    }
+                }
+
+                /// Set a field (including `bubble` and `stall`) by its name.
+                /// Return `false` if there is no such field or the value does
+                /// not fit.
+                #[allow(unused)]
+                pub fn set(&mut self, name: &str, value: &$crate::hcl::Value) -> bool {
+                    use $crate::hcl::Signal;
+                    match name {
+                        $( stringify!($pname) => Signal::from_value(value)
+                            .map(|v| self.$pname = v)
+                            .is_some(), )*
+                        "bubble" => Signal::from_value(value).map(|v| self.bubble = v).is_some(),
+                        "stall" => Signal::from_value(value).map(|v| self.stall = v).is_some(),
+                        _ => false,
+                    }
+                }
             })*
         }
 
@@ -199,6 +433,25 @@ macro_rules! define_stages {
             pub fn mux(&mut self, new: &PipeRegs) {
                 $( self.$pr_short_name.mux(&new.$pr_short_name); )*
             }
+
+            /// Get the value of a field in the stage with the short name.
+            #[allow(unused)]
+            pub fn field(&self, stage: &str, name: &str) -> Option<u64> {
+                match stage {
+                    $( stringify!($pr_short_name) => self.$pr_short_name.field(name), )*
+                    _ => None,
+                }
+            }
+
+            /// Set a field in the stage with the short name. Return `false`
+            /// if there is no such field or the value does not fit.
+            #[allow(unused)]
+            pub fn set(&mut self, stage: &str, name: &str, value: &$crate::hcl::Value) -> bool {
+                match stage {
+                    $( stringify!($pr_short_name) => self.$pr_short_name.set(name, value), )*
+                    _ => false,
+                }
+            }
         }
     };
 }
@@ -0,0 +1,277 @@
+//! Evaluate expressions on the state of a running simulator. This is used by
+//! the debug console of the debugger.
+//!
+//! An expression is built from
+//!
+//! - numeric literals (`42`, `0x2a`, optionally prefixed with `$`),
+//! - registers (`%rsp`),
+//! - labels defined in the source file (`ele1`),
+//! - signals of the current cycle (`f_pc`, `M.valE`, see [`CpuSim::signal`]),
+//! - memory dereference of 8-byte words (`*(%rsp + 8)`),
+//!
+//! combined with C-like unary (`-`, `!`, `~`, `*`) and binary operators
+//! (`* / % + - << >> < <= > >= == != & ^ | && ||`). Values are 64-bit words,
+//! and comparisons are signed.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context};
+
+use crate::{
+    framework::{CpuSim, MemData, MEM_SIZE},
+    isa::reg_code,
+    utils::{get_u64, parse_literal},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Num(u64),
+    Reg(u8),
+    /// Labels and signal names
+    Ident(String),
+    /// Operators and parentheses
+    Punct(&'static str),
+}
+
+const PUNCTS: [&str; 22] = [
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "&", "^",
+    "|", "!", "~", "(", ")",
+];
+
+fn tokenize(expr: &str) -> anyhow::Result<Vec<Token>> {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.';
+    let mut tokens = Vec::new();
+    let mut rest = expr.trim_start();
+    while !rest.is_empty() {
+        // `%` followed by a letter is a register, otherwise it is the modulo
+        if let Some(s) = rest
+            .strip_prefix('%')
+            .filter(|s| s.starts_with(|c: char| c.is_ascii_alphabetic()))
+        {
+            let end = s.find(|c| !is_word(c)).unwrap_or(s.len());
+            let reg = reg_code::code_of(&s[..end])
+                .filter(|r| *r != reg_code::RNONE)
+                .with_context(|| format!("unknown register %{}", &s[..end]))?;
+            tokens.push(Token::Reg(reg));
+            rest = &s[end..];
+        } else if let Some(p) = PUNCTS.iter().find(|p| rest.starts_with(**p)) {
+            tokens.push(Token::Punct(p));
+            rest = &rest[p.len()..];
+        } else {
+            let s = rest.strip_prefix('$').unwrap_or(rest);
+            let end = s.find(|c| !is_word(c)).unwrap_or(s.len());
+            let word = &s[..end];
+            if word.is_empty() {
+                bail!("unexpected character {:?}", s.chars().next().unwrap_or('$'));
+            }
+            if word.starts_with(|c: char| c.is_ascii_digit()) {
+                let num = parse_literal(word).with_context(|| format!("invalid number {word}"))?;
+                tokens.push(Token::Num(num));
+            } else {
+                tokens.push(Token::Ident(word.to_string()));
+            }
+            rest = &s[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Binding power of binary operators. Larger binds tighter.
+fn precedence(op: &str) -> Option<u8> {
+    Some(match op {
+        "||" => 1,
+        "&&" => 2,
+        "|" => 3,
+        "^" => 4,
+        "&" => 5,
+        "==" | "!=" => 6,
+        "<" | "<=" | ">" | ">=" => 7,
+        "<<" | ">>" => 8,
+        "+" | "-" => 9,
+        "*" | "/" | "%" => 10,
+        _ => return None,
+    })
+}
+
+/// The simulator state that expressions are evaluated on.
+pub struct EvalContext<'a> {
+    pub sim: &'a dyn CpuSim,
+    pub mem: &'a MemData,
+    /// Labels defined in the source file
+    pub symbols: &'a BTreeMap<String, u64>,
+}
+
+impl EvalContext<'_> {
+    /// Evaluate the expression to a 64-bit word.
+    pub fn eval(&self, expr: &str) -> anyhow::Result<u64> {
+        let tokens = tokenize(expr)?;
+        let mut parser = Parser {
+            ctx: self,
+            tokens: &tokens,
+            pos: 0,
+        };
+        let val = parser.binary(0)?;
+        if let Some(tok) = parser.peek() {
+            bail!("unexpected token {:?}", tok);
+        }
+        Ok(val)
+    }
+
+    fn read_mem(&self, addr: u64) -> anyhow::Result<u64> {
+        let addr = addr as usize;
+        if addr.checked_add(8).is_none_or(|end| end > MEM_SIZE) {
+            bail!("invalid memory address {:#x}", addr);
+        }
+        Ok(get_u64(&self.mem.read()[addr..addr + 8]))
+    }
+
+    fn ident(&self, name: &str) -> anyhow::Result<u64> {
+        if let Some(val) = self.sim.signal(name) {
+            return Ok(val);
+        }
+        if let Some(addr) = self.symbols.get(name) {
+            return Ok(*addr);
+        }
+        bail!("unknown signal or label {name}")
+    }
+}
+
+struct Parser<'a> {
+    ctx: &'a EvalContext<'a>,
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> anyhow::Result<&Token> {
+        let tok = self
+            .tokens
+            .get(self.pos)
+            .context("unexpected end of expression")?;
+        self.pos += 1;
+        Ok(tok)
+    }
+
+    /// Parse binary operators with precedence larger than `min_prec`.
+    fn binary(&mut self, min_prec: u8) -> anyhow::Result<u64> {
+        let mut lhs = self.unary()?;
+        while let Some(Token::Punct(op)) = self.peek() {
+            let op = *op;
+            let Some(prec) = precedence(op).filter(|p| *p > min_prec) else {
+                break;
+            };
+            self.pos += 1;
+            let rhs = self.binary(prec)?;
+            lhs = match op {
+                "||" => (lhs != 0 || rhs != 0) as u64,
+                "&&" => (lhs != 0 && rhs != 0) as u64,
+                "|" => lhs | rhs,
+                "^" => lhs ^ rhs,
+                "&" => lhs & rhs,
+                "==" => (lhs == rhs) as u64,
+                "!=" => (lhs != rhs) as u64,
+                "<" => ((lhs as i64) < (rhs as i64)) as u64,
+                "<=" => ((lhs as i64) <= (rhs as i64)) as u64,
+                ">" => ((lhs as i64) > (rhs as i64)) as u64,
+                ">=" => ((lhs as i64) >= (rhs as i64)) as u64,
+                "<<" => lhs.wrapping_shl(rhs as u32),
+                ">>" => lhs.wrapping_shr(rhs as u32),
+                "+" => lhs.wrapping_add(rhs),
+                "-" => lhs.wrapping_sub(rhs),
+                "*" => lhs.wrapping_mul(rhs),
+                "/" | "%" => {
+                    if rhs == 0 {
+                        bail!("division by zero");
+                    }
+                    let (l, r) = (lhs as i64, rhs as i64);
+                    if op == "/" {
+                        l.wrapping_div(r) as u64
+                    } else {
+                        l.wrapping_rem(r) as u64
+                    }
+                }
+                _ => unreachable!(),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> anyhow::Result<u64> {
+        Ok(match self.next()?.clone() {
+            Token::Num(num) => num,
+            Token::Reg(reg) => self.ctx.sim.registers()[reg as usize],
+            Token::Ident(name) => self.ctx.ident(&name)?,
+            Token::Punct("(") => {
+                let val = self.binary(0)?;
+                if self.next()? != &Token::Punct(")") {
+                    bail!("expect `)`");
+                }
+                val
+            }
+            Token::Punct("-") => self.unary()?.wrapping_neg(),
+            Token::Punct("!") => (self.unary()? == 0) as u64,
+            Token::Punct("~") => !self.unary()?,
+            Token::Punct("*") => {
+                let addr = self.unary()?;
+                self.ctx.read_mem(addr)?
+            }
+            tok => bail!("unexpected token {:?}", tok),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::EvalContext;
+    use crate::{architectures::create_sim, assemble, framework::MemData, AssembleOption};
+
+    #[test]
+    fn test_eval() {
+        let a = assemble(crate::asm::tests::RSUM_YS, AssembleOption::default()).unwrap();
+        let mem = MemData::init(a.obj.init_mem());
+        let mut sim = create_sim("pipe_std".to_string(), mem.clone(), false).unwrap();
+        // run until the first call is retired
+        while sim.retired_instruction() != Some(0x14) {
+            sim.initiate_next_cycle();
+            sim.propagate_signals();
+        }
+        let ctx = EvalContext {
+            sim: sim.as_ref(),
+            mem: &mem,
+            symbols: &a.obj.symbols,
+        };
+        let eval = |expr: &str| ctx.eval(expr).unwrap();
+
+        assert_eq!(eval("%rsp"), 0x1f8);
+        // return address pushed by `call`
+        assert_eq!(eval("*%rsp"), 0x1d);
+        assert_eq!(eval("*(%rsp - 8 + 0x8)"), 0x1d);
+        assert_eq!(eval("ele1"), 0x60);
+        assert_eq!(eval("*(ele1 + 8)"), eval("ele2"));
+        assert_eq!(eval("$1 + 2 * 3 == 7 && -1 < 0"), 1);
+        assert_eq!(eval("f_pc"), sim.program_counter());
+        assert_eq!(eval("W.icode"), crate::isa::inst_code::CALL as u64);
+        assert_eq!(
+            eval("d_srcA == e_dstE"),
+            (sim.signal("d_srcA") == sim.signal("e_dstE")) as u64
+        );
+
+        let empty = BTreeMap::new();
+        let ctx = EvalContext {
+            symbols: &empty,
+            ..ctx
+        };
+        assert!(ctx.eval("ele1").is_err());
+        assert!(ctx.eval("%foo").is_err());
+        assert!(ctx.eval("(1 + 2").is_err());
+        assert!(ctx.eval("*0xffff").is_err());
+        assert!(ctx.eval("1 / 0").is_err());
+    }
+}
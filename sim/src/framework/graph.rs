@@ -0,0 +1,308 @@
+//! Export the computational graph of an architecture to text formats, which
+//! can be embedded in documents where interactive HTML does not work.
+
+use std::fmt::Write;
+
+use super::PropOrder;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum NodeKind {
+    Unit,
+    Signal,
+    /// Input or output of a unit, e.g. `alu.a`
+    Port,
+}
+
+impl NodeKind {
+    fn name(self) -> &'static str {
+        match self {
+            NodeKind::Unit => "unit",
+            NodeKind::Signal => "signal",
+            NodeKind::Port => "port",
+        }
+    }
+}
+
+struct Node<'a> {
+    name: &'a str,
+    kind: NodeKind,
+    section: Option<&'static str>,
+    critical: bool,
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl PropOrder {
+    /// Nodes on the critical path, from the source to the sink.
+    pub fn critical_path(&self) -> &[String] {
+        &self.critical_path
+    }
+
+    fn is_critical_edge(&self, from: &str, to: &str) -> bool {
+        self.critical_path
+            .windows(2)
+            .any(|w| w[0] == from && w[1] == to)
+    }
+
+    /// Section names in the order of declaration
+    fn section_names(&self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        for (_, section) in &self.sections {
+            if !names.contains(section) {
+                names.push(*section);
+            }
+        }
+        names
+    }
+
+    /// All nodes in the graph. Ports are grouped with their units.
+    fn nodes(&self) -> Vec<Node<'_>> {
+        let mut names: Vec<&str> = self
+            .order
+            .iter()
+            .map(|item| item.name)
+            .chain(
+                self.edges
+                    .iter()
+                    .flat_map(|(a, b)| [a.as_str(), b.as_str()]),
+            )
+            .collect();
+        names.sort();
+        names.dedup();
+
+        let section_of = |name: &str| {
+            self.sections
+                .iter()
+                .find(|(node, _)| *node == name)
+                .map(|(_, section)| *section)
+        };
+        names
+            .into_iter()
+            .map(|name| {
+                let item = self.order.iter().find(|item| item.name == name);
+                let kind = match item {
+                    Some(item) if item.is_unit => NodeKind::Unit,
+                    Some(_) => NodeKind::Signal,
+                    None => NodeKind::Port,
+                };
+                let section = match name.split_once('.') {
+                    Some((unit, _)) if kind == NodeKind::Port => section_of(unit),
+                    _ => section_of(name),
+                };
+                Node {
+                    name,
+                    kind,
+                    section,
+                    critical: self.critical_path.iter().any(|n| n == name),
+                }
+            })
+            .collect()
+    }
+
+    /// Export the graph in Graphviz DOT format.
+    pub fn to_dot(&self, title: &str) -> String {
+        let nodes = self.nodes();
+        let mut out = String::new();
+        writeln!(out, "digraph {} {{", quote(title)).unwrap();
+        writeln!(out, "    rankdir=LR;").unwrap();
+        writeln!(out, "    node [fontname=\"Helvetica\", fontsize=10];").unwrap();
+
+        let node_line = |node: &Node| {
+            let mut attrs = match node.kind {
+                NodeKind::Unit => "shape=box, style=filled, fillcolor=\"#c6dbef\"".to_string(),
+                NodeKind::Signal => "shape=ellipse".to_string(),
+                NodeKind::Port => "shape=plaintext, fontsize=8".to_string(),
+            };
+            if node.critical {
+                attrs += ", color=\"#e6550d\", fontcolor=\"#e6550d\", penwidth=2";
+            }
+            format!("{} [{}];", quote(node.name), attrs)
+        };
+        for (i, section) in self.section_names().into_iter().enumerate() {
+            writeln!(out, "    subgraph cluster_{i} {{").unwrap();
+            writeln!(out, "        label={};", quote(section)).unwrap();
+            for node in nodes.iter().filter(|n| n.section == Some(section)) {
+                writeln!(out, "        {}", node_line(node)).unwrap();
+            }
+            writeln!(out, "    }}").unwrap();
+        }
+        for node in nodes.iter().filter(|n| n.section.is_none()) {
+            writeln!(out, "    {}", node_line(node)).unwrap();
+        }
+        for (from, to) in &self.edges {
+            let attrs = if self.is_critical_edge(from, to) {
+                " [color=\"#e6550d\", penwidth=2]"
+            } else {
+                ""
+            };
+            writeln!(out, "    {} -> {}{};", quote(from), quote(to), attrs).unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+        out
+    }
+
+    /// Export the graph as a Mermaid flowchart.
+    pub fn to_mermaid(&self, title: &str) -> String {
+        let nodes = self.nodes();
+        // node names contain dots, which are not allowed in Mermaid ids
+        let id = |name: &str| {
+            let index = nodes.iter().position(|n| n.name == name).unwrap();
+            format!("n{index}")
+        };
+        let mut out = String::new();
+        writeln!(out, "---\ntitle: {}\n---", title).unwrap();
+        writeln!(out, "flowchart LR").unwrap();
+
+        let node_line = |node: &Node| {
+            let shape = match node.kind {
+                NodeKind::Unit => format!("[{}]", quote(node.name)),
+                NodeKind::Signal => format!("({})", quote(node.name)),
+                NodeKind::Port => format!(">{}]", quote(node.name)),
+            };
+            let class = if node.critical {
+                "critical"
+            } else {
+                node.kind.name()
+            };
+            format!("{}{}:::{}", id(node.name), shape, class)
+        };
+        for (i, section) in self.section_names().into_iter().enumerate() {
+            writeln!(out, "    subgraph s{i} [{}]", quote(section)).unwrap();
+            for node in nodes.iter().filter(|n| n.section == Some(section)) {
+                writeln!(out, "        {}", node_line(node)).unwrap();
+            }
+            writeln!(out, "    end").unwrap();
+        }
+        for node in nodes.iter().filter(|n| n.section.is_none()) {
+            writeln!(out, "    {}", node_line(node)).unwrap();
+        }
+        let mut critical_links = Vec::new();
+        for (i, (from, to)) in self.edges.iter().enumerate() {
+            writeln!(out, "    {} --> {}", id(from), id(to)).unwrap();
+            if self.is_critical_edge(from, to) {
+                critical_links.push(i.to_string());
+            }
+        }
+        writeln!(out, "    classDef unit fill:#c6dbef,stroke:#3182bd").unwrap();
+        writeln!(out, "    classDef signal fill:#fff,stroke:#999").unwrap();
+        writeln!(
+            out,
+            "    classDef port fill:#fff,stroke:#ddd,font-size:10px"
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "    classDef critical fill:#fdd0a2,stroke:#e6550d,stroke-width:2px"
+        )
+        .unwrap();
+        if !critical_links.is_empty() {
+            writeln!(
+                out,
+                "    linkStyle {} stroke:#e6550d,stroke-width:2px",
+                critical_links.join(",")
+            )
+            .unwrap();
+        }
+        out
+    }
+
+    /// Export the graph as JSON.
+    pub fn to_json(&self, title: &str) -> String {
+        let opt = |s: Option<&str>| s.map(quote).unwrap_or_else(|| "null".to_string());
+        let nodes: Vec<String> = self
+            .nodes()
+            .iter()
+            .map(|node| {
+                let level = self
+                    .order
+                    .iter()
+                    .find(|item| item.name == node.name)
+                    .map(|item| item.level.to_string());
+                format!(
+                    "{{\"name\": {}, \"kind\": {}, \"section\": {}, \"level\": {}, \"latency\": {}, \"critical\": {}}}",
+                    quote(node.name),
+                    quote(node.kind.name()),
+                    opt(node.section),
+                    level.as_deref().unwrap_or("null"),
+                    self.latency(node.name),
+                    node.critical
+                )
+            })
+            .collect();
+        let edges: Vec<String> = self
+            .edges
+            .iter()
+            .map(|(from, to)| {
+                format!(
+                    "{{\"from\": {}, \"to\": {}, \"critical\": {}}}",
+                    quote(from),
+                    quote(to),
+                    self.is_critical_edge(from, to)
+                )
+            })
+            .collect();
+        let list = |items: Vec<String>| format!("[\n    {}\n  ]", items.join(",\n    "));
+        format!(
+            "{{\n  \"arch\": {},\n  \"max_dist\": {},\n  \"critical_delay\": {},\n  \"clock_period\": {},\n  \"sections\": {},\n  \"critical_path\": {},\n  \"nodes\": {},\n  \"edges\": {}\n}}\n",
+            quote(title),
+            self.max_dist,
+            self.critical_delay,
+            self.clock_period(),
+            list(self.section_names().into_iter().map(quote).collect()),
+            list(self.critical_path.iter().map(|s| quote(s)).collect()),
+            list(nodes),
+            list(edges),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        architectures::create_sim,
+        framework::{MemData, MEM_SIZE},
+    };
+
+    #[test]
+    fn test_export_graph() {
+        let sim = create_sim("seq_std".to_string(), MemData::init([0; MEM_SIZE]), false).unwrap();
+        let order = sim.proporder();
+
+        // the critical path is a chain of edges whose units sum up to the
+        // critical delay
+        let path = order.critical_path();
+        assert!(path
+            .windows(2)
+            .all(|w| order.edges.contains(&(w[0].clone(), w[1].clone()))));
+        let delay: u32 = path.iter().map(|n| order.latency(n)).sum();
+        assert_eq!(delay, order.critical_delay);
+
+        let dot = order.to_dot("seq_std");
+        assert!(dot.starts_with("digraph \"seq_std\" {"));
+        assert!(dot.contains("subgraph cluster_0 {\n        label=\"Fetch Stage\";"));
+        assert!(dot.contains("\"alu\" [shape=box"));
+        assert!(dot.contains("\"valE\" [shape=ellipse"));
+        assert!(dot.contains("[color=\"#e6550d\", penwidth=2]"));
+
+        let mermaid = order.to_mermaid("seq_std");
+        assert!(mermaid.contains("flowchart LR"));
+        assert!(mermaid.contains("subgraph s1 [\"Decode Stage\"]"));
+        assert!(mermaid.contains("linkStyle "));
+        assert_eq!(
+            mermaid.matches(" --> ").count(),
+            order.edges.len(),
+            "{mermaid}"
+        );
+
+        let json = order.to_json("seq_std");
+        assert!(
+            json.contains("{\"name\": \"alu\", \"kind\": \"unit\", \"section\": \"Execute Stage\"")
+        );
+        assert!(json
+            .contains("{\"name\": \"alu.a\", \"kind\": \"port\", \"section\": \"Execute Stage\""));
+        assert!(json.contains(&format!("\"max_dist\": {}", order.max_dist)));
+        assert!(json.contains("\"name\": \"dmem\", \"kind\": \"unit\", \"section\": \"Memory Stage\", \"level\": 5, \"latency\": 3"));
+    }
+}
@@ -1,10 +1,11 @@
 //! To provide a flexible codebase for different CPU architectures, we give a
 //! general CPU simulator framework.
+mod graph;
 mod propagate;
 
 pub use propagate::{PropCircuit, PropOrder, PropOrderBuilder, PropUpdates, Propagator, Tracer};
 
-use crate::isa::RegFile;
+use crate::isa::{ConditionCode, RegFile};
 
 /// HardwareUnits depends on the [`std::fmt::Display`] trait, which enables
 /// rich-text output in terminal.
@@ -14,6 +15,97 @@ pub trait HardwareUnits: std::fmt::Display {
 
     /// Return the content of register file, indexed by the register code.
     fn register_file(&self) -> RegFile;
+
+    /// Overwrite a register in the register file, e.g. to set up initial
+    /// register values before the simulation starts.
+    fn set_register(&mut self, reg: u8, val: u64);
+
+    /// Return the condition codes. Units without a condition code register
+    /// keep the default ones.
+    fn condition_code(&self) -> ConditionCode {
+        ConditionCode::default()
+    }
+
+    /// Return the log where the memory and register file units record their
+    /// accesses.
+    fn access_log(&self) -> &AccessLog;
+}
+
+/// A read or write access to a memory word or a register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataAccess {
+    /// Read the 8-byte word at the given memory address.
+    MemRead(u64),
+    /// Write the 8-byte word at the given memory address.
+    MemWrite(u64),
+    /// Read the register with the given register code.
+    RegRead(u8),
+    /// Write the register with the given register code.
+    RegWrite(u8),
+}
+
+/// Data accesses performed by hardware units during a cycle. Units that own
+/// the memory or the register file record their accesses here, which allows
+/// the debugger to implement data breakpoints (watchpoints).
+#[derive(Debug, Default, Clone)]
+pub struct AccessLog(std::rc::Rc<std::cell::RefCell<Vec<DataAccess>>>);
+
+impl AccessLog {
+    pub fn record(&self, access: DataAccess) {
+        self.0.borrow_mut().push(access)
+    }
+
+    /// Remove all records. This is called at the beginning of each cycle.
+    pub fn clear(&self) {
+        self.0.borrow_mut().clear()
+    }
+
+    pub fn accesses(&self) -> Vec<DataAccess> {
+        self.0.borrow().clone()
+    }
+}
+
+/// Conversion of signal values to 64-bit words, which allows the debugger to
+/// evaluate expressions on signals. Values that are not scalars (e.g. the
+/// instruction bytes) can not be converted.
+pub trait AsWord {
+    fn as_word(&self) -> Option<u64>;
+}
+
+macro_rules! impl_as_word {
+    ($($t:ty),*) => {
+        $(impl AsWord for $t {
+            fn as_word(&self) -> Option<u64> {
+                Some(*self as u64)
+            }
+        })*
+    };
+}
+
+impl_as_word!(
+    u8,
+    u16,
+    u32,
+    u64,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    bool,
+    crate::isa::Stat
+);
+
+impl<T, const N: usize> AsWord for [T; N] {
+    fn as_word(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl AsWord for crate::isa::ConditionCode {
+    fn as_word(&self) -> Option<u64> {
+        None
+    }
 }
 
 /// Size of the memory that is used to store instructions and data (stack).
@@ -78,8 +170,8 @@ pub trait CpuSim: std::fmt::Display {
     /// will increase the cycle count by 1.
     fn cycle_count(&self) -> u64;
 
-    /// The (time) cost of running a cycle. See
-    /// [`propagate::PropOrder::max_dist`].
+    /// The (time) cost of running a cycle, i.e. the clock period. See
+    /// [`propagate::PropOrder::clock_period`].
     fn cycle_cost(&self) -> u64;
 
     /// Get the registers and their values.
@@ -87,9 +179,86 @@ pub trait CpuSim: std::fmt::Display {
     /// The id of the register should be in increasing order.
     fn registers(&self) -> RegFile;
 
+    /// Overwrite the register with the register code.
+    fn set_register(&mut self, reg: u8, val: u64);
+
+    /// Get the condition codes.
+    fn condition_code(&self) -> ConditionCode;
+
     /// This function is called by debugger to display variables
     fn get_stage_info(&self) -> Vec<StageInfo>;
 
+    /// Memory and register accesses performed in the last cycle. This
+    /// function is called by debugger to check data breakpoints.
+    fn data_accesses(&self) -> Vec<DataAccess>;
+
+    /// Addresses of the instructions held by each stage in the last cycle,
+    /// from the first stage to the last one. The instruction in the first
+    /// stage is the one at [`CpuSim::program_counter`]. `None` means the
+    /// stage holds a bubble.
+    ///
+    /// Instructions are tracked through the bubble and stall signals of the
+    /// pipeline registers.
+    fn stage_instructions(&self) -> Vec<(&'static str, Option<u64>)>;
+
+    /// The address of the instruction that has gone through the last stage
+    /// in the last cycle, if any. For a single-stage architecture, this is
+    /// the instruction executed in the last cycle.
+    fn retired_instruction(&self) -> Option<u64>;
+
+    /// The address of the next instruction to retire if it has entered the
+    /// pipeline, i.e. the instruction in the latest stage before the last
+    /// one. A single-stage architecture has none, since an instruction enters
+    /// and retires in the same cycle.
+    fn next_instruction(&self) -> Option<u64> {
+        let stages = self.stage_instructions();
+        let n = stages.len().saturating_sub(1);
+        stages[..n].iter().rev().find_map(|(_, pc)| *pc)
+    }
+
+    /// Names of the tunnels that signals went through in the last cycle, in
+    /// sorted order. A tunnel is triggered by the `#[tunnel(..)]` attribute in
+    /// HCL.
+    fn active_tunnels(&self) -> Vec<&'static str>;
+
+    /// Tunnels declared by `@tunnel(..)` in HCL.
+    fn tunnel_map(&self) -> &'static [crate::datapath::Tunnel];
+
+    /// Datapath drawing declared by `#![datapath = ..]` in HCL, if any.
+    fn datapath(&self) -> Option<crate::datapath::Datapath>;
+
+    /// The failure of an `@assert(..)` in HCL, with the cycle, the message
+    /// and the values of the signals involved. The simulation terminates when
+    /// an assertion fails.
+    fn failed_assertion(&self) -> Option<String>;
+
+    /// How often each case of each switch signal has been taken since the
+    /// start of the simulation.
+    fn coverage(&self) -> &crate::coverage::Coverage;
+
+    /// Source of the Rust module where a compiled architecture is defined by
+    /// `hcl!`, which the lines of [`CpuSim::coverage`] refer to. `None` if it
+    /// is not known, or the architecture is loaded from a `.hcl` file.
+    fn source(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Get the value of a signal in the current cycle. `name` is either an
+    /// intermediate signal (e.g. `d_srcA`) or a stage register field. `M.valE`
+    /// refers to the value at the start of the cycle, while `m.valE` refers
+    /// to the value to be latched at the end of the cycle.
+    ///
+    /// Return `None` if the signal does not exist or it is not a scalar.
+    fn signal(&self, name: &str) -> Option<u64>;
+
+    /// Set a stage register field at the start of the cycle (e.g. `E.icode`),
+    /// or force an output of a unit (e.g. `dmem.error`) to hold the value
+    /// whenever the unit runs. This is used to test signals in isolation, see
+    /// [`crate::test::SignalTester`].
+    ///
+    /// Fail if there is no such signal or the value does not fit its type.
+    fn force_signal(&mut self, name: &str, value: &crate::hcl::Value) -> anyhow::Result<()>;
+
     fn step(&mut self);
 
     /// Get the value of a register if it exists
@@ -101,6 +270,29 @@ pub trait CpuSim: std::fmt::Display {
     fn proporder(&self) -> &PropOrder;
 }
 
+/// Address of the next instruction to retire, given the most recently retired
+/// one. A single-stage architecture has not fetched it yet, so it is found by
+/// executing the retired instruction in the ISA semantics.
+pub fn next_to_retire(sim: &dyn CpuSim, mem: &MemData, last_retired: Option<u64>) -> Option<u64> {
+    use crate::isa::{disassemble, inst_code, reg_code};
+
+    if let Some(addr) = sim.next_instruction() {
+        return Some(addr);
+    }
+    let addr = last_retired?;
+    let mem = mem.read();
+    let word = |addr: u64| Some(crate::utils::get_u64(mem.get(addr as usize..)?.get(..8)?));
+    let byte = *mem.get(addr as usize)?;
+    match byte >> 4 {
+        inst_code::HALT => Some(addr),
+        inst_code::CALL => word(addr + 1),
+        inst_code::JX if sim.condition_code().test(byte & 0xf) => word(addr + 1),
+        // the return address has been popped
+        inst_code::RET => word(sim.registers()[reg_code::RSP as usize].wrapping_sub(8)),
+        _ => disassemble(&mem[..], addr as usize).map(|(len, _)| addr + len as u64),
+    }
+}
+
 // here we use trait to collect the types
 pub trait CpuCircuit {
     type UnitIn: Default;
@@ -111,7 +303,17 @@ pub trait CpuCircuit {
 
 pub trait CpuArch: CpuCircuit + Sized {
     type Units: HardwareUnits;
-    fn build_circuit() -> PropCircuit<Self>;
+    /// Names of the pipeline registers (stages) in pipeline order, i.e. the
+    /// aliases in `#![stage_alias(...)]`.
+    const STAGES: &'static [&'static str];
+    /// Switch signals in HCL, with the source and the line of their cases,
+    /// e.g. `("f_pc", &[("M.icode == JX && !M.cnd : M.valA", Some(12)), ..])`.
+    /// A boolean signal defined by an expression `e` has the cases `e : true`
+    /// and `1 : false`. See [`crate::coverage::Coverage`].
+    const SWITCHES: &'static crate::coverage::Switches<'static>;
+    /// Build the computational graph. Fail if the graph has a cycle or an
+    /// input of some unit has no source.
+    fn build_circuit() -> anyhow::Result<PropCircuit<Self>>;
 }
 
 pub type Signals<A> = (
@@ -140,15 +342,28 @@ pub struct PipeSim<T: CpuArch> {
     /// Whether to print the output to tty
     pub(crate) tty_out: bool,
     pub(crate) cycle_count: u64,
+    /// See [`StageTracker`].
+    pub(crate) stages: StageTracker,
+    /// See [`CpuSim::active_tunnels`].
+    pub(crate) tunnels: Vec<&'static str>,
+    /// See [`CpuSim::failed_assertion`].
+    pub(crate) failed_assertion: Option<String>,
+    /// (unit, output, value) forced by [`CpuSim::force_signal`]
+    pub(crate) forced_outputs: Vec<(String, String, crate::hcl::Value)>,
+    /// See [`CpuSim::coverage`].
+    pub(crate) coverage: crate::coverage::Coverage,
 }
 
 impl<T: CpuArch> PipeSim<T> {
     /// Initialize the simulator with given memory
     ///
     /// tty_out: whether to print rich-text information
+    ///
+    /// Panics if the circuit of the architecture can not be built, see
+    /// [`CpuArch::build_circuit`].
     pub fn new(memory: MemData, tty_out: bool) -> Self {
         Self {
-            circuit: T::build_circuit(),
+            circuit: T::build_circuit().unwrap_or_else(|e| panic!("{e}")),
             cur_inter: T::Inter::default(),
             cur_unit_in: T::UnitIn::default(),
             cur_unit_out: T::UnitOut::default(),
@@ -158,8 +373,76 @@ impl<T: CpuArch> PipeSim<T> {
             terminate: false,
             tty_out,
             cycle_count: 0,
+            stages: StageTracker::new(T::STAGES.len()),
+            tunnels: Vec::new(),
+            failed_assertion: None,
+            forced_outputs: Vec::new(),
+            coverage: crate::coverage::Coverage::new(T::SWITCHES),
+        }
+    }
+}
+
+/// Track the addresses of the instructions held by each stage. See
+/// [`CpuSim::stage_instructions`].
+#[derive(Debug, Clone)]
+pub(crate) struct StageTracker {
+    pub(crate) stage_pcs: Vec<Option<u64>>,
+    /// Whether the last stage received a new instruction at the beginning of
+    /// the current cycle.
+    last_stage_fresh: bool,
+    /// See [`CpuSim::retired_instruction`].
+    pub(crate) retired: Option<u64>,
+}
+
+impl StageTracker {
+    pub(crate) fn new(n_stages: usize) -> Self {
+        Self {
+            stage_pcs: vec![None; n_stages.max(1)],
+            last_stage_fresh: false,
+            retired: None,
+        }
+    }
+
+    /// Move instructions to the next stage at the end of a cycle, w.r.t. the
+    /// `(bubble, stall)` control signals of each stage. The first stage
+    /// always holds the instruction being fetched, thus its control is
+    /// ignored.
+    pub(crate) fn shift(&mut self, ctrl: &[(bool, bool)]) {
+        let n = self.stage_pcs.len();
+        for k in (1..n).rev() {
+            let (bubble, stall) = ctrl.get(k).copied().unwrap_or_default();
+            if bubble {
+                self.stage_pcs[k] = None;
+            } else if !stall {
+                self.stage_pcs[k] = self.stage_pcs[k - 1];
+            }
+            if k == n - 1 {
+                self.last_stage_fresh = !bubble && !stall;
+            }
         }
     }
+
+    /// Record the address of the fetched instruction after the signals are
+    /// propagated, and update the retired instruction.
+    pub(crate) fn fetch(&mut self, pc: u64) {
+        self.stage_pcs[0] = Some(pc);
+        self.retired = if self.stage_pcs.len() == 1 {
+            Some(pc)
+        } else if self.last_stage_fresh {
+            self.stage_pcs.last().copied().flatten()
+        } else {
+            None
+        };
+    }
+
+    /// Pair the stage names with the instructions they hold.
+    pub(crate) fn instructions(&self, names: &[&'static str]) -> Vec<(&'static str, Option<u64>)> {
+        names
+            .iter()
+            .copied()
+            .zip(self.stage_pcs.iter().copied())
+            .collect()
+    }
 }
 
 #[derive(Debug)]
@@ -1,9 +1,11 @@
 use std::{
     collections::{HashMap, HashSet, VecDeque},
-    fmt::{Debug, Write},
+    fmt::{Display, Write},
     hash::Hash,
 };
 
+use anyhow::{bail, Result};
+
 use crate::framework::CpuCircuit;
 
 #[derive(Debug)]
@@ -25,8 +27,18 @@ pub struct PropOrder {
     /// It is used to determine the CPU clock time. A severely pipelined CPU
     /// tends to have a small `max_dist`.
     pub(crate) max_dist: u32,
+    /// Latencies of units declared by `.latency(n)` in [`crate::define_units`].
+    pub(crate) latencies: HashMap<&'static str, u32>,
+    /// The total latency of units on the critical path.
+    pub(crate) critical_delay: u32,
     /// Edges of the computational graph.
     pub(crate) edges: Vec<(String, String)>,
+    /// Nodes on the critical path, i.e. the slowest chain of units weighted
+    /// by their latencies, from the source to the sink.
+    pub(crate) critical_path: Vec<String>,
+    /// (node, section) for units and intermediate signals, where sections
+    /// are declared by `:===: title :===:` in HCL.
+    pub(crate) sections: Vec<(&'static str, &'static str)>,
 }
 
 impl std::fmt::Display for PropOrder {
@@ -45,17 +57,58 @@ impl std::fmt::Display for PropOrder {
             }
             f.write_char('\n')?;
         }
-        Ok(())
+        write!(f, "{s}critical path{s:#} (delay {}):", self.critical_delay)?;
+        for item in self
+            .critical_path
+            .iter()
+            .filter_map(|node| self.order.iter().find(|item| item.name == node))
+        {
+            if item.is_unit {
+                let s = crate::utils::GRNB;
+                write!(f, " {s}{}{s:#}[{}]", item.name, self.latency(item.name))?
+            } else {
+                let s = crate::utils::GRAY;
+                write!(f, " {s}{}{s:#}", item.name)?
+            }
+        }
+        f.write_char('\n')
+    }
+}
+
+/// The latency of latching the stage registers at the end of a cycle, which
+/// every cycle pays besides the critical path.
+pub const REGISTER_LATENCY: u32 = 1;
+
+impl PropOrder {
+    /// Latency of a node. Units default to 1, and signals (and ports) have
+    /// no latency.
+    pub fn latency(&self, name: &str) -> u32 {
+        if self
+            .order
+            .iter()
+            .any(|item| item.is_unit && item.name == name)
+        {
+            self.latencies.get(name).copied().unwrap_or(1)
+        } else {
+            0
+        }
+    }
+
+    /// The clock period of the CPU, which is the delay of the critical path
+    /// plus [`REGISTER_LATENCY`].
+    pub fn clock_period(&self) -> u64 {
+        (self.critical_delay + REGISTER_LATENCY) as u64
     }
 }
 
 /// Compute topological order of nodes using BFS.
 ///
-/// Return node list in order and their levels
-pub fn topo<Node: Copy + Eq + Hash + Debug>(
+/// Return node list in order, or an error showing a cycle such as
+/// `a -> b -> a` if the graph is not a DAG.
+pub fn topo<Node: Copy + Eq + Hash + Display>(
     nodes: impl Iterator<Item = Node> + Clone,
     edges: impl Iterator<Item = (Node, Node)> + Clone,
-) -> Vec<Node> {
+) -> Result<Vec<Node>> {
     let mut degree_level: HashMap<Node, i32> = HashMap::default();
     for (_, to) in edges.clone() {
         let entry = degree_level.entry(to).or_default();
@@ -63,7 +116,7 @@ pub fn topo<Node: Copy + Eq + Hash + Debug>(
     }
     let mut que: VecDeque<Node> = VecDeque::new();
     let mut levels = Vec::new();
-    for node in nodes {
+    for node in nodes.clone() {
         if degree_level.get(&node).cloned().unwrap_or_default() == 0 {
             que.push_back(node)
         }
@@ -82,21 +135,44 @@ pub fn topo<Node: Copy + Eq + Hash + Debug>(
         }
     }
 
-    if !degree_level.is_empty() {
-        panic!(
-            "The computational graph of your CPU pipeline is not a DAG! degrees: {:?}",
-            degree_level
-        )
+    // Every node left has a predecessor that is also left. Walk back along
+    // the predecessors until a node repeats, which closes a cycle.
+    let Some(start) = nodes
+        .chain(degree_level.keys().copied())
+        .find(|node| degree_level.contains_key(node))
+    else {
+        return Ok(levels);
+    };
+    let mut path = vec![start];
+    loop {
+        let last = path[path.len() - 1];
+        let prev = edges
+            .clone()
+            .find(|(from, to)| *to == last && degree_level.contains_key(from))
+            .map(|(from, _)| from)
+            .expect("topo: a node in a cycle has no predecessor");
+        if let Some(pos) = path.iter().position(|node| *node == prev) {
+            let cycle = std::iter::once(&prev)
+                .chain(path[pos..].iter().rev())
+                .map(|node| node.to_string())
+                .collect::<Vec<_>>();
+            bail!(
+                "the computational graph of your CPU is not a DAG, found a cycle: {}",
+                cycle.join(" -> ")
+            )
+        }
+        path.push(prev);
     }
-
-    levels
 }
+
 pub struct PropOrderBuilder {
     runnable_nodes_set: HashSet<String>,
     /// Runnable nodes includes units and intermediate signals.
     runnable_nodes: NameList,
     nodes: HashSet<String>,
     edges: Vec<(String, String)>,
+    sections: Vec<(&'static str, &'static str)>,
+    latencies: HashMap<&'static str, u32>,
 }
 
 impl Default for PropOrderBuilder {
@@ -112,6 +188,8 @@ impl PropOrderBuilder {
             runnable_nodes: Default::default(),
             nodes: Default::default(),
             edges: Default::default(),
+            sections: Default::default(),
+            latencies: Default::default(),
         }
     }
 
@@ -139,6 +217,11 @@ impl PropOrderBuilder {
         self.add_edge(unit_name.to_string(), full_name.to_string());
     }
 
+    /// Set the latency of unit `name`. The default latency is 1.
+    pub fn set_unit_latency(&mut self, unit_name: &'static str, latency: u32) {
+        self.latencies.insert(unit_name, latency);
+    }
+
     pub fn add_intermediate(&mut self, name: &'static str) {
         if !self.runnable_nodes_set.insert(name.to_string()) {
             panic!("duplicate intermediate name: {}", name)
@@ -147,8 +230,17 @@ impl PropOrderBuilder {
         self.nodes.insert(name.to_string());
     }
 
-    /// Compute topological order of nodes.
-    pub fn build(mut self) -> PropOrder {
+    /// Put a unit or an intermediate signal into a section. Only the first
+    /// section of a node counts.
+    pub fn add_to_section(&mut self, name: &'static str, section: &'static str) {
+        if self.sections.iter().all(|(node, _)| *node != name) {
+            self.sections.push((name, section));
+        }
+    }
+
+    /// Compute topological order of nodes. Fail if the graph has a cycle or
+    /// an input of some unit has no source.
+    pub fn build(mut self) -> Result<PropOrder> {
         // remove duplicates
         self.edges = std::mem::take(&mut self.edges)
             .into_iter()
@@ -157,6 +249,12 @@ impl PropOrderBuilder {
             .collect::<Vec<(String, String)>>();
 
         self.edges.sort();
+
+        // sort the nodes so that the order is deterministic
+        let mut nodes = self.nodes.iter().collect::<Vec<_>>();
+        nodes.sort();
+        let levels = topo(nodes.into_iter(), self.edges.iter().map(|(a, b)| (a, b)))?;
+
         // check if every input of units has at least one source
         let independent_unit_in = self
             .runnable_nodes
@@ -169,10 +267,9 @@ impl PropOrderBuilder {
                     .find(|(unit_in, _)| self.edges.iter().all(|(_, to)| to != unit_in))
             });
         if let Some(e) = independent_unit_in {
-            panic!("unit input {} has no source", e.0)
+            bail!("unit input `{}` has no source", e.0)
         }
 
-        let levels = topo(self.nodes.iter(), self.edges.iter().map(|(a, b)| (a, b)));
         let order: Vec<(bool, &'static str)> = levels
             .iter()
             .filter_map(|node| self.runnable_nodes.iter().find(|(_, p)| p == node).copied())
@@ -180,7 +277,7 @@ impl PropOrderBuilder {
 
         // compute distance of each node from the source
         let mut dist: HashMap<&str, u32> = HashMap::new();
-        for node in levels {
+        for &node in &levels {
             let is_unit = self.runnable_nodes.iter().any(|(is, p)| *is && p == node);
             for from in self
                 .edges
@@ -194,6 +291,47 @@ impl PropOrderBuilder {
         }
         let max_dist = dist.values().max().copied().unwrap_or_default() + 1;
 
+        // compute the delay of each node weighted by the latencies of units
+        let weight = |node: &str| {
+            let is_unit = self.runnable_nodes.iter().any(|(is, p)| *is && *p == node);
+            match self.latencies.get(node) {
+                _ if !is_unit => 0,
+                Some(latency) => *latency,
+                None => 1,
+            }
+        };
+        let mut delay: HashMap<&str, u32> = HashMap::new();
+        for &node in &levels {
+            let from_delay = self
+                .edges
+                .iter()
+                .filter(|(_, to)| to == node)
+                .map(|(from, _)| delay.get(from.as_str()).copied().unwrap_or(0))
+                .max()
+                .unwrap_or(0);
+            delay.insert(node, from_delay + weight(node));
+        }
+        let critical_delay = delay.values().max().copied().unwrap_or_default();
+
+        // trace back from the slowest node along the predecessors that
+        // determine the delay
+        let mut critical_path = Vec::new();
+        let mut cur = delay
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+            .map(|(node, _)| *node);
+        while let Some(node) = cur {
+            critical_path.push(node.to_string());
+            let d = delay[node] - weight(node);
+            cur = self
+                .edges
+                .iter()
+                .filter(|(_, to)| to == node)
+                .map(|(from, _)| from.as_str())
+                .find(|from| delay[from] == d);
+        }
+        critical_path.reverse();
+
         let mut order = order
             .into_iter()
             .map(|(is_unit, p)| PropOrderItem {
@@ -205,17 +343,23 @@ impl PropOrderBuilder {
         order.sort_by_key(|a| a.level);
 
         // order
-        PropOrder {
+        Ok(PropOrder {
             order,
             max_dist,
+            latencies: self.latencies,
+            critical_delay,
             edges: self.edges,
-        }
+            critical_path,
+            sections: self.sections,
+        })
     }
 }
 
 #[derive(Default, Debug)]
 pub struct Tracer {
     pub(crate) tunnel: Vec<&'static str>,
+    /// (switch, case) taken in this cycle, see [`crate::coverage::Coverage`].
+    pub(crate) cases: Vec<(usize, usize)>,
 }
 impl Tracer {
     pub fn trigger_tunnel(&mut self, name: &'static str) {
@@ -224,6 +368,11 @@ impl Tracer {
         }
         self.tunnel.push(name);
     }
+    /// Record that the `case`-th case of the `switch`-th switch signal is
+    /// taken.
+    pub fn take_case(&mut self, switch: usize, case: usize) {
+        self.cases.push((switch, case));
+    }
 }
 
 // Update input and intermediate signals from output signals.
@@ -346,3 +495,63 @@ where
         (self.unit_out, self.tracer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::PropOrderBuilder;
+
+    #[test]
+    fn test_weighted_critical_path() {
+        // src -> fast1 -> fast2 -> x, src -> slow -> y
+        let mut g = PropOrderBuilder::new();
+        for unit in ["src", "fast1", "fast2", "slow"] {
+            g.add_unit_node(unit);
+        }
+        g.add_unit_output("src", "o");
+        g.add_unit_input("fast1", "i");
+        g.add_unit_output("fast1", "o");
+        g.add_unit_input("fast2", "i");
+        g.add_unit_output("fast2", "o");
+        g.add_unit_input("slow", "i");
+        g.add_unit_output("slow", "o");
+        g.add_edge("src.o".into(), "fast1.i".into());
+        g.add_edge("fast1.o".into(), "fast2.i".into());
+        g.add_edge("src.o".into(), "slow.i".into());
+        g.set_unit_latency("slow", 3);
+        let order = g.build().unwrap();
+
+        assert_eq!(order.max_dist, 3);
+        assert_eq!(order.critical_delay, 4);
+        assert_eq!(order.clock_period(), 5);
+        assert_eq!(order.critical_path, ["src", "src.o", "slow.i", "slow"]);
+    }
+
+    #[test]
+    fn test_graph_errors() {
+        // a -> u.i -> u -> u.o -> b -> a
+        let mut g = PropOrderBuilder::new();
+        g.add_unit_node("u");
+        g.add_unit_input("u", "i");
+        g.add_unit_output("u", "o");
+        g.add_intermediate("a");
+        g.add_intermediate("b");
+        g.add_edge("a".into(), "u.i".into());
+        g.add_edge("u.o".into(), "b".into());
+        g.add_edge("b".into(), "a".into());
+        let err = g.build().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the computational graph of your CPU is not a DAG, found a cycle: \
+             a -> u.i -> u -> u.o -> b -> a"
+        );
+
+        let mut g = PropOrderBuilder::new();
+        g.add_unit_node("u");
+        g.add_unit_input("u", "i");
+        g.add_unit_input("u", "j");
+        g.add_intermediate("a");
+        g.add_edge("a".into(), "u.i".into());
+        let err = g.build().unwrap_err();
+        assert_eq!(err.to_string(), "unit input `u.j` has no source");
+    }
+}
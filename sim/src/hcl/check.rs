@@ -0,0 +1,543 @@
+//! Static checks on runtime HCL, which find likely mistakes that do not stop
+//! the architecture from running. See [`Lint`].
+use super::{
+    parse::{Arch, BinaryOp, Expr, LValue, Source, UnaryOp},
+    sim::constant,
+    Value,
+};
+use crate::framework::PropOrder;
+
+/// A likely mistake in an architecture
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    /// The line where the mistake is found, if any
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl std::fmt::Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {line}: {}", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Check the architecture, whose computational graph is `order`.
+pub(super) fn lints(arch: &Arch, order: &PropOrder) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    incomplete_switches(arch, &mut lints);
+    dead_signals(arch, &mut lints);
+    unassigned_stage_fields(arch, &mut lints);
+    bubble_and_stall(arch, &mut lints);
+    lints.extend(graph_lints(order));
+    // in the order of the source, and lints without a line go last
+    lints.sort_by_key(|lint| lint.line.unwrap_or(usize::MAX));
+    lints
+}
+
+/// Check the computational graph `order`, which is also available for
+/// compiled architectures.
+pub(super) fn graph_lints(order: &PropOrder) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    unread_unit_outputs(order, &mut lints);
+    lints
+}
+
+fn is_true(expr: &Expr) -> bool {
+    matches!(expr, Expr::Bool(true)) || matches!(expr, Expr::Int(n) if *n != 0)
+}
+
+/// A switch without a default case (`1 : ..`) keeps the value of the last
+/// cycle when no case matches, which infers a latch in hardware.
+fn incomplete_switches(arch: &Arch, lints: &mut Vec<Lint>) {
+    for signal in &arch.signals {
+        if let Source::Switch(cases) = &signal.source {
+            if !cases.last().is_some_and(|case| is_true(&case.condition)) {
+                lints.push(Lint {
+                    line: Some(signal.line),
+                    message: format!(
+                        "the switch of `{}` has no default case `1 : ..`, so it keeps its \
+                         old value when no case matches",
+                        signal.name
+                    ),
+                });
+            }
+        }
+    }
+}
+
+/// Signals that are neither read by other signals nor sent to any unit or
+/// stage.
+fn dead_signals(arch: &Arch, lints: &mut Vec<Lint>) {
+    for signal in &arch.signals {
+        let name = signal.name.as_str();
+        let reads = |lvs: Vec<&LValue>| {
+            lvs.iter()
+                .any(|lv| lv.parts.len() == 1 && lv.parts[0] == name)
+        };
+        let read = arch.signals.iter().any(|s| reads(s.source.lvalues()))
+            || arch.asserts.iter().any(|a| reads(a.expr.lvalues()));
+        if !read
+            && signal.dests.is_empty()
+            && name != arch.program_counter
+            && name != arch.termination
+        {
+            lints.push(Lint {
+                line: Some(signal.line),
+                message: format!("signal `{name}` is never used"),
+            });
+        }
+    }
+}
+
+/// Stage fields that no signal is sent to always hold their default values.
+fn unassigned_stage_fields(arch: &Arch, lints: &mut Vec<Lint>) {
+    for stage in &arch.stages {
+        for field in &stage.fields {
+            let assigned = arch.signals.iter().flat_map(|s| &s.dests).any(|dest| {
+                dest.is_stage_field
+                    && dest.target.parts.len() == 2
+                    && dest.target.parts[0] == stage.short
+                    && dest.target.parts[1] == field.name
+            });
+            if !assigned {
+                lints.push(Lint {
+                    line: Some(field.line),
+                    message: format!(
+                        "stage field `{}.{}` is never assigned, so it always holds its \
+                         default value",
+                        stage.short, field.name
+                    ),
+                });
+            }
+        }
+    }
+}
+
+/// Outputs of units that no signal reads, found in the edges of the
+/// computational graph.
+fn unread_unit_outputs(order: &PropOrder, lints: &mut Vec<Lint>) {
+    for (unit, output) in &order.edges {
+        let is_unit = order
+            .order
+            .iter()
+            .any(|item| item.is_unit && item.name == unit);
+        if is_unit && !order.edges.iter().any(|(from, _)| from == output) {
+            lints.push(Lint {
+                line: None,
+                message: format!("output `{output}` of unit `{unit}` is never read"),
+            });
+        }
+    }
+}
+
+/// The maximum number of assignments of atoms to try, see
+/// [`bubble_and_stall`].
+const MAX_ASSIGNMENTS: u64 = 1 << 20;
+
+/// A constant in a comparison: (value, name)
+type Const = (u64, String);
+
+/// Atoms of a boolean formula. Comparisons of an lvalue with constants are
+/// modeled by the value of the lvalue, so that `E.icode == JX` and
+/// `E.icode == RET` can not be true at the same time.
+#[derive(Default)]
+struct Atoms {
+    /// (lvalue, the constants it is compared with)
+    values: Vec<(String, Vec<Const>)>,
+    /// other conditions, e.g. boolean lvalues and `a < b`
+    bools: Vec<String>,
+}
+
+/// An assignment of [`Atoms`]: the index of the constant each lvalue is
+/// equal to (or none of them), and the value of each boolean atom.
+struct Assignment<'a> {
+    atoms: &'a Atoms,
+    values: Vec<Option<usize>>,
+    bools: Vec<bool>,
+}
+
+/// Intermediate signals defined by expressions are inlined into the formula,
+/// up to this depth.
+const MAX_INLINE_DEPTH: usize = 16;
+
+/// The formula of the bubble or stall signal of a stage.
+struct Formula<'a> {
+    arch: &'a Arch,
+}
+
+impl<'a> Formula<'a> {
+    /// The value of a constant, e.g. `JX` or `3`.
+    fn constant(&self, expr: &Expr) -> Option<Const> {
+        match expr {
+            Expr::Int(n) => Some((*n, n.to_string())),
+            Expr::LVal(lv) => Self::constant_of(lv),
+            _ => None,
+        }
+    }
+
+    fn constant_of(lv: &LValue) -> Option<Const> {
+        match &lv.parts[..] {
+            [name] => match constant(name)?.0 {
+                Value::Word(w) => Some((w, name.clone())),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Intermediate signal defined by an expression, which can be inlined.
+    fn inlined(&self, lv: &LValue) -> Option<&'a Expr> {
+        let [name] = &lv.parts[..] else {
+            return None;
+        };
+        self.arch
+            .signals
+            .iter()
+            .find(|s| &s.name == name)
+            .and_then(|s| match &s.source {
+                Source::Expr(_, expr, _) => Some(expr),
+                Source::Switch(_) => None,
+            })
+    }
+
+    /// `lv == c`, `lv != c` or `lv in { c, .. }` where `c` are constants.
+    fn comparison(&self, expr: &Expr) -> Option<(String, Vec<Const>, bool)> {
+        match expr {
+            Expr::Binary(a, op @ (BinaryOp::Eq | BinaryOp::NotEq), b) => {
+                let (lv, c) = match (&**a, &**b) {
+                    (Expr::LVal(lv), c) | (c, Expr::LVal(lv))
+                        if Self::constant_of(lv).is_none() =>
+                    {
+                        (lv, self.constant(c)?)
+                    }
+                    _ => return None,
+                };
+                Some((lv.to_string(), vec![c], *op == BinaryOp::Eq))
+            }
+            Expr::In(e, set) => {
+                let Expr::LVal(lv) = &**e else { return None };
+                if Self::constant_of(lv).is_some() {
+                    return None;
+                }
+                let set = set
+                    .iter()
+                    .map(Self::constant_of)
+                    .collect::<Option<Vec<_>>>()?;
+                Some((lv.to_string(), set, true))
+            }
+            _ => None,
+        }
+    }
+
+    fn collect(&self, expr: &Expr, depth: usize, atoms: &mut Atoms) {
+        if let Some((lv, consts, _)) = self.comparison(expr) {
+            let index = match atoms.values.iter().position(|(name, _)| *name == lv) {
+                Some(index) => index,
+                None => {
+                    atoms.values.push((lv, Vec::new()));
+                    atoms.values.len() - 1
+                }
+            };
+            let known = &mut atoms.values[index].1;
+            for c in consts {
+                if !known.iter().any(|(v, _)| *v == c.0) {
+                    known.push(c);
+                }
+            }
+            return;
+        }
+        match expr {
+            Expr::Int(_) | Expr::Bool(_) => {}
+            Expr::Unary(UnaryOp::Not, e) => self.collect(e, depth, atoms),
+            Expr::Binary(a, BinaryOp::And | BinaryOp::Or, b) => {
+                self.collect(a, depth, atoms);
+                self.collect(b, depth, atoms);
+            }
+            Expr::LVal(lv) if depth < MAX_INLINE_DEPTH && self.inlined(lv).is_some() => {
+                self.collect(self.inlined(lv).unwrap(), depth + 1, atoms)
+            }
+            e => {
+                let name = show(e);
+                if !atoms.bools.contains(&name) {
+                    atoms.bools.push(name);
+                }
+            }
+        }
+    }
+
+    fn eval(&self, expr: &Expr, depth: usize, asg: &Assignment) -> bool {
+        if let Some((lv, consts, eq)) = self.comparison(expr) {
+            let index = asg
+                .atoms
+                .values
+                .iter()
+                .position(|(name, _)| *name == lv)
+                .unwrap();
+            let known = &asg.atoms.values[index].1;
+            let hit =
+                asg.values[index].is_some_and(|i| consts.iter().any(|(v, _)| *v == known[i].0));
+            return hit == eq;
+        }
+        match expr {
+            Expr::Int(n) => *n != 0,
+            Expr::Bool(b) => *b,
+            Expr::Unary(UnaryOp::Not, e) => !self.eval(e, depth, asg),
+            Expr::Binary(a, BinaryOp::And, b) => {
+                self.eval(a, depth, asg) && self.eval(b, depth, asg)
+            }
+            Expr::Binary(a, BinaryOp::Or, b) => {
+                self.eval(a, depth, asg) || self.eval(b, depth, asg)
+            }
+            Expr::LVal(lv) if depth < MAX_INLINE_DEPTH && self.inlined(lv).is_some() => {
+                self.eval(self.inlined(lv).unwrap(), depth + 1, asg)
+            }
+            e => {
+                let name = show(e);
+                let index = asg.atoms.bools.iter().position(|b| *b == name).unwrap();
+                asg.bools[index]
+            }
+        }
+    }
+}
+
+impl Assignment<'_> {
+    /// The `n`-th assignment, where `n` is less than [`Atoms::count`].
+    fn nth(atoms: &Atoms, mut n: u64) -> Assignment<'_> {
+        let mut values = Vec::new();
+        for (_, known) in &atoms.values {
+            let size = known.len() as u64 + 1;
+            let i = (n % size) as usize;
+            values.push((i < known.len()).then_some(i));
+            n /= size;
+        }
+        let bools = (0..atoms.bools.len()).map(|i| n >> i & 1 == 1).collect();
+        Assignment {
+            atoms,
+            values,
+            bools,
+        }
+    }
+}
+
+impl std::fmt::Display for Assignment<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut conds = Vec::new();
+        for ((lv, known), value) in self.atoms.values.iter().zip(&self.values) {
+            conds.push(match value {
+                Some(i) => format!("{lv} == {}", known[*i].1),
+                None => {
+                    let names: Vec<_> = known.iter().map(|(_, name)| name.as_str()).collect();
+                    format!("{lv} not in {{ {} }}", names.join(", "))
+                }
+            });
+        }
+        for (name, value) in self.atoms.bools.iter().zip(&self.bools) {
+            conds.push(if *value {
+                name.clone()
+            } else {
+                format!("!({name})")
+            });
+        }
+        write!(f, "{}", conds.join(", "))
+    }
+}
+
+impl Atoms {
+    /// The number of assignments, or `None` if there are too many.
+    fn count(&self) -> Option<u64> {
+        let mut count: u64 = 1 << self.bools.len().min(63);
+        for (_, known) in &self.values {
+            count = count.checked_mul(known.len() as u64 + 1)?;
+        }
+        (count <= MAX_ASSIGNMENTS).then_some(count)
+    }
+}
+
+/// The bubble and stall signals of a stage should never be true at the same
+/// time. Their formulas are checked by trying all assignments of the atoms.
+fn bubble_and_stall(arch: &Arch, lints: &mut Vec<Lint>) {
+    let formula = Formula { arch };
+    let control = |cur: &str, field: &str| {
+        arch.signals.iter().find(|s| {
+            s.dests.iter().any(|d| {
+                d.is_stage_field
+                    && d.target.parts.len() == 2
+                    && d.target.parts[0] == cur
+                    && d.target.parts[1] == field
+            })
+        })
+    };
+    for (cur, _) in &arch.stage_alias {
+        let (Some(bubble), Some(stall)) = (control(cur, "bubble"), control(cur, "stall")) else {
+            continue;
+        };
+        // a signal whose value is a switch is an atom itself
+        let expr = |name: &str| {
+            Expr::LVal(LValue {
+                parts: vec![name.to_string()],
+            })
+        };
+        let both = Expr::Binary(
+            Box::new(expr(&bubble.name)),
+            BinaryOp::And,
+            Box::new(expr(&stall.name)),
+        );
+        let mut atoms = Atoms::default();
+        formula.collect(&both, 0, &mut atoms);
+        let Some(count) = atoms.count() else {
+            continue;
+        };
+        if let Some(asg) = (0..count)
+            .map(|n| Assignment::nth(&atoms, n))
+            .find(|asg| formula.eval(&both, 0, asg))
+        {
+            lints.push(Lint {
+                line: Some(bubble.line),
+                message: format!(
+                    "`{}` and `{}` of stage `{cur}` can be true at the same time, e.g. when {asg}",
+                    bubble.name, stall.name
+                ),
+            });
+        }
+    }
+}
+
+/// Show the expression in HCL syntax, with binary operations in parentheses.
+fn show(expr: &Expr) -> String {
+    match expr {
+        Expr::Int(n) => n.to_string(),
+        Expr::Bool(b) => b.to_string(),
+        Expr::LVal(lv) => lv.to_string(),
+        Expr::Unary(op, e) => {
+            let op = match op {
+                UnaryOp::Not => "!",
+                UnaryOp::BitNot => "~",
+                UnaryOp::Neg => "-",
+            };
+            format!("{op}{}", show(e))
+        }
+        Expr::Binary(a, op, b) => {
+            let op = match op {
+                BinaryOp::Mul => "*",
+                BinaryOp::Add => "+",
+                BinaryOp::Sub => "-",
+                BinaryOp::Shl => "<<",
+                BinaryOp::Shr => ">>",
+                BinaryOp::Lt => "<",
+                BinaryOp::Le => "<=",
+                BinaryOp::Gt => ">",
+                BinaryOp::Ge => ">=",
+                BinaryOp::Eq => "==",
+                BinaryOp::NotEq => "!=",
+                BinaryOp::BitAnd => "&",
+                BinaryOp::BitXor => "^",
+                BinaryOp::BitOr => "|",
+                BinaryOp::And => "&&",
+                BinaryOp::Or => "||",
+            };
+            let side = |e: &Expr| match e {
+                Expr::Binary(..) | Expr::In(..) => format!("({})", show(e)),
+                e => show(e),
+            };
+            format!("{} {op} {}", side(a), side(b))
+        }
+        Expr::In(e, set) => {
+            let set: Vec<_> = set.iter().map(LValue::to_string).collect();
+            format!("{} in {{ {} }}", show(e), set.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lints, Lint};
+    use crate::framework::{PropOrder, PropOrderBuilder};
+
+    fn check(body: &str, order: &PropOrder) -> Vec<String> {
+        let src = format!(
+            "define_stages! {{ DStage d {{ icode: u8 = NOP, valC: u64 = 0 }} }}
+            #![hardware = crate::architectures::hardware_pipe]
+            #![program_counter = pc]
+            #![termination = term]
+            #![stage_alias(D => d)]
+            u64 pc = 0;
+            bool term = false;
+            {body}"
+        );
+        let arch = super::super::parse::parse(&src).unwrap();
+        lints(&arch, order).iter().map(Lint::to_string).collect()
+    }
+
+    #[test]
+    fn test_lints() {
+        let order = PropOrder::default();
+        let lints = check(
+            "u8 icode = [ D.icode == JX : D.icode; ] -> d.icode;
+            u64 unused = D.valC + 8;",
+            &order,
+        );
+        assert_eq!(
+            lints,
+            [
+                "line 1: stage field `d.valC` is never assigned, so it always holds its \
+                 default value",
+                "line 8: the switch of `icode` has no default case `1 : ..`, so it keeps \
+                 its old value when no case matches",
+                "line 9: signal `unused` is never used",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unread_unit_outputs() {
+        let mut g = PropOrderBuilder::new();
+        g.add_unit_node("alu");
+        g.add_unit_output("alu", "e");
+        g.add_unit_output("alu", "zf");
+        g.add_intermediate("valE");
+        g.add_edge("alu.e".into(), "valE".into());
+        let order = g.build().unwrap();
+        let lints = check("u8 icode = 0 -> d.icode; u64 valC = 0 -> d.valC;", &order);
+        assert_eq!(lints, ["output `alu.zf` of unit `alu` is never read"]);
+    }
+
+    #[test]
+    fn test_bubble_and_stall() {
+        let order = PropOrder::default();
+        let fields = "u8 icode = 0 -> d.icode; u64 valC = 0 -> d.valC;";
+        // a load/use hazard stalls, while a mispredicted branch or a return
+        // (without the hazard) inserts a bubble
+        let lints = check(
+            &format!(
+                "{fields}
+                bool load_use = D.icode in {{ MRMOVQ, POPQ }} && cond.cnd;
+                bool d_stall = load_use;
+                bool d_bubble = D.icode == JX && !cond.cnd || !load_use && D.icode == RET;
+                @set_stage(d, {{ stall: d_stall, bubble: d_bubble }});"
+            ),
+            &order,
+        );
+        assert_eq!(lints, Vec::<String>::new());
+
+        let lints = check(
+            &format!(
+                "{fields}
+                bool load_use = D.icode in {{ MRMOVQ, POPQ }} && cond.cnd;
+                bool d_stall = load_use;
+                bool d_bubble = D.icode == MRMOVQ;
+                @set_stage(d, {{ stall: d_stall, bubble: d_bubble }});"
+            ),
+            &order,
+        );
+        assert_eq!(
+            lints,
+            [
+                "line 11: `d_bubble` and `d_stall` of stage `d` can be true at the same time, \
+                 e.g. when D.icode == MRMOVQ, cond.cnd"
+            ]
+        );
+    }
+}
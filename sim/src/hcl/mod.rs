@@ -0,0 +1,384 @@
+//! Load an architecture from a `.hcl` file at runtime, without recompiling
+//! the simulator.
+//!
+//! A `.hcl` file holds the pipeline registers in the syntax of
+//! [`crate::define_stages`], followed by the body of a [`sim_macro::hcl`]
+//! block:
+//!
+//! ```plain
+//! define_stages! {
+//!     SEQstage s { pc: u64 = 0 }
+//! }
+//!
+//! #![hardware = crate::architectures::hardware_seq]
+//! #![program_counter = pc]
+//! #![termination = prog_term]
+//! #![stage_alias(S => s)]
+//!
+//! u64 pc = S.pc -> imem.pc;
+//! ...
+//! ```
+//!
+//! The architecture runs on one of the hardware modules compiled into the
+//! simulator, selected by `#![hardware = ..]`. Signals are interpreted
+//! through a [`PropCircuit`](crate::framework::PropCircuit) built from the
+//! file. Datapath drawings are not supported.
+
+mod check;
+mod parse;
+mod sim;
+
+use anyhow::{bail, Context, Result};
+
+pub use check::Lint;
+pub use sim::HclSim;
+
+use crate::{
+    architectures::{hardware_pipe, hardware_seq},
+    framework::{CpuSim, HardwareUnits, MemData, PropOrderBuilder, MEM_SIZE},
+    isa::{ConditionCode, Stat},
+};
+
+/// Value of a signal in a runtime HCL architecture
+#[derive(Debug, Clone)]
+pub enum Value {
+    Bool(bool),
+    /// Integers and [`Stat`]
+    Word(u64),
+    Bytes(Vec<u8>),
+    Cc(ConditionCode),
+}
+
+impl Value {
+    /// Convert the value to a 64-bit word, see [`crate::framework::AsWord`].
+    pub fn as_word(&self) -> Option<u64> {
+        match self {
+            Value::Bool(b) => Some(*b as u64),
+            Value::Word(w) => Some(*w),
+            _ => None,
+        }
+    }
+}
+
+/// Types of unit inputs and outputs, which are accessed by name in runtime
+/// HCL.
+pub trait Signal: Sized {
+    fn to_value(&self) -> Value;
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+macro_rules! impl_signal {
+    ($($t:ty),*) => {
+        $(impl Signal for $t {
+            fn to_value(&self) -> Value {
+                Value::Word(*self as u64)
+            }
+            fn from_value(value: &Value) -> Option<Self> {
+                value.as_word().map(|w| w as $t)
+            }
+        })*
+    };
+}
+
+impl_signal!(u8, u16, u32, u64, usize, i8, i16, i32, i64);
+
+impl Signal for bool {
+    fn to_value(&self) -> Value {
+        Value::Bool(*self)
+    }
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_word().map(|w| w != 0)
+    }
+}
+
+impl Signal for Stat {
+    fn to_value(&self) -> Value {
+        Value::Word(*self as u64)
+    }
+    fn from_value(value: &Value) -> Option<Self> {
+        Stat::from_code(value.as_word()?)
+    }
+}
+
+impl<const N: usize> Signal for [u8; N] {
+    fn to_value(&self) -> Value {
+        Value::Bytes(self.to_vec())
+    }
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bytes(bytes) => bytes.as_slice().try_into().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl Signal for ConditionCode {
+    fn to_value(&self) -> Value {
+        Value::Cc(*self)
+    }
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Cc(cc) => Some(*cc),
+            _ => None,
+        }
+    }
+}
+
+/// Hardware units that can be driven by runtime HCL. This is implemented by
+/// [`crate::define_units`].
+pub trait HclUnits: HardwareUnits + Sized + 'static {
+    type UnitIn: Default + Clone;
+    type UnitOut: Default + Clone;
+
+    /// Add units and their ports to the graph builder.
+    fn setup(builder: &mut PropOrderBuilder);
+
+    /// Execute the unit with the given name.
+    fn run(&mut self, name: &'static str, sigs: (&Self::UnitIn, &mut Self::UnitOut));
+
+    fn input(unit_in: &Self::UnitIn, unit: &str, field: &str) -> Option<Value>;
+
+    fn set_input(unit_in: &mut Self::UnitIn, unit: &str, field: &str, value: &Value) -> bool;
+
+    fn output(unit_out: &Self::UnitOut, unit: &str, field: &str) -> Option<Value>;
+
+    fn set_output(unit_out: &mut Self::UnitOut, unit: &str, field: &str, value: &Value) -> bool;
+}
+
+/// Whether the architecture name refers to a runtime HCL file
+pub fn is_hcl_path(arch: &str) -> bool {
+    arch.ends_with(".hcl")
+}
+
+/// Load the architecture from a `.hcl` file.
+pub fn load(path: &str, memory: MemData, tty_out: bool) -> Result<Box<dyn CpuSim>> {
+    let src =
+        std::fs::read_to_string(path).with_context(|| format!("could not read file `{}`", path))?;
+    load_str(&src, memory, tty_out).with_context(|| format!("failed to load `{}`", path))
+}
+
+/// Load the architecture from the source of a `.hcl` file.
+pub fn load_str(src: &str, memory: MemData, tty_out: bool) -> Result<Box<dyn CpuSim>> {
+    let arch = parse::parse(src)?;
+    Ok(match arch.hardware.as_str() {
+        "hardware_seq" => Box::new(HclSim::<hardware_seq::Units>::new(arch, memory, tty_out)?),
+        "hardware_pipe" => Box::new(HclSim::<hardware_pipe::Units>::new(arch, memory, tty_out)?),
+        other => bail!("unknown hardware module `{}`", other),
+    })
+}
+
+/// Check the architecture for likely mistakes, see [`Lint`]. `arch` is
+/// either the path of a `.hcl` file or the name of a compiled architecture.
+/// The source of a compiled architecture is not available, so only its
+/// computational graph is checked.
+pub fn check(arch: &str) -> Result<Vec<Lint>> {
+    if !is_hcl_path(arch) {
+        let sim = crate::architectures::create_sim(
+            arch.to_string(),
+            MemData::init([0; MEM_SIZE]),
+            false,
+        )?;
+        return Ok(check::graph_lints(sim.proporder()));
+    }
+    let src =
+        std::fs::read_to_string(arch).with_context(|| format!("could not read file `{}`", arch))?;
+    check_str(&src).with_context(|| format!("failed to load `{}`", arch))
+}
+
+/// Check the source of a `.hcl` file for likely mistakes. The file must load
+/// without errors first.
+pub fn check_str(src: &str) -> Result<Vec<Lint>> {
+    let sim = load_str(src, MemData::init([0; MEM_SIZE]), false)?;
+    let arch = parse::parse(src)?;
+    Ok(check::lints(&arch, sim.proporder()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        architectures::create_sim,
+        framework::{MemData, MEM_SIZE},
+        test::SimTester,
+    };
+
+    fn port(name: &str) -> String {
+        format!(
+            "{}/src/architectures/hcl/{}.hcl",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        )
+    }
+
+    #[test]
+    fn test_hcl_ports() -> anyhow::Result<()> {
+        for arch in ["seq_std", "pipe_std"] {
+            let tester = SimTester::new(&port(arch)).expect("fail to load");
+            tester.test_opq()?;
+            tester.test_cmov()?;
+            tester.test_jm()?;
+        }
+        Ok(())
+    }
+
+    /// The runtime ports behave exactly as the compiled architectures.
+    #[test]
+    fn test_same_as_compiled() -> anyhow::Result<()> {
+        for arch in ["seq_std", "pipe_std"] {
+            let a = crate::assemble(crate::asm::tests::RSUM_YS, Default::default())?;
+            let mut sims = [arch.to_string(), port(arch)]
+                .map(|kind| create_sim(kind, MemData::init(a.obj.init_mem()), false).unwrap());
+            assert_eq!(sims[0].cycle_cost(), sims[1].cycle_cost(), "{arch}");
+            while !sims[0].is_terminate() {
+                for sim in &mut sims {
+                    sim.step();
+                }
+                let [expected, actual] = &sims;
+                assert_eq!(expected.program_counter(), actual.program_counter());
+                assert_eq!(expected.is_terminate(), actual.is_terminate());
+                assert_eq!(expected.registers(), actual.registers());
+                assert_eq!(expected.data_accesses(), actual.data_accesses());
+                assert_eq!(expected.stage_instructions(), actual.stage_instructions());
+                assert_eq!(expected.retired_instruction(), actual.retired_instruction());
+                let info = |sim: &dyn crate::framework::CpuSim| {
+                    let info = sim.get_stage_info();
+                    info.into_iter()
+                        .map(|s| (s.name, s.signals))
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(info(expected.as_ref()), info(actual.as_ref()));
+                for name in ["icode", "f_pc", "D.icode", "e.dstE", "M.cnd", "W.stat"] {
+                    assert_eq!(expected.signal(name), actual.signal(name), "{name}");
+                }
+            }
+            assert!(sims[1].is_terminate());
+            let counts = |sim: &dyn crate::framework::CpuSim| {
+                let switches = &sim.coverage().switches;
+                switches
+                    .iter()
+                    .map(|s| (s.signal.clone(), s.cases.iter().map(|c| c.count).collect()))
+                    .collect::<Vec<(String, Vec<u64>)>>()
+            };
+            assert_eq!(counts(sims[0].as_ref()), counts(sims[1].as_ref()), "{arch}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let load = |body: &str| {
+            let src = format!(
+                "define_stages! {{ S s {{ pc: u64 = 0 }} }}
+                #![hardware = crate::architectures::hardware_seq]
+                #![program_counter = pc]
+                #![termination = term]
+                #![stage_alias(S => s)]
+                {body}"
+            );
+            super::load_str(&src, MemData::init([0; MEM_SIZE]), false)
+                .map(|_| ())
+                .map_err(|e| format!("{:#}", e))
+        };
+        let err = load("u64 pc = S.pc;\nbool term = foo;").unwrap_err();
+        assert!(err.contains("`term` (line 7)"), "{err}");
+        assert!(err.contains("unknown signal or constant `foo`"), "{err}");
+        let err = load("u64 pc = S.pc -> imem.foo; bool term = true;").unwrap_err();
+        assert!(err.contains("unknown unit input `imem.foo`"), "{err}");
+        let err = load("u64 pc = S.pc; bool term = imem.align;").unwrap_err();
+        assert!(err.contains("expected `bool`, found `[u8; 9]`"), "{err}");
+        let err =
+            load("u64 pc = S.pc; bool term = true; @set_input(imem, { pc: x });").unwrap_err();
+        assert!(err.contains("signal `x` is not defined"), "{err}");
+        let err = load("u64 pc = b; u64 b = pc; bool term = true;").unwrap_err();
+        assert!(err.contains("found a cycle: b -> pc -> b"), "{err}");
+        let err = load("u64 pc = S.pc -> s.pc; bool term = true; @set_stage(s, { pc: pc });")
+            .unwrap_err();
+        assert!(
+            err.contains("stage field `s.pc` is set more than once"),
+            "{err}"
+        );
+        let err = load("u64 pc = S.pc; bool term = true").unwrap_err();
+        assert!(err.contains("fail to parse HCL file"), "{err}");
+
+        // creating a simulator reports the error instead of panicking
+        let create = |kind: &str| {
+            create_sim(kind.to_string(), MemData::init([0; MEM_SIZE]), false).map(|_| ())
+        };
+        let err = format!("{:#}", create("no-such-file.hcl").unwrap_err());
+        assert!(
+            err.contains("could not read file `no-such-file.hcl`"),
+            "{err}"
+        );
+        let err = format!("{:#}", create("no_such_arch").unwrap_err());
+        assert_eq!(err, "unknown architecture `no_such_arch`");
+    }
+
+    /// Operators behave as in the compiled architectures: comparisons are
+    /// signed and arithmetic wraps around at the width of the operands.
+    #[test]
+    fn test_operators() -> anyhow::Result<()> {
+        let src = std::fs::read_to_string(port("seq_std"))?;
+        let ops = "u8 small = 4;
+            u64 big = 0 - 3;
+            bool ordered = big < 3 && !(big >= 0);
+            u8 narrow = (small - 5) >> 4;
+            u8 shifted = small << 9;
+            bool zero = !small;
+            u8 wrapped = (imem.icode - 1) >> 4;
+            bool no_icode = !imem.icode;";
+        let mut sim = super::load_str(
+            &format!("{src}\n{ops}"),
+            MemData::init([0; MEM_SIZE]),
+            false,
+        )?;
+        sim.step();
+        assert_eq!(sim.signal("ordered"), Some(1));
+        assert_eq!(sim.signal("narrow"), Some(0xf));
+        assert_eq!(sim.signal("shifted"), Some(8));
+        assert_eq!(sim.signal("zero"), Some(0));
+        assert_eq!(sim.signal("wrapped"), Some(0xf));
+        assert_eq!(sim.signal("no_icode"), Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_assertions() -> anyhow::Result<()> {
+        let src = std::fs::read_to_string(port("seq_std"))?;
+        let a = crate::assemble(crate::asm::tests::RSUM_YS, Default::default())?;
+        let load = |asserts: &str| {
+            super::load_str(
+                &format!("{src}\n{asserts}"),
+                MemData::init(a.obj.init_mem()),
+                false,
+            )
+        };
+        let mut sim = load(
+            r#"@assert(s.pc == new_pc, "the next pc is latched");
+            @assert(icode != IRMOVQ || pc == 0, "only the first instruction is irmovq");"#,
+        )?;
+        while !sim.is_terminate() {
+            sim.step();
+        }
+        assert_eq!(
+            sim.failed_assertion().unwrap(),
+            "assertion `icode != IRMOVQ || pc == 0` failed in cycle 2: only the first \
+             instruction is irmovq\n    icode = 0x3\n    pc = 0xa"
+        );
+        let err = load(r#"@assert(icode, "not a condition");"#)
+            .map(|_| ())
+            .unwrap_err();
+        assert!(
+            format!("{err:#}").contains("expected `bool`, found `u8`"),
+            "{err:#}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_ports() -> anyhow::Result<()> {
+        for arch in ["seq_std", "pipe_std"] {
+            assert_eq!(super::check(&port(arch))?, []);
+            assert_eq!(super::check(arch)?, []);
+        }
+        Ok(())
+    }
+}
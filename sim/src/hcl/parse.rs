@@ -0,0 +1,715 @@
+//! Parse `.hcl` files into an AST, see `hcl.pest` for the grammar.
+use anyhow::{anyhow, bail, Context, Result};
+use pest::{
+    iterators::{Pair, Pairs},
+    pratt_parser::{Assoc, Op, PrattParser},
+    Parser,
+};
+use pest_derive::Parser;
+
+use super::Value;
+use crate::isa::{ConditionCode, Stat};
+
+#[derive(Parser)]
+#[grammar = "src/hcl/hcl.pest"] // relative to src
+struct HclParser;
+
+/// e.g. `imem.error`, `D.icode`, `f_pc`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct LValue {
+    pub parts: Vec<String>,
+}
+
+impl std::fmt::Display for LValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.parts.join("."))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum UnaryOp {
+    Not,
+    BitNot,
+    Neg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum BinaryOp {
+    Mul,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    NotEq,
+    BitAnd,
+    BitXor,
+    BitOr,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone)]
+pub(super) enum Expr {
+    Int(u64),
+    Bool(bool),
+    LVal(LValue),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+    /// `expr in { a, b, c }`
+    In(Box<Expr>, Vec<LValue>),
+}
+
+impl Expr {
+    /// All lvalues referenced by the expression.
+    pub fn lvalues(&self) -> Vec<&LValue> {
+        match self {
+            Expr::Int(_) | Expr::Bool(_) => Vec::new(),
+            Expr::LVal(lv) => vec![lv],
+            Expr::Unary(_, e) => e.lvalues(),
+            Expr::Binary(a, _, b) => {
+                let mut lvs = a.lvalues();
+                lvs.extend(b.lvalues());
+                lvs
+            }
+            Expr::In(e, set) => {
+                let mut lvs = e.lvalues();
+                lvs.extend(set);
+                lvs
+            }
+        }
+    }
+}
+
+/// Types of signals and stage fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Ty {
+    Bool,
+    Int { bits: u32, signed: bool },
+    Stat,
+    Cc,
+    Bytes(usize),
+}
+
+impl Ty {
+    pub const U8: Ty = Ty::int(8, false);
+    pub const U64: Ty = Ty::int(64, false);
+
+    const fn int(bits: u32, signed: bool) -> Self {
+        Ty::Int { bits, signed }
+    }
+
+    fn parse(pair: Pair<'_, Rule>) -> Result<Self> {
+        let line = pair.line_col().0;
+        let inner = pair.into_inner().next().expect("invalid type");
+        Ok(match inner.as_rule() {
+            Rule::array_ty => {
+                let mut it = inner.into_inner();
+                let elem = it.next().expect("invalid array type").as_str();
+                if elem != "u8" {
+                    bail!("line {line}: only arrays of u8 are supported");
+                }
+                Ty::Bytes(parse_int(it.next().expect("invalid array type").as_str())? as usize)
+            }
+            _ => match inner.as_str() {
+                "bool" => Ty::Bool,
+                "u8" => Ty::int(8, false),
+                "u16" => Ty::int(16, false),
+                "u32" => Ty::int(32, false),
+                "u64" | "usize" => Ty::int(64, false),
+                "i8" => Ty::int(8, true),
+                "i16" => Ty::int(16, true),
+                "i32" => Ty::int(32, true),
+                "i64" => Ty::int(64, true),
+                "Stat" => Ty::Stat,
+                "ConditionCode" => Ty::Cc,
+                other => bail!("line {line}: unsupported type `{other}`"),
+            },
+        })
+    }
+
+    /// The default value, the same as [`Default::default`] of the Rust type.
+    pub fn default_value(self) -> Value {
+        match self {
+            Ty::Bool => Value::Bool(false),
+            Ty::Int { .. } => Value::Word(0),
+            Ty::Stat => Value::Word(Stat::default() as u64),
+            Ty::Cc => Value::Cc(ConditionCode::default()),
+            Ty::Bytes(n) => Value::Bytes(vec![0; n]),
+        }
+    }
+
+    /// Whether a value of the type is a word.
+    /// Width of the words of the type
+    pub fn bits(self) -> u32 {
+        match self {
+            Ty::Int { bits, .. } => bits,
+            _ => 64,
+        }
+    }
+
+    pub fn is_word(self) -> bool {
+        matches!(self, Ty::Bool | Ty::Int { .. } | Ty::Stat)
+    }
+
+    /// Convert the value to the type, as `as` does in Rust. Return `None` if
+    /// the value is of another kind.
+    pub fn convert(self, value: Value) -> Option<Value> {
+        Some(match (self, value) {
+            (Ty::Bool, v @ (Value::Bool(_) | Value::Word(_))) => {
+                Value::Bool(v.as_word() != Some(0))
+            }
+            (Ty::Int { bits, signed }, v @ (Value::Bool(_) | Value::Word(_))) => {
+                let w = v.as_word()?;
+                if bits == 64 {
+                    return Some(Value::Word(w));
+                }
+                let mask = (1 << bits) - 1;
+                if signed && (w & mask) >> (bits - 1) != 0 {
+                    Value::Word(w | !mask)
+                } else {
+                    Value::Word(w & mask)
+                }
+            }
+            (Ty::Stat, Value::Word(w)) => Value::Word(w),
+            (Ty::Cc, Value::Cc(cc)) => Value::Cc(cc),
+            (Ty::Bytes(n), Value::Bytes(b)) if b.len() == n => Value::Bytes(b),
+            _ => return None,
+        })
+    }
+}
+
+impl std::fmt::Display for Ty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ty::Bool => write!(f, "bool"),
+            Ty::Int { bits, signed } => write!(f, "{}{bits}", if *signed { 'i' } else { 'u' }),
+            Ty::Stat => write!(f, "Stat"),
+            Ty::Cc => write!(f, "ConditionCode"),
+            Ty::Bytes(n) => write!(f, "[u8; {n}]"),
+        }
+    }
+}
+
+/// A field of a pipeline register
+#[derive(Debug)]
+pub(super) struct FieldDef {
+    pub name: String,
+    pub ty: Ty,
+    pub default: Expr,
+    pub line: usize,
+}
+
+/// A pipeline register declared in `define_stages!`
+#[derive(Debug)]
+pub(super) struct StageDef {
+    pub short: String,
+    pub fields: Vec<FieldDef>,
+}
+
+/// e.g. `imem.error : NOP`
+#[derive(Debug)]
+pub(super) struct Case {
+    pub tunnel: Option<String>,
+    pub condition: Expr,
+    pub value: Expr,
+    /// Source of the case without the tunnel, e.g. `imem.error : Adr`
+    pub text: String,
+    pub line: usize,
+}
+
+#[derive(Debug)]
+pub(super) enum Source {
+    Switch(Vec<Case>),
+    /// (tunnel, expression, source of the expression)
+    Expr(Option<String>, Expr, String),
+}
+
+impl Source {
+    pub fn lvalues(&self) -> Vec<&LValue> {
+        match self {
+            Source::Switch(cases) => cases
+                .iter()
+                .flat_map(|case| {
+                    let mut lvs = case.condition.lvalues();
+                    lvs.extend(case.value.lvalues());
+                    lvs
+                })
+                .collect(),
+            Source::Expr(_, expr, _) => expr.lvalues(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct Dest {
+    pub tunnel: Option<String>,
+    pub target: LValue,
+    /// this destination is stage field or device input
+    pub is_stage_field: bool,
+}
+
+#[derive(Debug)]
+pub(super) struct SignalDef {
+    pub name: String,
+    pub ty: Ty,
+    pub source: Source,
+    pub dests: Vec<Dest>,
+    /// index of the section
+    pub section: Option<usize>,
+    pub line: usize,
+}
+
+/// `@assert(expr, "message")`
+#[derive(Debug)]
+pub(super) struct AssertDef {
+    pub expr: Expr,
+    /// source of the expression
+    pub text: String,
+    pub message: String,
+    pub line: usize,
+}
+
+/// A parsed `.hcl` file
+#[derive(Debug, Default)]
+pub(super) struct Arch {
+    /// The name of the hardware module, e.g. `hardware_seq`
+    pub hardware: String,
+    pub program_counter: String,
+    pub termination: String,
+    /// (cur, pre)
+    pub stage_alias: Vec<(String, String)>,
+    /// stage fields shown in the status table
+    pub status: Vec<String>,
+    pub stages: Vec<StageDef>,
+    pub sections: Vec<String>,
+    /// (unit, index of the section where its inputs are set)
+    pub unit_sections: Vec<(String, usize)>,
+    pub signals: Vec<SignalDef>,
+    pub asserts: Vec<AssertDef>,
+}
+
+pub(super) fn parse_int(s: &str) -> Result<u64> {
+    let r = match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    r.with_context(|| format!("invalid integer `{s}`"))
+}
+
+fn parse_lvalue(pair: Pair<'_, Rule>) -> LValue {
+    LValue {
+        parts: pair.into_inner().map(|p| p.as_str().to_string()).collect(),
+    }
+}
+
+/// Source of an expression with whitespaces collapsed
+fn source_text(pair: &Pair<'_, Rule>) -> String {
+    pair.as_str()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn parse_tunnel(pair: Pair<'_, Rule>) -> String {
+    pair.into_inner()
+        .next()
+        .expect("invalid tunnel")
+        .as_str()
+        .to_string()
+}
+
+struct ExprParser(PrattParser<Rule>);
+
+impl ExprParser {
+    fn new() -> Self {
+        // from the lowest precedence to the highest, as in C
+        Self(
+            PrattParser::new()
+                .op(Op::infix(Rule::or, Assoc::Left))
+                .op(Op::infix(Rule::and, Assoc::Left))
+                .op(Op::infix(Rule::bit_or, Assoc::Left))
+                .op(Op::infix(Rule::bit_xor, Assoc::Left))
+                .op(Op::infix(Rule::bit_and, Assoc::Left))
+                .op(Op::infix(Rule::eq, Assoc::Left)
+                    | Op::infix(Rule::ne, Assoc::Left)
+                    | Op::postfix(Rule::in_set))
+                .op(Op::infix(Rule::lt, Assoc::Left)
+                    | Op::infix(Rule::le, Assoc::Left)
+                    | Op::infix(Rule::gt, Assoc::Left)
+                    | Op::infix(Rule::ge, Assoc::Left))
+                .op(Op::infix(Rule::shl, Assoc::Left) | Op::infix(Rule::shr, Assoc::Left))
+                .op(Op::infix(Rule::add, Assoc::Left) | Op::infix(Rule::sub, Assoc::Left))
+                .op(Op::infix(Rule::mul, Assoc::Left))
+                .op(Op::prefix(Rule::not) | Op::prefix(Rule::bit_not) | Op::prefix(Rule::neg)),
+        )
+    }
+
+    fn parse(&self, pairs: Pairs<'_, Rule>) -> Result<Expr> {
+        self.0
+            .map_primary(|primary| {
+                Ok(match primary.as_rule() {
+                    Rule::int => Expr::Int(parse_int(primary.as_str())?),
+                    Rule::boolean => Expr::Bool(primary.as_str() == "true"),
+                    Rule::lvalue => Expr::LVal(parse_lvalue(primary)),
+                    Rule::expr => self.parse(primary.into_inner())?,
+                    rule => unreachable!("unexpected rule {:?}", rule),
+                })
+            })
+            .map_prefix(|op, rhs| {
+                let op = match op.as_rule() {
+                    Rule::not => UnaryOp::Not,
+                    Rule::bit_not => UnaryOp::BitNot,
+                    Rule::neg => UnaryOp::Neg,
+                    rule => unreachable!("unexpected rule {:?}", rule),
+                };
+                Ok(Expr::Unary(op, Box::new(rhs?)))
+            })
+            .map_postfix(|lhs, op| {
+                let set = op
+                    .into_inner()
+                    .filter(|p| p.as_rule() == Rule::lvalue)
+                    .map(parse_lvalue)
+                    .collect();
+                Ok(Expr::In(Box::new(lhs?), set))
+            })
+            .map_infix(|lhs, op, rhs| {
+                let op = match op.as_rule() {
+                    Rule::mul => BinaryOp::Mul,
+                    Rule::add => BinaryOp::Add,
+                    Rule::sub => BinaryOp::Sub,
+                    Rule::shl => BinaryOp::Shl,
+                    Rule::shr => BinaryOp::Shr,
+                    Rule::lt => BinaryOp::Lt,
+                    Rule::le => BinaryOp::Le,
+                    Rule::gt => BinaryOp::Gt,
+                    Rule::ge => BinaryOp::Ge,
+                    Rule::eq => BinaryOp::Eq,
+                    Rule::ne => BinaryOp::NotEq,
+                    Rule::bit_and => BinaryOp::BitAnd,
+                    Rule::bit_xor => BinaryOp::BitXor,
+                    Rule::bit_or => BinaryOp::BitOr,
+                    Rule::and => BinaryOp::And,
+                    Rule::or => BinaryOp::Or,
+                    rule => unreachable!("unexpected rule {:?}", rule),
+                };
+                Ok(Expr::Binary(Box::new(lhs?), op, Box::new(rhs?)))
+            })
+            .parse(pairs)
+    }
+}
+
+/// Parse the source of a `.hcl` file.
+pub(super) fn parse(src: &str) -> Result<Arch> {
+    let file = HclParser::parse(Rule::file, src)
+        .map_err(|e| anyhow!("{e}"))
+        .context("fail to parse HCL file")?
+        .next()
+        .expect("invalid parse rule");
+    let exprs = ExprParser::new();
+
+    let mut arch = Arch::default();
+    // (unit or stage, field, signal, line, is_stage)
+    let mut assigns: Vec<(String, String, String, usize, bool)> = Vec::new();
+    let mut hardware = None;
+    let mut program_counter = None;
+    let mut termination = None;
+    let mut status = None;
+
+    for pair in file.into_inner() {
+        let line = pair.line_col().0;
+        match pair.as_rule() {
+            Rule::stages => {
+                for stage in pair.into_inner() {
+                    let mut it = stage.into_inner();
+                    // the name of the struct is not used
+                    let _ = it.next().expect("invalid stage");
+                    let short = it.next().expect("invalid stage").as_str().to_string();
+                    let mut fields = Vec::new();
+                    for field in it {
+                        let line = field.line_col().0;
+                        let mut it = field.into_inner();
+                        let name = it.next().expect("invalid field").as_str().to_string();
+                        let ty = Ty::parse(it.next().expect("invalid field"))?;
+                        let default = exprs
+                            .parse(it.next().expect("invalid field").into_inner())
+                            .with_context(|| format!("line {line}"))?;
+                        fields.push(FieldDef {
+                            name,
+                            ty,
+                            default,
+                            line,
+                        });
+                    }
+                    arch.stages.push(StageDef { short, fields });
+                }
+            }
+            Rule::inner_attr => {
+                let attr = pair.into_inner().next().expect("invalid attribute");
+                if attr.as_rule() == Rule::stage_alias {
+                    for alias in attr.into_inner() {
+                        let mut it = alias.into_inner();
+                        let pre = it.next().expect("invalid alias").as_str().to_string();
+                        let cur = it.next().expect("invalid alias").as_str().to_string();
+                        arch.stage_alias.push((cur, pre));
+                    }
+                    continue;
+                }
+                if attr.as_rule() == Rule::status {
+                    status = Some(attr.into_inner().map(|f| f.as_str().to_string()).collect());
+                    continue;
+                }
+                let mut it = attr.into_inner();
+                let key = it.next().expect("invalid attribute").as_str();
+                let value = it.next().expect("invalid attribute").as_str();
+                // the last segment of the path
+                let value = value.rsplit("::").next().unwrap_or(value).to_string();
+                match key {
+                    "hardware" => hardware = Some(value),
+                    "program_counter" => program_counter = Some(value),
+                    "termination" => termination = Some(value),
+                    "datapath" => {
+                        tracing::warn!(
+                            "line {line}: datapath drawings are not supported in HCL files"
+                        )
+                    }
+                    _ => bail!("line {line}: unknown attribute `{key}`"),
+                }
+            }
+            Rule::section => {
+                let title = pair.into_inner().next().expect("invalid section").as_str();
+                let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+                arch.sections.push(title);
+            }
+            Rule::set_input | Rule::set_stage => {
+                let is_stage = pair.as_rule() == Rule::set_stage;
+                let mut it = pair.into_inner();
+                let target = it.next().expect("invalid directive").as_str().to_string();
+                if !is_stage {
+                    if let Some(index) = arch.sections.len().checked_sub(1) {
+                        arch.unit_sections.push((target.clone(), index));
+                    }
+                }
+                for assign in it {
+                    let mut it = assign.into_inner();
+                    let field = it.next().expect("invalid assignment").as_str().to_string();
+                    let signal = it.next().expect("invalid assignment").as_str().to_string();
+                    assigns.push((target.clone(), field, signal, line, is_stage));
+                }
+            }
+            Rule::signal => {
+                let mut it = pair.into_inner();
+                let ty = Ty::parse(it.next().expect("invalid signal"))?;
+                let name = it.next().expect("invalid signal").as_str().to_string();
+                let source = it.next().expect("invalid signal");
+                let source = match source.as_rule() {
+                    Rule::switch => {
+                        let mut cases = Vec::new();
+                        for case in source.into_inner() {
+                            let line = case.line_col().0;
+                            let mut tunnel = None;
+                            let mut e = Vec::new();
+                            let mut texts = Vec::new();
+                            for p in case.into_inner() {
+                                match p.as_rule() {
+                                    Rule::tunnel => tunnel = Some(parse_tunnel(p)),
+                                    _ => {
+                                        texts.push(source_text(&p));
+                                        e.push(exprs.parse(p.into_inner())?)
+                                    }
+                                }
+                            }
+                            let value = e.pop().expect("invalid case");
+                            let condition = e.pop().expect("invalid case");
+                            cases.push(Case {
+                                tunnel,
+                                condition,
+                                value,
+                                text: texts.join(" : "),
+                                line,
+                            });
+                        }
+                        Source::Switch(cases)
+                    }
+                    _ => {
+                        let mut tunnel = None;
+                        let mut expr = None;
+                        for p in source.into_inner() {
+                            match p.as_rule() {
+                                Rule::tunnel => tunnel = Some(parse_tunnel(p)),
+                                _ => {
+                                    expr = Some((
+                                        exprs.parse(p.clone().into_inner())?,
+                                        source_text(&p),
+                                    ))
+                                }
+                            }
+                        }
+                        let (expr, text) = expr.expect("invalid signal");
+                        Source::Expr(tunnel, expr, text)
+                    }
+                };
+                let mut dests = Vec::new();
+                for dest in it.flat_map(|p| p.into_inner()) {
+                    let mut tunnel = None;
+                    let mut target = None;
+                    for p in dest.into_inner() {
+                        match p.as_rule() {
+                            Rule::tunnel => tunnel = Some(parse_tunnel(p)),
+                            _ => target = Some(parse_lvalue(p)),
+                        }
+                    }
+                    let target = target.expect("invalid destination");
+                    let is_stage_field = arch
+                        .stage_alias
+                        .iter()
+                        .any(|(cur, _)| cur == &target.parts[0]);
+                    dests.push(Dest {
+                        tunnel,
+                        target,
+                        is_stage_field,
+                    });
+                }
+                if arch.signals.iter().any(|s| s.name == name) {
+                    bail!("line {line}: signal `{name}` is defined more than once");
+                }
+                arch.signals.push(SignalDef {
+                    name,
+                    ty,
+                    source,
+                    dests,
+                    section: arch.sections.len().checked_sub(1),
+                    line,
+                });
+            }
+            Rule::assert => {
+                let mut it = pair.into_inner();
+                let expr = it.next().expect("invalid assertion");
+                let text = expr
+                    .as_str()
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let expr = exprs
+                    .parse(expr.into_inner())
+                    .with_context(|| format!("line {line}"))?;
+                let message = it.next().expect("invalid assertion").into_inner().as_str();
+                arch.asserts.push(AssertDef {
+                    expr,
+                    text,
+                    message: message.replace("\\\"", "\"").replace("\\\\", "\\"),
+                    line,
+                });
+            }
+            Rule::use_item | Rule::EOI => {}
+            rule => unreachable!("unexpected rule {:?}", rule),
+        }
+    }
+
+    for (target, field, signal, line, is_stage) in assigns {
+        let Some(sig) = arch.signals.iter_mut().find(|s| s.name == signal) else {
+            bail!("line {line}: signal `{signal}` is not defined");
+        };
+        sig.dests.push(Dest {
+            tunnel: None,
+            target: LValue {
+                parts: vec![target, field],
+            },
+            is_stage_field: is_stage,
+        });
+    }
+
+    // every stage field or unit input is set by exactly one signal
+    let mut dests: Vec<&LValue> = Vec::new();
+    for signal in &arch.signals {
+        for dest in &signal.dests {
+            if dests.contains(&&dest.target) {
+                let kind = if dest.is_stage_field {
+                    "stage field"
+                } else {
+                    "unit input"
+                };
+                bail!(
+                    "line {}: {kind} `{}` is set more than once",
+                    signal.line,
+                    dest.target
+                );
+            }
+            dests.push(&dest.target);
+        }
+    }
+
+    arch.status = status.unwrap_or_else(|| vec!["stat".to_string(), "icode".to_string()]);
+    arch.hardware = hardware.context("missing #![hardware = ..] attribute")?;
+    arch.program_counter = program_counter.context("missing #![program_counter = ..] attribute")?;
+    arch.termination = termination.context("missing #![termination = ..] attribute")?;
+    Ok(arch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_expr(src: &str) -> String {
+        let pairs = HclParser::parse(Rule::expr, src).expect("parse error");
+        format!("{:?}", ExprParser::new().parse(pairs).expect("parse error"))
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(parse_expr("a + b * c"), parse_expr("a + (b * c)"));
+        assert_eq!(parse_expr("a || b && c"), parse_expr("a || (b && c)"));
+        assert_eq!(parse_expr("!a in { B, C }"), parse_expr("(!a) in { B, C }"));
+        assert_eq!(
+            parse_expr("a in { B } && c == d"),
+            parse_expr("(a in { B }) && (c == d)")
+        );
+        assert_eq!(parse_expr("a - b - c"), parse_expr("(a - b) - c"));
+        assert_eq!(parse_expr("a << 1 + b"), parse_expr("a << (1 + b)"));
+    }
+
+    #[test]
+    fn test_parse_file() {
+        let arch = parse(
+            r#"
+            define_stages! {
+                /// the only stage
+                SEQstage s { pc: u64 = 0 }
+            }
+            #![hardware = crate::architectures::hardware_seq]
+            #![program_counter = pc]
+            #![termination = term]
+            #![stage_alias(S => s)]
+            use Stat::*;
+
+            :====: Fetch Stage :====:
+            u64 pc = S.pc;
+            @set_input(imem, { pc: pc });
+            u8 icode = [
+                #[tunnel(x)] imem.error : NOP;
+                true : imem.icode
+            ] -> d.icode;
+            bool term = icode in { HALT } -> s.pc;
+            @tunnel(x, { from: imem.icode, to: [icode], at: [(1, 2)] });
+            "#,
+        )
+        .expect("parse error");
+        assert_eq!(arch.hardware, "hardware_seq");
+        assert_eq!(arch.stage_alias, [("s".to_string(), "S".to_string())]);
+        assert_eq!(arch.status, ["stat", "icode"]);
+        assert_eq!(arch.sections, ["Fetch Stage"]);
+        assert_eq!(arch.unit_sections, [("imem".to_string(), 0)]);
+        assert_eq!(arch.signals.len(), 3);
+        assert_eq!(arch.signals[0].dests[0].target.to_string(), "imem.pc");
+        assert!(!arch.signals[1].dests[0].is_stage_field);
+        assert!(arch.signals[2].dests[0].is_stage_field);
+        let Source::Switch(cases) = &arch.signals[1].source else {
+            panic!("expect a switch")
+        };
+        assert_eq!(cases[0].tunnel.as_deref(), Some("x"));
+    }
+}
@@ -0,0 +1,1064 @@
+//! Interpret a parsed HCL file on the hardware units of a hardware module.
+use std::{marker::PhantomData, rc::Rc};
+
+use anyhow::{bail, Context, Result};
+
+use super::{
+    parse::{Arch, BinaryOp, Expr, LValue, Source, Ty, UnaryOp},
+    HclUnits, Signal, Value,
+};
+use crate::{
+    coverage::{CaseCoverage, Coverage, SwitchCoverage},
+    framework::{
+        CpuCircuit, CpuSim, DataAccess, MemData, PropCircuit, PropOrder, PropOrderBuilder,
+        StageInfo, StageTracker, Tracer,
+    },
+    isa::{inst_code, op_code, reg_code, ConditionCode, RegFile, Stat, CC_INIT},
+};
+
+/// Signals are stored by their indices, in the order of definition. Each
+/// stage is a list of fields followed by `bubble` and `stall`.
+pub struct HclArch<U>(PhantomData<U>);
+
+impl<U: HclUnits> CpuCircuit for HclArch<U> {
+    type UnitIn = U::UnitIn;
+    type UnitOut = U::UnitOut;
+    type Inter = Vec<Value>;
+    type StageState = Vec<Vec<Value>>;
+}
+
+fn leak(s: &str) -> &'static str {
+    Box::leak(s.to_string().into_boxed_str())
+}
+
+/// Constants available in expressions, as imported by the `hcl!` macro.
+pub(super) fn constant(name: &str) -> Option<(Value, Ty)> {
+    // codes are matched case-insensitively by `code_of`
+    if name == name.to_ascii_uppercase() {
+        if let Some(code) = inst_code::code_of(name)
+            .or_else(|| reg_code::code_of(name))
+            .or_else(|| op_code::code_of(name))
+        {
+            return Some((Value::Word(code as u64), Ty::U8));
+        }
+    }
+    let stat = match name {
+        "Aok" => Stat::Aok,
+        "Bub" => Stat::Bub,
+        "Hlt" => Stat::Hlt,
+        "Adr" => Stat::Adr,
+        "Ins" => Stat::Ins,
+        "NEG_8" => return Some((Value::Word(-8i64 as u64), Ty::U64)),
+        "CC_INIT" => return Some((Value::Cc(CC_INIT), Ty::Cc)),
+        _ => return None,
+    };
+    Some((stat.to_value(), Ty::Stat))
+}
+
+/// The type of a unit port, inferred from its default value.
+fn ty_of(value: &Value) -> Ty {
+    match value {
+        Value::Bool(_) => Ty::Bool,
+        Value::Word(_) => Ty::U64,
+        Value::Bytes(b) => Ty::Bytes(b.len()),
+        Value::Cc(_) => Ty::Cc,
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    value.as_word().is_some_and(|w| w != 0)
+}
+
+/// Wrap an arithmetic result around at the width of the integer type.
+fn wrap(ty: Ty, word: u64) -> Value {
+    ty.convert(Value::Word(word)).unwrap_or(Value::Word(word))
+}
+
+/// Whether the expression consists of integer literals only. Like in Rust,
+/// such an operand takes the type of the other operand.
+fn is_literal(expr: &Expr) -> bool {
+    match expr {
+        Expr::Int(_) => true,
+        Expr::Unary(_, e) => is_literal(e),
+        Expr::Binary(a, _, b) => is_literal(a) && is_literal(b),
+        _ => false,
+    }
+}
+
+/// Type of the result of an arithmetic operator on the operand type
+fn arith(ty: Ty) -> Ty {
+    match ty {
+        Ty::Int { .. } => ty,
+        _ => Ty::U64,
+    }
+}
+
+/// An expression whose names are resolved.
+enum Node {
+    Const(Value),
+    /// index of the intermediate signal
+    Inter(usize),
+    /// (stage, field) at the start of the cycle
+    Prev(usize, usize),
+    /// (stage, field) to be latched at the end of the cycle, only read by
+    /// assertions
+    Next(usize, usize),
+    /// (unit, output)
+    Out(&'static str, &'static str),
+    /// An arithmetic result wraps around at the width of the type, as in
+    /// the compiled architectures.
+    Unary(UnaryOp, Box<Node>, Ty),
+    Binary(Box<Node>, BinaryOp, Box<Node>, Ty),
+    In(Box<Node>, Vec<Node>),
+}
+
+impl Node {
+    fn eval<U: HclUnits>(
+        &self,
+        c_: &[Value],
+        n_: &[Vec<Value>],
+        o_: &U::UnitOut,
+        p_: &[Vec<Value>],
+    ) -> Value {
+        let word = |node: &Node| node.eval::<U>(c_, n_, o_, p_).as_word().unwrap_or_default();
+        match self {
+            Node::Const(v) => v.clone(),
+            Node::Inter(i) => c_[*i].clone(),
+            Node::Prev(s, f) => p_[*s][*f].clone(),
+            Node::Next(s, f) => n_[*s][*f].clone(),
+            Node::Out(unit, field) => U::output(o_, unit, field).expect("invalid unit output"),
+            Node::Unary(UnaryOp::Not, e, _) => Value::Bool(!truthy(&e.eval::<U>(c_, n_, o_, p_))),
+            Node::Unary(UnaryOp::BitNot, e, ty) => wrap(*ty, !word(e)),
+            Node::Unary(UnaryOp::Neg, e, ty) => wrap(*ty, word(e).wrapping_neg()),
+            Node::Binary(a, BinaryOp::And, b, _) => Value::Bool(word(a) != 0 && word(b) != 0),
+            Node::Binary(a, BinaryOp::Or, b, _) => Value::Bool(word(a) != 0 || word(b) != 0),
+            Node::Binary(a, op, b, ty) => {
+                let (a, b) = (word(a), word(b));
+                // the shift amount is taken modulo the width
+                let shift = b as u32 % ty.bits();
+                // words of signed types are sign-extended, so that they are
+                // compared as signed 64-bit integers like in the compiled
+                // architectures
+                let (x, y) = (a as i64, b as i64);
+                match op {
+                    BinaryOp::Mul => wrap(*ty, a.wrapping_mul(b)),
+                    BinaryOp::Add => wrap(*ty, a.wrapping_add(b)),
+                    BinaryOp::Sub => wrap(*ty, a.wrapping_sub(b)),
+                    BinaryOp::Shl => wrap(*ty, a.wrapping_shl(shift)),
+                    BinaryOp::Shr => wrap(*ty, a.wrapping_shr(shift)),
+                    BinaryOp::BitAnd => wrap(*ty, a & b),
+                    BinaryOp::BitXor => wrap(*ty, a ^ b),
+                    BinaryOp::BitOr => wrap(*ty, a | b),
+                    BinaryOp::Lt => Value::Bool(x < y),
+                    BinaryOp::Le => Value::Bool(x <= y),
+                    BinaryOp::Gt => Value::Bool(x > y),
+                    BinaryOp::Ge => Value::Bool(x >= y),
+                    BinaryOp::Eq => Value::Bool(a == b),
+                    BinaryOp::NotEq => Value::Bool(a != b),
+                    BinaryOp::And | BinaryOp::Or => unreachable!(),
+                }
+            }
+            Node::In(e, set) => {
+                let v = word(e);
+                Value::Bool(set.iter().any(|node| word(node) == v))
+            }
+        }
+    }
+}
+
+enum DestNode {
+    /// (stage, field, type of the field)
+    Stage(usize, usize, Ty),
+    /// (unit, input)
+    Unit(&'static str, &'static str),
+}
+
+/// The update function of an intermediate signal
+struct SignalNode {
+    index: usize,
+    ty: Ty,
+    /// (tunnel, condition, value). A plain expression has a `true` condition.
+    cases: Vec<(Option<&'static str>, Node, Node)>,
+    /// Index among the switch signals, see [`Tracer::take_case`]
+    switch: Option<usize>,
+    dests: Vec<(Option<&'static str>, DestNode)>,
+}
+
+impl SignalNode {
+    fn update<U: HclUnits>(
+        &self,
+        i_: &mut U::UnitIn,
+        c_: &mut [Value],
+        n_: &mut [Vec<Value>],
+        tracer: &mut Tracer,
+        o_: &U::UnitOut,
+        p_: &[Vec<Value>],
+    ) {
+        let mut has_tunnel_input = false;
+        // if no case matches, the signal keeps its value
+        if let Some((case, (tunnel, _, value))) = self
+            .cases
+            .iter()
+            .enumerate()
+            .find(|(_, (_, cond, _))| truthy(&cond.eval::<U>(c_, n_, o_, p_)))
+        {
+            if let Some(switch) = self.switch {
+                tracer.take_case(switch, case);
+            }
+            let value = value.eval::<U>(c_, n_, o_, p_);
+            if let Some(value) = self.ty.convert(value) {
+                c_[self.index] = value;
+            }
+            if let Some(tunnel) = tunnel {
+                has_tunnel_input = true;
+                tracing::debug!("tunnel triggered: {}", tunnel);
+                tracer.trigger_tunnel(tunnel);
+            }
+        }
+        for (tunnel, dest) in &self.dests {
+            let value = c_[self.index].clone();
+            match dest {
+                DestNode::Stage(s, f, ty) => {
+                    if let Some(value) = ty.convert(value) {
+                        n_[*s][*f] = value;
+                    }
+                }
+                DestNode::Unit(unit, field) => {
+                    U::set_input(i_, unit, field, &value);
+                }
+            }
+            if let Some(tunnel) = tunnel.filter(|_| has_tunnel_input) {
+                tracing::debug!("tunnel triggered: {}", tunnel);
+                tracer.trigger_tunnel(tunnel);
+            }
+        }
+    }
+}
+
+/// An `@assert(..)` in the HCL file
+struct Assertion {
+    cond: Node,
+    text: String,
+    message: String,
+    /// (name, type, value) of the signals in the condition
+    signals: Vec<(String, Ty, Node)>,
+}
+
+/// Pipeline registers declared in the HCL file
+struct StageMeta {
+    /// short name, e.g. `d`
+    short: &'static str,
+    /// (name, type), including `bubble` and `stall` at the end
+    fields: Vec<(&'static str, Ty)>,
+    /// default values of the fields
+    defaults: Vec<Value>,
+}
+
+impl StageMeta {
+    fn field(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|(f, _)| *f == name)
+    }
+
+    /// Number of fields, excluding `bubble` and `stall`
+    fn n_fields(&self) -> usize {
+        self.fields.len() - 2
+    }
+}
+
+/// Resolve names in expressions against the signals, the stages and the
+/// hardware units.
+struct Resolver<'a, U> {
+    arch: &'a Arch,
+    stages: &'a [StageMeta],
+    /// whether the next state of stages (`d.icode`) can be read
+    read_next: bool,
+    _units: PhantomData<U>,
+}
+
+impl<U: HclUnits> Resolver<'_, U> {
+    fn stage_of(&self, alias: &str, cur: bool) -> Option<usize> {
+        let (short, _) =
+            self.arch
+                .stage_alias
+                .iter()
+                .find(|(c, p)| if cur { c == alias } else { p == alias })?;
+        self.stages.iter().position(|s| s.short == short)
+    }
+
+    fn lvalue(&self, lv: &LValue) -> Result<(Node, Ty)> {
+        let parts = &lv.parts;
+        if parts.len() == 1 {
+            if let Some(index) = self.arch.signals.iter().position(|s| s.name == parts[0]) {
+                return Ok((Node::Inter(index), self.arch.signals[index].ty));
+            }
+            if let Some((value, ty)) = constant(&parts[0]) {
+                return Ok((Node::Const(value), ty));
+            }
+            bail!("unknown signal or constant `{lv}`")
+        } else if parts.len() == 2 {
+            if let Some(s) = self.stage_of(&parts[0], false) {
+                let stage = &self.stages[s];
+                let f = stage
+                    .field(&parts[1])
+                    .with_context(|| format!("stage `{}` has no field `{}`", parts[0], parts[1]))?;
+                return Ok((Node::Prev(s, f), stage.fields[f].1));
+            }
+            if let Some(s) = self.stage_of(&parts[0], true).filter(|_| self.read_next) {
+                let stage = &self.stages[s];
+                let f = stage
+                    .field(&parts[1])
+                    .with_context(|| format!("stage `{}` has no field `{}`", parts[0], parts[1]))?;
+                return Ok((Node::Next(s, f), stage.fields[f].1));
+            }
+            let mut out = U::UnitOut::default();
+            let Some(value) = U::output(&out, &parts[0], &parts[1]) else {
+                bail!("unknown unit output `{lv}`")
+            };
+            // the width of an integer output is found by storing all ones
+            let ty = match value {
+                Value::Word(_)
+                    if U::set_output(&mut out, &parts[0], &parts[1], &Value::Word(!0)) =>
+                {
+                    let ones = U::output(&out, &parts[0], &parts[1]).and_then(|v| v.as_word());
+                    let bits = 64 - ones.unwrap_or(!0).leading_zeros();
+                    Ty::Int {
+                        bits,
+                        signed: false,
+                    }
+                }
+                _ => ty_of(&value),
+            };
+            Ok((Node::Out(leak(&parts[0]), leak(&parts[1])), ty))
+        } else {
+            bail!("invalid name `{lv}`")
+        }
+    }
+
+    /// Resolve names and check that operators are applied to words.
+    fn expr(&self, expr: &Expr) -> Result<(Node, Ty)> {
+        let operand = |e: &Expr, op: &str| -> Result<(Node, Ty)> {
+            let (node, ty) = self.expr(e)?;
+            if !ty.is_word() {
+                bail!("`{op}` can not be applied to `{ty}`");
+            }
+            Ok((node, ty))
+        };
+        Ok(match expr {
+            Expr::Int(i) => (Node::Const(Value::Word(*i)), Ty::U64),
+            Expr::Bool(b) => (Node::Const(Value::Bool(*b)), Ty::Bool),
+            Expr::LVal(lv) => self.lvalue(lv)?,
+            Expr::Unary(op, e) => {
+                let (node, ty) = self.expr(e)?;
+                if !ty.is_word() {
+                    bail!("`{op:?}` can not be applied to `{ty}`");
+                }
+                let ty = if *op == UnaryOp::Not {
+                    Ty::Bool
+                } else {
+                    arith(ty)
+                };
+                (Node::Unary(*op, Box::new(node), ty), ty)
+            }
+            Expr::Binary(a, op, b) => {
+                let name = format!("{op:?}");
+                let (a_node, a_ty) = operand(a, &name)?;
+                let (b_node, b_ty) = operand(b, &name)?;
+                // the shift amount does not affect the type
+                let operand_ty = if is_literal(a) && !matches!(op, BinaryOp::Shl | BinaryOp::Shr) {
+                    b_ty
+                } else {
+                    a_ty
+                };
+                let node = Node::Binary(Box::new(a_node), *op, Box::new(b_node), arith(operand_ty));
+                let ty = match op {
+                    BinaryOp::Lt
+                    | BinaryOp::Le
+                    | BinaryOp::Gt
+                    | BinaryOp::Ge
+                    | BinaryOp::Eq
+                    | BinaryOp::NotEq
+                    | BinaryOp::And
+                    | BinaryOp::Or => Ty::Bool,
+                    _ => Ty::U64,
+                };
+                (node, ty)
+            }
+            Expr::In(e, set) => {
+                let (node, _) = operand(e, "in")?;
+                let set = set
+                    .iter()
+                    .map(|lv| Ok(operand(&Expr::LVal(lv.clone()), "in")?.0))
+                    .collect::<Result<_>>()?;
+                (Node::In(Box::new(node), set), Ty::Bool)
+            }
+        })
+    }
+}
+
+/// Simulator of an architecture loaded from a `.hcl` file. It works like
+/// [`crate::framework::PipeSim`], except that signals are interpreted.
+pub struct HclSim<U: HclUnits> {
+    circuit: PropCircuit<HclArch<U>>,
+    cur_unit_in: U::UnitIn,
+    cur_unit_out: U::UnitOut,
+    cur_inter: Vec<Value>,
+    cur_state: Vec<Vec<Value>>,
+    nex_state: Vec<Vec<Value>>,
+    units: U,
+    terminate: bool,
+    tty_out: bool,
+    cycle_count: u64,
+    stages: StageTracker,
+    tunnels: Vec<&'static str>,
+    /// (name, type, section index) of intermediate signals
+    signals: Vec<(&'static str, Ty, Option<usize>)>,
+    sections: Vec<&'static str>,
+    regs: Vec<StageMeta>,
+    /// (cur, pre, index of the stage) in pipeline order
+    stage_alias: Vec<(&'static str, &'static str, usize)>,
+    stage_names: Vec<&'static str>,
+    /// stage fields shown in the status table
+    status: Vec<&'static str>,
+    program_counter: usize,
+    termination: usize,
+    asserts: Vec<Assertion>,
+    /// See [`CpuSim::failed_assertion`].
+    failed_assertion: Option<String>,
+    /// (unit, output, value) forced by [`CpuSim::force_signal`]
+    forced_outputs: Vec<(&'static str, &'static str, Value)>,
+    /// See [`CpuSim::coverage`].
+    coverage: Coverage,
+}
+
+impl<U: HclUnits> HclSim<U> {
+    pub(super) fn new(arch: Arch, memory: MemData, tty_out: bool) -> Result<Self> {
+        // pipeline registers
+        let no_signals = Resolver::<U> {
+            arch: &Arch::default(),
+            stages: &[],
+            read_next: false,
+            _units: PhantomData,
+        };
+        let mut regs = Vec::new();
+        for stage in &arch.stages {
+            let mut fields = Vec::new();
+            let mut defaults = Vec::new();
+            for field in &stage.fields {
+                let (node, _) = no_signals
+                    .expr(&field.default)
+                    .with_context(|| format!("line {}", field.line))?;
+                let value = node.eval::<U>(&[], &[], &U::UnitOut::default(), &[]);
+                let Some(value) = field.ty.convert(value) else {
+                    bail!(
+                        "line {}: the default value of `{}` is not a `{}`",
+                        field.line,
+                        field.name,
+                        field.ty
+                    );
+                };
+                fields.push((leak(&field.name), field.ty));
+                defaults.push(value);
+            }
+            fields.push(("bubble", Ty::Bool));
+            fields.push(("stall", Ty::Bool));
+            defaults.push(Value::Bool(false));
+            defaults.push(Value::Bool(false));
+            regs.push(StageMeta {
+                short: leak(&stage.short),
+                fields,
+                defaults,
+            });
+        }
+        let mut stage_alias = Vec::new();
+        for (cur, pre) in &arch.stage_alias {
+            let Some(index) = regs.iter().position(|s| s.short == cur) else {
+                bail!("stage `{cur}` in #![stage_alias(..)] is not defined");
+            };
+            stage_alias.push((leak(cur), leak(pre), index));
+        }
+
+        // intermediate signals
+        let resolver = Resolver::<U> {
+            arch: &arch,
+            stages: &regs,
+            read_next: false,
+            _units: PhantomData,
+        };
+        let mut nodes = Vec::new();
+        let mut coverage = Coverage::default();
+        for (index, signal) in arch.signals.iter().enumerate() {
+            let err = || format!("in `{}` (line {})", signal.name, signal.line);
+            let resolve = |expr: &Expr, expected: Ty| -> Result<Node> {
+                let (node, ty) = resolver.expr(expr).with_context(err)?;
+                if expected.convert(ty.default_value()).is_none() {
+                    bail!("type error {}: expected `{expected}`, found `{ty}`", err());
+                }
+                Ok(node)
+            };
+            let (cases, branches) = match &signal.source {
+                Source::Switch(cases) => (
+                    cases
+                        .iter()
+                        .map(|case| {
+                            Ok((
+                                case.tunnel.as_deref().map(leak),
+                                resolve(&case.condition, Ty::Bool)?,
+                                resolve(&case.value, signal.ty)?,
+                            ))
+                        })
+                        .collect::<Result<_>>()?,
+                    cases
+                        .iter()
+                        .map(|case| (case.text.clone(), case.line))
+                        .collect(),
+                ),
+                // a boolean signal counts as `[expr : true; 1 : false]`, the
+                // same as the `hcl!` macro does
+                Source::Expr(tunnel, expr, text)
+                    if signal.ty == Ty::Bool && !expr.lvalues().is_empty() =>
+                {
+                    let tunnel = tunnel.as_deref().map(leak);
+                    (
+                        vec![
+                            (
+                                tunnel,
+                                resolve(expr, Ty::Bool)?,
+                                Node::Const(Value::Bool(true)),
+                            ),
+                            (
+                                tunnel,
+                                Node::Const(Value::Bool(true)),
+                                Node::Const(Value::Bool(false)),
+                            ),
+                        ],
+                        vec![
+                            (format!("{text} : true"), signal.line),
+                            ("1 : false".to_string(), signal.line),
+                        ],
+                    )
+                }
+                Source::Expr(tunnel, expr, _) => (
+                    vec![(
+                        tunnel.as_deref().map(leak),
+                        Node::Const(Value::Bool(true)),
+                        resolve(expr, signal.ty)?,
+                    )],
+                    Vec::new(),
+                ),
+            };
+            let switch = (!branches.is_empty()).then(|| {
+                coverage.switches.push(SwitchCoverage {
+                    signal: signal.name.clone(),
+                    cases: branches
+                        .into_iter()
+                        .map(|(source, line)| CaseCoverage {
+                            source,
+                            line: Some(line),
+                            count: 0,
+                        })
+                        .collect(),
+                });
+                coverage.switches.len() - 1
+            });
+            let mut dests = Vec::new();
+            for dest in &signal.dests {
+                let target = &dest.target;
+                if target.parts.len() != 2 {
+                    bail!("invalid destination `{target}` {}", err());
+                }
+                let (name, field) = (&target.parts[0], &target.parts[1]);
+                let node = if dest.is_stage_field {
+                    let s = resolver
+                        .stage_of(name, true)
+                        .with_context(|| format!("unknown stage `{name}` {}", err()))?;
+                    let Some(f) = regs[s].field(field) else {
+                        bail!("stage `{name}` has no field `{field}` {}", err());
+                    };
+                    let ty = regs[s].fields[f].1;
+                    if ty.convert(signal.ty.default_value()).is_none() {
+                        bail!("type error {}: `{target}` is a `{ty}`", err());
+                    }
+                    DestNode::Stage(s, f, ty)
+                } else {
+                    let mut unit_in = U::UnitIn::default();
+                    if U::input(&unit_in, name, field).is_none() {
+                        bail!("unknown unit input `{target}` {}", err());
+                    }
+                    if !U::set_input(&mut unit_in, name, field, &signal.ty.default_value()) {
+                        bail!("type error {}: `{target}` is not a `{}`", err(), signal.ty);
+                    }
+                    DestNode::Unit(leak(name), leak(field))
+                };
+                dests.push((dest.tunnel.as_deref().map(leak), node));
+            }
+            nodes.push(SignalNode {
+                index,
+                ty: signal.ty,
+                cases,
+                switch,
+                dests,
+            });
+        }
+
+        // assertions
+        let resolver = Resolver {
+            read_next: true,
+            ..resolver
+        };
+        let mut asserts = Vec::new();
+        for assert in &arch.asserts {
+            let err = || format!("in assertion `{}` (line {})", assert.text, assert.line);
+            let (cond, ty) = resolver.expr(&assert.expr).with_context(err)?;
+            if ty != Ty::Bool {
+                bail!("type error {}: expected `bool`, found `{ty}`", err());
+            }
+            let mut signals: Vec<(String, Ty, Node)> = Vec::new();
+            for lv in assert.expr.lvalues() {
+                let name = lv.to_string();
+                let (node, ty) = resolver.lvalue(lv).with_context(err)?;
+                if !matches!(node, Node::Const(_)) && signals.iter().all(|(n, ..)| *n != name) {
+                    signals.push((name, ty, node));
+                }
+            }
+            asserts.push(Assertion {
+                cond,
+                text: assert.text.clone(),
+                message: assert.message.clone(),
+                signals,
+            });
+        }
+
+        let find_signal = |name: &str, attr: &str| {
+            arch.signals
+                .iter()
+                .position(|s| s.name == name)
+                .with_context(|| format!("signal `{name}` in #![{attr} = ..] is not defined"))
+        };
+        let program_counter = find_signal(&arch.program_counter, "program_counter")?;
+        let termination = find_signal(&arch.termination, "termination")?;
+
+        let sections: Vec<&'static str> = arch.sections.iter().map(|s| leak(s)).collect();
+        let signals: Vec<_> = arch
+            .signals
+            .iter()
+            .map(|s| (leak(&s.name), s.ty, s.section))
+            .collect();
+        let order = Self::build_order(&arch, &signals, &sections)?;
+        let mut circuit = PropCircuit::new(order);
+        for node in nodes {
+            let node = Rc::new(node);
+            let name = signals[node.index].0;
+            circuit.add_update(
+                name,
+                move |i_,
+                      c_: &mut Vec<Value>,
+                      n_: &mut Vec<Vec<Value>>,
+                      tracer,
+                      o_,
+                      p_: &Vec<Vec<Value>>| {
+                    node.update::<U>(i_, c_, n_, tracer, o_, p_)
+                },
+            );
+        }
+
+        let state: Vec<Vec<Value>> = regs.iter().map(|s| s.defaults.clone()).collect();
+        let mut stage_names: Vec<&'static str> =
+            stage_alias.iter().map(|(_, pre, _)| *pre).collect();
+        if stage_names.is_empty() {
+            stage_names.push("(default)");
+        }
+        Ok(Self {
+            circuit,
+            cur_unit_in: Default::default(),
+            cur_unit_out: Default::default(),
+            cur_inter: signals
+                .iter()
+                .map(|(_, ty, _)| ty.default_value())
+                .collect(),
+            cur_state: state.clone(),
+            nex_state: state,
+            units: U::init(memory),
+            terminate: false,
+            tty_out,
+            cycle_count: 0,
+            stages: StageTracker::new(stage_alias.len()),
+            tunnels: Vec::new(),
+            signals,
+            sections,
+            regs,
+            stage_alias,
+            stage_names,
+            status: arch.status.iter().map(|f| leak(f)).collect(),
+            program_counter,
+            termination,
+            asserts,
+            failed_assertion: None,
+            forced_outputs: Vec::new(),
+            coverage,
+        })
+    }
+
+    /// Build the computational graph, the same as the `hcl!` macro does.
+    fn build_order(
+        arch: &Arch,
+        signals: &[(&'static str, Ty, Option<usize>)],
+        sections: &[&'static str],
+    ) -> Result<PropOrder> {
+        let mut g = PropOrderBuilder::new();
+        U::setup(&mut g);
+        for (signal, &(name, _, section)) in arch.signals.iter().zip(signals) {
+            for lv in signal.source.lvalues() {
+                // intermediate signals and unit ports. Previous stage fields
+                // are not included.
+                let parts = &lv.parts;
+                if parts.len() == 1 && arch.signals.iter().any(|s| s.name == parts[0])
+                    || parts.len() == 2 && arch.stage_alias.iter().all(|(_, pre)| pre != &parts[0])
+                {
+                    g.add_edge(lv.to_string(), name.to_string());
+                }
+            }
+            g.add_intermediate(name);
+            if let Some(index) = section {
+                g.add_to_section(name, sections[index]);
+            }
+            for dest in signal.dests.iter().filter(|d| !d.is_stage_field) {
+                g.add_edge(name.to_string(), dest.target.to_string());
+            }
+        }
+        for (unit, index) in &arch.unit_sections {
+            g.add_to_section(leak(unit), sections[*index]);
+        }
+        g.build()
+    }
+
+    /// Simulate one cycle of the CPU, see `update` generated by the `hcl!`
+    /// macro.
+    fn update(&mut self) -> Tracer {
+        let mut rcd = self.circuit.updates.make_propagator(
+            &mut self.cur_unit_in,
+            self.cur_unit_out.clone(),
+            &mut self.nex_state,
+            &self.cur_state,
+            &mut self.cur_inter,
+        );
+        let units = &mut self.units;
+        let forced = &self.forced_outputs;
+        for item in &self.circuit.order.order {
+            if item.is_unit {
+                rcd.run_unit(|unit_in, unit_out| {
+                    units.run(item.name, (unit_in, unit_out));
+                    for (unit, field, value) in forced {
+                        if *unit == item.name {
+                            U::set_output(unit_out, unit, field, value);
+                        }
+                    }
+                });
+            } else {
+                rcd.run_combinatorial_logic(item.name);
+            }
+        }
+        let (out, tracer) = rcd.finalize();
+        self.cur_unit_out = out;
+        tracer
+    }
+
+    /// Check the `@assert` invariants, returning the failure message of the
+    /// first one that does not hold.
+    fn check_assertions(&self) -> Option<String> {
+        let eval = |node: &Node| {
+            node.eval::<U>(
+                &self.cur_inter,
+                &self.nex_state,
+                &self.cur_unit_out,
+                &self.cur_state,
+            )
+        };
+        let assert = self.asserts.iter().find(|a| !truthy(&eval(&a.cond)))?;
+        let mut msg = format!(
+            "assertion `{}` failed in cycle {}: {}",
+            assert.text, self.cycle_count, assert.message
+        );
+        for (name, ty, node) in &assert.signals {
+            msg += &format!("\n    {} = {}", name, Self::format_value(*ty, &eval(node)));
+        }
+        Some(msg)
+    }
+
+    fn format_value(ty: Ty, value: &Value) -> String {
+        match (ty, value) {
+            (Ty::U8 | Ty::U64, Value::Word(w)) => format!("{:#x?}", w),
+            (Ty::Stat, v) => match Stat::from_value(v) {
+                Some(stat) => format!("{:x?}", stat),
+                None => format!("{:x?}", v),
+            },
+            (Ty::Cc, Value::Cc(cc)) => format!("{:x?}", cc),
+            (_, Value::Bool(b)) => format!("{:x?}", b),
+            (_, Value::Word(w)) => format!("{:x?}", w),
+            (_, Value::Bytes(b)) => format!("{:x?}", b),
+            (_, Value::Cc(cc)) => format!("{:x?}", cc),
+        }
+    }
+
+    /// Print the status of the stages at the beginning of a cycle, like the
+    /// one generated by the `hcl!` macro.
+    fn print_state(&self) {
+        let pres: Vec<_> = self.stage_alias.iter().map(|(_, pre, _)| *pre).collect();
+        let table = crate::utils::format_stage_status(
+            &pres,
+            &self.status,
+            |i, field| {
+                let s = self.stage_alias[i].2;
+                self.regs[s]
+                    .field(field)
+                    .and_then(|k| self.cur_state[s][k].as_word())
+            },
+            |i| {
+                let s = self.stage_alias[i].2;
+                let n = self.regs[s].n_fields();
+                (
+                    truthy(&self.nex_state[s][n]),
+                    truthy(&self.nex_state[s][n + 1]),
+                )
+            },
+        );
+        if !table.is_empty() {
+            println!("{table}");
+        }
+    }
+}
+
+impl<U: HclUnits> std::fmt::Display for HclSim<U> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.circuit.order)
+    }
+}
+
+impl<U: HclUnits> CpuSim for HclSim<U> {
+    fn initiate_next_cycle(&mut self) {
+        let field = |stage: &[Value], k: usize| truthy(&stage[k]);
+        let ctrl: Vec<(bool, bool)> = self
+            .stage_alias
+            .iter()
+            .map(|(_, _, s)| {
+                let n = self.regs[*s].n_fields();
+                (
+                    field(&self.nex_state[*s], n),
+                    field(&self.nex_state[*s], n + 1),
+                )
+            })
+            .collect();
+        self.stages.shift(&ctrl);
+        for (s, reg) in self.regs.iter().enumerate() {
+            let n = reg.n_fields();
+            let (bubble, stall) = (
+                field(&self.nex_state[s], n),
+                field(&self.nex_state[s], n + 1),
+            );
+            if bubble {
+                self.cur_state[s][..n].clone_from_slice(&reg.defaults[..n]);
+                if stall {
+                    tracing::error!("bubble and stall at the same time");
+                }
+            } else if !stall {
+                let new = self.nex_state[s][..n].to_vec();
+                self.cur_state[s][..n].clone_from_slice(&new);
+            }
+        }
+    }
+
+    fn propagate_signals(&mut self) {
+        self.units.access_log().clear();
+        let tracer = self.update();
+        self.coverage.record(&tracer.cases);
+        self.tunnels = tracer.tunnel;
+        self.tunnels.sort_unstable();
+        self.tunnels.dedup();
+        self.cycle_count += 1;
+        self.stages.fetch(self.program_counter());
+
+        if truthy(&self.cur_inter[self.termination]) {
+            self.terminate = true;
+        }
+        if let Some(msg) = self.check_assertions() {
+            self.failed_assertion = Some(msg);
+            self.terminate = true;
+        }
+    }
+
+    fn program_counter(&self) -> u64 {
+        self.cur_inter[self.program_counter]
+            .as_word()
+            .unwrap_or_default()
+    }
+
+    fn is_terminate(&self) -> bool {
+        self.terminate
+    }
+
+    fn cycle_count(&self) -> u64 {
+        self.cycle_count
+    }
+
+    fn cycle_cost(&self) -> u64 {
+        self.circuit.order.clock_period()
+    }
+
+    fn registers(&self) -> RegFile {
+        self.units.register_file()
+    }
+
+    fn set_register(&mut self, reg: u8, val: u64) {
+        self.units.set_register(reg, val)
+    }
+
+    fn condition_code(&self) -> ConditionCode {
+        self.units.condition_code()
+    }
+
+    fn get_stage_info(&self) -> Vec<StageInfo> {
+        let mut info: Vec<StageInfo> = self
+            .sections
+            .iter()
+            .map(|name| StageInfo {
+                name,
+                signals: vec![],
+            })
+            .collect();
+        if info.is_empty() {
+            info.push(StageInfo {
+                name: "(default)",
+                signals: vec![],
+            });
+        }
+        for ((name, ty, section), value) in self.signals.iter().zip(&self.cur_inter) {
+            info[section.unwrap_or(0)]
+                .signals
+                .push((name.to_string(), Self::format_value(*ty, value)));
+        }
+        info
+    }
+
+    fn data_accesses(&self) -> Vec<DataAccess> {
+        self.units.access_log().accesses()
+    }
+
+    fn stage_instructions(&self) -> Vec<(&'static str, Option<u64>)> {
+        self.stages.instructions(&self.stage_names)
+    }
+
+    fn retired_instruction(&self) -> Option<u64> {
+        self.stages.retired
+    }
+
+    fn active_tunnels(&self) -> Vec<&'static str> {
+        self.tunnels.clone()
+    }
+
+    fn failed_assertion(&self) -> Option<String> {
+        self.failed_assertion.clone()
+    }
+
+    fn coverage(&self) -> &Coverage {
+        &self.coverage
+    }
+
+    fn tunnel_map(&self) -> &'static [crate::datapath::Tunnel] {
+        &[]
+    }
+
+    fn datapath(&self) -> Option<crate::datapath::Datapath> {
+        None
+    }
+
+    fn signal(&self, name: &str) -> Option<u64> {
+        if let Some((stage, field)) = name.split_once('.') {
+            let (state, s) = self.stage_alias.iter().find_map(|&(cur, pre, s)| {
+                if stage == pre {
+                    Some((&self.cur_state, s))
+                } else if stage == cur {
+                    Some((&self.nex_state, s))
+                } else {
+                    None
+                }
+            })?;
+            let f = self.regs[s].field(field)?;
+            return state[s][f].as_word();
+        }
+        let index = self.signals.iter().position(|(n, _, _)| *n == name)?;
+        self.cur_inter[index].as_word()
+    }
+
+    fn force_signal(&mut self, name: &str, value: &Value) -> Result<()> {
+        let Some((unit, field)) = name.split_once('.') else {
+            bail!("`{name}` is neither a stage field nor a unit output");
+        };
+        if let Some(&(_, _, s)) = self.stage_alias.iter().find(|(_, pre, _)| *pre == unit) {
+            let Some(f) = self.regs[s].field(field) else {
+                bail!("stage `{unit}` has no field `{field}`");
+            };
+            let Some(value) = self.regs[s].fields[f].1.convert(value.clone()) else {
+                bail!("can not set `{name}` to {value:?}");
+            };
+            self.cur_state[s][f] = value;
+            return Ok(());
+        }
+        if U::output(&self.cur_unit_out, unit, field).is_none() {
+            bail!("`{name}` is neither a stage field nor a unit output");
+        }
+        if !U::set_output(&mut self.cur_unit_out, unit, field, value) {
+            bail!("can not set `{name}` to {value:?}");
+        }
+        self.forced_outputs
+            .retain(|(u, f, _)| (*u, *f) != (unit, field));
+        self.forced_outputs
+            .push((leak(unit), leak(field), value.clone()));
+        Ok(())
+    }
+
+    fn step(&mut self) {
+        use binutils::clap::builder::styling::*;
+        let title_style = Style::new().bold();
+
+        if self.tty_out {
+            println!(
+                "{title_style}{summary:=^80}{title_style:#}",
+                summary = format!(
+                    " [Cycle {} (*{})] ",
+                    self.cycle_count() + 1,
+                    self.cycle_cost()
+                ),
+            );
+        }
+
+        self.propagate_signals();
+
+        tracing::trace!("{:?}", self.get_stage_info());
+
+        if self.tty_out {
+            println!(
+                "{title_style}PC = {:#x}{title_style:#}",
+                self.program_counter(),
+            );
+            self.print_state();
+            println!("{}", self.units);
+            for stage in self.get_stage_info() {
+                tracing::info!("{:-^70}", format!(" {} ", stage.name));
+                for (name, val) in stage.signals {
+                    tracing::info!("{:<10} = {}", name, val);
+                }
+            }
+        }
+
+        if self.is_terminate() {
+            if self.tty_out {
+                println!("terminate!");
+            }
+        } else {
+            self.initiate_next_cycle();
+        }
+    }
+
+    fn proporder(&self) -> &PropOrder {
+        &self.circuit.order
+    }
+}
@@ -0,0 +1,869 @@
+//! Translate the HCL files of CS:APP, e.g. `seq-full.hcl` and `pipe-full.hcl`,
+//! into the HCL dialect of this project (see [`crate::hcl`]), which is either
+//! loaded at runtime or compiled as a Rust module by [`sim_macro::hcl`].
+//!
+//! A CS:APP HCL file only defines the control logic, while the datapath is
+//! fixed in the C simulator. Thus the translated control signals are followed
+//! by the pipeline registers and the wiring of `seq_std` (if `new_pc` is
+//! defined) or `pipe_std` (if `f_pc` is defined). Signals declared by
+//! `boolsig` and `wordsig` are mapped by their C expressions, e.g.
+//! `'if_id_curr->icode'` becomes `D.icode`, and the `quote` blocks are
+//! dropped.
+//!
+//! As in `hcl2c`, a case expression yields 0 if none of its cases matches.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, bail, Context, Result};
+use pest::{
+    iterators::{Pair, Pairs},
+    pratt_parser::{Assoc, Op, PrattParser},
+    Parser,
+};
+use pest_derive::Parser;
+
+use crate::isa::{inst_code, op_code, reg_code};
+
+#[derive(Parser)]
+#[grammar = "src/hcl2rs/csapp.pest"] // relative to src
+struct CsappParser;
+
+#[derive(Debug)]
+enum Expr {
+    Num(i64),
+    Name(String),
+    Not(Box<Expr>),
+    Binary(Box<Expr>, &'static str, Box<Expr>),
+    /// `expr in { a, b, c }`
+    In(Box<Expr>, Vec<Expr>),
+    /// `[ cond : value; ... ]`
+    Switch(Vec<(Expr, Expr)>),
+}
+
+#[derive(Debug)]
+enum ItemKind {
+    Comment(String),
+    Quote(String),
+    /// `wordsig name 'C expression'`
+    Decl {
+        name: String,
+        c_expr: String,
+    },
+    /// `word name = value;`
+    Def {
+        kind: String,
+        name: String,
+        value: Expr,
+    },
+}
+
+#[derive(Debug)]
+struct Item {
+    line: usize,
+    end_line: usize,
+    kind: ItemKind,
+}
+
+struct ExprParser(PrattParser<Rule>);
+
+impl ExprParser {
+    fn new() -> Self {
+        // from the lowest precedence to the highest, as in `hcl.y` of CS:APP
+        Self(
+            PrattParser::new()
+                .op(Op::infix(Rule::or, Assoc::Left))
+                .op(Op::infix(Rule::and, Assoc::Left))
+                .op(Op::prefix(Rule::not))
+                .op(Op::infix(Rule::eq, Assoc::Left)
+                    | Op::infix(Rule::ne, Assoc::Left)
+                    | Op::infix(Rule::lt, Assoc::Left)
+                    | Op::infix(Rule::le, Assoc::Left)
+                    | Op::infix(Rule::gt, Assoc::Left)
+                    | Op::infix(Rule::ge, Assoc::Left))
+                .op(Op::postfix(Rule::in_set)),
+        )
+    }
+
+    fn parse(&self, pairs: Pairs<'_, Rule>) -> Result<Expr> {
+        self.0
+            .map_primary(|primary| {
+                Ok(match primary.as_rule() {
+                    Rule::num => Expr::Num(parse_num(primary.as_str())?),
+                    Rule::ident => Expr::Name(primary.as_str().to_string()),
+                    Rule::expr => self.parse(primary.into_inner())?,
+                    Rule::switch => Expr::Switch(
+                        primary
+                            .into_inner()
+                            .map(|case| {
+                                let mut it = case.into_inner();
+                                let mut next =
+                                    || self.parse(it.next().expect("invalid case").into_inner());
+                                Ok((next()?, next()?))
+                            })
+                            .collect::<Result<_>>()?,
+                    ),
+                    rule => unreachable!("unexpected rule {:?}", rule),
+                })
+            })
+            .map_prefix(|_, rhs| Ok(Expr::Not(Box::new(rhs?))))
+            .map_postfix(|lhs, op| {
+                let set = op
+                    .into_inner()
+                    .filter(|p| p.as_rule() == Rule::expr)
+                    .map(|p| self.parse(p.into_inner()))
+                    .collect::<Result<_>>()?;
+                Ok(Expr::In(Box::new(lhs?), set))
+            })
+            .map_infix(|lhs, op, rhs| {
+                let op = match op.as_rule() {
+                    Rule::and => "&&",
+                    Rule::or => "||",
+                    Rule::eq => "==",
+                    Rule::ne => "!=",
+                    Rule::le => "<=",
+                    Rule::ge => ">=",
+                    Rule::lt => "<",
+                    Rule::gt => ">",
+                    rule => unreachable!("unexpected rule {:?}", rule),
+                };
+                Ok(Expr::Binary(Box::new(lhs?), op, Box::new(rhs?)))
+            })
+            .parse(pairs)
+    }
+}
+
+fn parse_num(s: &str) -> Result<i64> {
+    let (neg, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let r = match s.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    let n = r.with_context(|| format!("invalid integer `{s}`"))?;
+    Ok(if neg { -n } else { n })
+}
+
+fn quoted(pair: Pair<'_, Rule>) -> String {
+    pair.into_inner()
+        .next()
+        .expect("invalid quoted code")
+        .as_str()
+        .to_string()
+}
+
+fn parse(src: &str) -> Result<Vec<Item>> {
+    let file = CsappParser::parse(Rule::file, src)
+        .map_err(|e| anyhow!("{e}"))
+        .context("fail to parse CS:APP HCL file")?
+        .next()
+        .expect("invalid parse rule");
+    let exprs = ExprParser::new();
+
+    let mut items = Vec::new();
+    for pair in file.into_inner() {
+        let line = pair.line_col().0;
+        let end_line = line + pair.as_str().trim_end().lines().count().max(1) - 1;
+        let kind = match pair.as_rule() {
+            Rule::comment => ItemKind::Comment(pair.as_str().to_string()),
+            Rule::item => {
+                let inner = pair.into_inner().next().expect("invalid item");
+                let rule = inner.as_rule();
+                let mut it = inner.into_inner();
+                let mut next = || it.next().expect("invalid item");
+                match rule {
+                    Rule::quote => {
+                        let _ = next();
+                        ItemKind::Quote(quoted(next()))
+                    }
+                    Rule::decl => {
+                        let _ = next();
+                        ItemKind::Decl {
+                            name: next().as_str().to_string(),
+                            c_expr: quoted(next()),
+                        }
+                    }
+                    Rule::def => ItemKind::Def {
+                        kind: next().as_str().to_string(),
+                        name: next().as_str().to_string(),
+                        value: exprs
+                            .parse(next().into_inner())
+                            .with_context(|| format!("line {line}"))?,
+                    },
+                    rule => unreachable!("unexpected rule {:?}", rule),
+                }
+            }
+            Rule::EOI => continue,
+            rule => unreachable!("unexpected rule {:?}", rule),
+        };
+        items.push(Item {
+            line,
+            end_line,
+            kind,
+        });
+    }
+    Ok(items)
+}
+
+/// A processor of CS:APP that is translated onto the hardware of this project
+struct Target {
+    hardware: &'static str,
+    /// The output, where `{control}` is replaced by the control logic
+    template: &'static str,
+    /// Control signals used by the datapath: (name in the CS:APP file, name,
+    /// type, value if it is not defined)
+    controls: &'static [(
+        &'static str,
+        &'static str,
+        &'static str,
+        Option<&'static str>,
+    )],
+    /// Map a C expression of the CS:APP simulator to an expression.
+    signal: fn(&str) -> Option<String>,
+}
+
+impl Target {
+    /// Names of the signals defined by the datapath in the template
+    fn fixed_names(&self) -> HashSet<&'static str> {
+        let (_, datapath) = self
+            .template
+            .split_once("{control}")
+            .expect("invalid template");
+        datapath
+            .lines()
+            .filter(|line| !line.starts_with('@'))
+            .filter_map(|line| line.split_once(" = ")?.0.split_whitespace().last())
+            .collect()
+    }
+}
+
+const SEQ: Target = Target {
+    hardware: "hardware_seq",
+    template: include_str!("seq.hcl"),
+    controls: &[
+        ("icode", "icode", "u8", None),
+        ("ifun", "ifun", "u8", None),
+        ("instr_valid", "instr_valid", "bool", None),
+        ("need_regids", "need_regids", "bool", None),
+        ("need_valC", "need_valC", "bool", None),
+        ("srcA", "srcA", "u8", None),
+        ("srcB", "srcB", "u8", None),
+        ("dstE", "dstE", "u8", None),
+        ("dstM", "dstM", "u8", None),
+        ("aluA", "aluA", "u64", None),
+        ("aluB", "aluB", "u64", None),
+        ("alufun", "alufun", "u8", None),
+        ("set_cc", "set_cc", "bool", None),
+        ("mem_read", "mem_read", "bool", None),
+        ("mem_write", "mem_write", "bool", None),
+        ("mem_addr", "mem_addr", "u64", None),
+        ("mem_data", "mem_data", "u64", None),
+        ("Stat", "stat", "Stat", None),
+        ("new_pc", "new_pc", "u64", None),
+    ],
+    signal: seq_signal,
+};
+
+fn seq_signal(c_expr: &str) -> Option<String> {
+    let s = match c_expr {
+        "pc" => "pc",
+        "imem_icode" => "imem.icode",
+        "imem_ifun" => "imem.ifun",
+        "imem_error" => "imem.error",
+        "icode" => "icode",
+        "ifun" => "ifun",
+        "ra" => "ialign.rA",
+        "rb" => "ialign.rB",
+        "valc" => "ialign.valC",
+        "valp" => "valP",
+        "instr_valid" => "instr_valid",
+        "vala" => "reg_read.valA",
+        "valb" => "reg_read.valB",
+        "vale" => "valE",
+        "cond" => "cnd",
+        "valm" => "valM",
+        "dmem_error" => "dmem.error",
+        _ => return None,
+    };
+    Some(s.to_string())
+}
+
+const PIPE: Target = Target {
+    hardware: "hardware_pipe",
+    template: include_str!("pipe.hcl"),
+    controls: &[
+        ("f_pc", "f_pc", "u64", None),
+        ("f_icode", "f_icode", "u8", None),
+        ("f_ifun", "f_ifun", "u8", None),
+        ("instr_valid", "instr_valid", "bool", None),
+        ("f_stat", "f_stat", "Stat", None),
+        ("need_regids", "need_regids", "bool", None),
+        ("need_valC", "need_valC", "bool", None),
+        ("f_predPC", "f_pred_pc", "u64", None),
+        ("d_srcA", "d_srcA", "u8", None),
+        ("d_srcB", "d_srcB", "u8", None),
+        ("d_dstE", "d_dstE", "u8", None),
+        ("d_dstM", "d_dstM", "u8", None),
+        ("d_valA", "d_valA", "u64", None),
+        ("d_valB", "d_valB", "u64", None),
+        ("aluA", "aluA", "u64", None),
+        ("aluB", "aluB", "u64", None),
+        ("alufun", "alufun", "u8", None),
+        ("set_cc", "set_cc", "bool", None),
+        ("e_valA", "e_valA", "u64", Some("E.valA")),
+        ("e_dstE", "e_dstE", "u8", Some("E.dstE")),
+        ("mem_addr", "mem_addr", "u64", None),
+        ("mem_read", "mem_read", "bool", None),
+        ("mem_write", "mem_write", "bool", None),
+        ("mem_data", "mem_data", "u64", Some("M.valA")),
+        ("m_stat", "m_stat", "Stat", None),
+        ("w_dstE", "w_dstE", "u8", Some("W.dstE")),
+        ("w_valE", "w_valE", "u64", Some("W.valE")),
+        ("w_dstM", "w_dstM", "u8", Some("W.dstM")),
+        ("w_valM", "w_valM", "u64", Some("W.valM")),
+        ("Stat", "prog_stat", "Stat", None),
+        ("F_bubble", "f_bubble", "bool", None),
+        ("F_stall", "f_stall", "bool", None),
+        ("D_stall", "d_stall", "bool", None),
+        ("D_bubble", "d_bubble", "bool", None),
+        ("E_stall", "e_stall", "bool", None),
+        ("E_bubble", "e_bubble", "bool", None),
+        ("M_stall", "m_stall", "bool", None),
+        ("M_bubble", "m_bubble", "bool", None),
+        ("W_stall", "w_stall", "bool", None),
+        ("W_bubble", "w_bubble", "bool", None),
+    ],
+    signal: pipe_signal,
+};
+
+/// Fields of the pipeline registers in `pipe.hcl`
+const PIPE_FIELDS: &[(&str, &[&str])] = &[
+    ("f", &["pred_pc"]),
+    ("d", &["stat", "icode", "ifun", "rA", "rB", "valC", "valP"]),
+    (
+        "e",
+        &[
+            "stat", "icode", "ifun", "valC", "valA", "valB", "dstE", "dstM", "srcA", "srcB",
+        ],
+    ),
+    (
+        "m",
+        &["stat", "icode", "cnd", "valE", "valA", "dstE", "dstM"],
+    ),
+    ("w", &["stat", "icode", "valE", "valM", "dstE", "dstM"]),
+];
+
+fn pipe_signal(c_expr: &str) -> Option<String> {
+    let s = match c_expr {
+        "imem_icode" => Some("imem.icode"),
+        "imem_ifun" => Some("imem.ifun"),
+        "imem_error" => Some("imem.error"),
+        "instr_valid" => Some("instr_valid"),
+        "dmem_error" => Some("dmem.error"),
+        "d_regvala" => Some("d_rvalA"),
+        "d_regvalb" => Some("d_rvalB"),
+        _ => None,
+    };
+    if let Some(s) = s {
+        return Some(s.to_string());
+    }
+
+    // e.g. `if_id_curr->icode`
+    let (reg, field) = c_expr.split_once("->")?;
+    let (reg, next) = match reg.strip_suffix("_curr") {
+        Some(reg) => (reg, false),
+        None => (reg.strip_suffix("_next")?, true),
+    };
+    // the stage of the pipeline register, and the stage that computes its
+    // next value
+    let (stage, pre) = match reg {
+        "pc" => ("f", "f"),
+        "if_id" => ("d", "f"),
+        "id_ex" => ("e", "d"),
+        "ex_mem" => ("m", "e"),
+        "mem_wb" => ("w", "m"),
+        _ => return None,
+    };
+    let field = match field {
+        "pc" => "pred_pc",
+        "status" => "stat",
+        "icode" => "icode",
+        "ifun" => "ifun",
+        "ra" => "rA",
+        "rb" => "rB",
+        "valc" => "valC",
+        "valp" => "valP",
+        "srca" => "srcA",
+        "srcb" => "srcB",
+        "vala" => "valA",
+        "valb" => "valB",
+        "vale" => "valE",
+        "valm" => "valM",
+        "deste" => "dstE",
+        "destm" => "dstM",
+        "takebranch" => "cnd",
+        _ => return None,
+    };
+    let (_, fields) = PIPE_FIELDS.iter().find(|(s, _)| *s == stage)?;
+    if !fields.contains(&field) {
+        return None;
+    }
+    Some(if next {
+        format!("{pre}_{field}")
+    } else {
+        format!("{}.{field}", stage.to_ascii_uppercase())
+    })
+}
+
+/// Map a constant of the CS:APP simulator, e.g. `I_NOP`, to a constant.
+fn constant(c_expr: &str) -> Option<String> {
+    let s = match c_expr {
+        "I_RRMOVQ" => "CMOVX",
+        "I_ALU" => "OPQ",
+        "I_JMP" => "JX",
+        "I_IADDQ" => "IOPQ",
+        "F_NONE" => "0",
+        "REG_NONE" => "RNONE",
+        "STAT_AOK" => "Aok",
+        "STAT_BUB" => "Bub",
+        "STAT_HLT" => "Hlt",
+        "STAT_ADR" => "Adr",
+        "STAT_INS" => "Ins",
+        _ => {
+            let (prefix, name) = c_expr.split_once('_')?;
+            let known = match prefix {
+                "I" => inst_code::code_of(name).is_some(),
+                "REG" => reg_code::code_of(name).is_some(),
+                "A" => op_code::code_of(name).is_some(),
+                _ => false,
+            };
+            return known.then(|| name.to_string());
+        }
+    };
+    Some(s.to_string())
+}
+
+/// Whether the quoted C code is the glue of the CS:APP simulator, which has
+/// nothing to do with the control logic.
+fn is_glue(c_code: &str) -> bool {
+    let c_code = c_code.trim();
+    c_code.starts_with("#include")
+        || c_code.starts_with("int main")
+        || ["sim_main", "plusmode", "gen_pc"]
+            .iter()
+            .any(|s| c_code.contains(s))
+}
+
+/// Precedence of operators in the output, from the lowest to the highest
+fn prec(op: &str) -> u8 {
+    match op {
+        "||" => 1,
+        "&&" => 2,
+        "==" | "!=" | "in" => 3,
+        "<" | "<=" | ">" | ">=" => 4,
+        "!" => 5,
+        _ => 6,
+    }
+}
+
+/// Put the expression in parentheses if it binds looser than `min`.
+fn wrap((s, p): (String, u8), min: u8) -> String {
+    if p < min {
+        format!("({s})")
+    } else {
+        s
+    }
+}
+
+/// e.g. `D.icode`, `NOP`
+fn is_lvalue(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+struct Translator<'a> {
+    target: &'a Target,
+    /// name -> C expression
+    decls: HashMap<&'a str, &'a str>,
+    /// name in the CS:APP file -> name of the signal
+    defined: HashMap<&'a str, String>,
+    /// names that have been reported
+    reported: HashSet<&'a str>,
+    problems: Vec<String>,
+}
+
+impl<'a> Translator<'a> {
+    fn name(&mut self, name: &'a str, line: usize) -> String {
+        if let Some(ours) = self.defined.get(name) {
+            return ours.clone();
+        }
+        let problem = match self.decls.get(name) {
+            Some(c_expr) => match constant(c_expr).or_else(|| (self.target.signal)(c_expr)) {
+                Some(s) => return s,
+                None => format!(
+                    "line {line}: `{name}` is bound to `{c_expr}`, which has no counterpart in `{}`",
+                    self.target.hardware
+                ),
+            },
+            None => format!("line {line}: `{name}` is not declared"),
+        };
+        if self.reported.insert(name) {
+            self.problems.push(problem);
+        }
+        name.to_string()
+    }
+
+    /// Translate the expression. Return the output and its precedence. In a
+    /// boolean context, `0` and `1` become `false` and `true`.
+    fn expr(&mut self, expr: &'a Expr, boolean: bool, line: usize) -> (String, u8) {
+        match expr {
+            Expr::Num(n @ (0 | 1)) if boolean => ((*n == 1).to_string(), 6),
+            Expr::Num(n) => (n.to_string(), if *n < 0 { prec("!") } else { 6 }),
+            Expr::Name(name) => (self.name(name, line), 6),
+            Expr::Not(e) => {
+                let e = self.expr(e, true, line);
+                (format!("!{}", wrap(e, prec("!"))), prec("!"))
+            }
+            Expr::Binary(a, op, b) => {
+                let p = prec(op);
+                let logical = matches!(*op, "&&" | "||");
+                let a = wrap(self.expr(a, logical, line), p);
+                let b = wrap(self.expr(b, logical, line), p + 1);
+                (format!("{a} {op} {b}"), p)
+            }
+            Expr::In(e, set) => {
+                let e = self.expr(e, false, line);
+                let set: Vec<String> = set
+                    .iter()
+                    .map(|item| wrap(self.expr(item, false, line), prec("==") + 1))
+                    .collect();
+                if set.iter().all(|s| is_lvalue(s)) {
+                    let e = wrap(e, prec("in") + 1);
+                    (format!("{e} in {{ {} }}", set.join(", ")), prec("in"))
+                } else {
+                    // only signals and constants are allowed in a set
+                    let e = wrap(e, prec("==") + 1);
+                    let cmps: Vec<String> = set.iter().map(|s| format!("{e} == {s}")).collect();
+                    (cmps.join(" || "), prec("||"))
+                }
+            }
+            Expr::Switch(_) => {
+                self.problems.push(format!(
+                    "line {line}: case expressions are only supported as the value of a definition"
+                ));
+                ("0".to_string(), 6)
+            }
+        }
+    }
+
+    /// Translate the definition of a signal.
+    fn def(&mut self, ty: &str, name: &str, value: &'a Expr, line: usize) -> String {
+        let boolean = ty == "bool";
+        let Expr::Switch(cases) = value else {
+            let (value, _) = self.expr(value, boolean, line);
+            return format!("{ty} {name} = {value};\n");
+        };
+        let mut out = format!("{ty} {name} = [\n");
+        for (cond, value) in cases {
+            let (cond, _) = self.expr(cond, true, line);
+            let (value, _) = self.expr(value, boolean, line);
+            out += &format!("    {cond} : {value};\n");
+        }
+        if !matches!(cases.last(), Some((Expr::Num(n), _)) if *n != 0) {
+            let zero = match ty {
+                "bool" => "false",
+                "Stat" => "Aok",
+                _ => "0",
+            };
+            out += &format!("    true : {zero};\n");
+        }
+        out + "];\n"
+    }
+}
+
+/// Section titles are written as `#### title ####` in CS:APP files.
+fn section_title(comment: &str) -> Option<&str> {
+    let comment = comment.trim();
+    if !comment.starts_with("###") || !comment.ends_with("###") {
+        return None;
+    }
+    let title = comment.trim_matches('#').trim();
+    (!title.is_empty()).then_some(title)
+}
+
+/// e.g. `:=====: Fetch Stage :=====:`
+fn section(title: &str) -> String {
+    let width = 76usize.saturating_sub(title.len() + 2);
+    format!(
+        ":{}: {title} :{}:\n",
+        "=".repeat(width / 2),
+        "=".repeat(width - width / 2)
+    )
+}
+
+/// The result of [`translate`]
+#[derive(Debug)]
+pub struct Translation {
+    /// The source in the HCL dialect of this project
+    pub output: String,
+    /// Constructs that can not be translated, with their line numbers. They
+    /// are kept as they are in the output.
+    pub problems: Vec<String>,
+}
+
+impl Translation {
+    /// The output as a Rust module, which defines the architecture by
+    /// [`crate::define_stages`] and [`sim_macro::hcl`]. It can be compiled
+    /// into the simulator by putting it into `src/architectures/extra`.
+    pub fn rust_module(&self) -> String {
+        let start = self
+            .output
+            .find("define_stages!")
+            .expect("no stages in the output");
+        // the `define_stages!` block ends at its matching brace
+        let mut depth = 0;
+        let mut end = start;
+        for (i, c) in self.output[start..].char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        end = start + i + 1;
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let body = self.output[end..].trim();
+        format!(
+            "{}crate::{}\n\nsim_macro::hcl! {{\n{body}\n}}\n",
+            &self.output[..start],
+            &self.output[start..end],
+        )
+    }
+}
+
+/// Translate the source of a CS:APP HCL file.
+pub fn translate(src: &str) -> Result<Translation> {
+    let items = parse(src)?;
+    let defs: Vec<(&str, usize)> = items
+        .iter()
+        .filter_map(|item| match &item.kind {
+            ItemKind::Def { name, .. } => Some((name.as_str(), item.line)),
+            _ => None,
+        })
+        .collect();
+    let is_defined = |name: &str| defs.iter().any(|(n, _)| *n == name);
+    let target = if is_defined("f_pc") {
+        &PIPE
+    } else if is_defined("new_pc") {
+        &SEQ
+    } else if is_defined("pc") {
+        bail!("SEQ+ processors (which define `pc`) are not supported")
+    } else {
+        bail!("neither `new_pc` (SEQ) nor `f_pc` (PIPE) is defined")
+    };
+
+    let mut tr = Translator {
+        target,
+        decls: HashMap::new(),
+        defined: HashMap::new(),
+        reported: HashSet::new(),
+        problems: Vec::new(),
+    };
+    for item in &items {
+        if let ItemKind::Decl { name, c_expr } = &item.kind {
+            tr.decls.insert(name, c_expr);
+        }
+    }
+    let fixed = target.fixed_names();
+    for &(name, line) in &defs {
+        let ours = match target.controls.iter().find(|c| c.0 == name) {
+            Some(&(_, ours, ..)) => ours,
+            None => name,
+        };
+        if fixed.contains(ours) {
+            tr.problems.push(format!(
+                "line {line}: `{name}` is computed by the datapath of `{}`",
+                target.hardware
+            ));
+        } else if tr.defined.insert(name, ours.to_string()).is_some() {
+            tr.problems
+                .push(format!("line {line}: `{name}` is defined more than once"));
+        }
+    }
+    let mut defaults = String::new();
+    for &(csapp, ours, ty, default) in target.controls {
+        if tr.defined.contains_key(csapp) {
+            continue;
+        }
+        match default {
+            Some(value) => defaults += &format!("{ty} {ours} = {value};\n"),
+            None => tr.problems.push(format!("missing definition of `{csapp}`")),
+        }
+    }
+
+    // blocks of the control logic, separated by blank lines
+    let mut blocks: Vec<String> = Vec::new();
+    let mut comments: Vec<&str> = Vec::new();
+    let mut title = None;
+    // (end line of the last item, index of its block if it is a definition)
+    let mut last: Option<(usize, Option<usize>)> = None;
+    for item in &items {
+        match &item.kind {
+            ItemKind::Comment(raw) => {
+                let text = raw.trim_start_matches('#').trim();
+                if let Some((end_line, block)) = last {
+                    if end_line == item.line {
+                        // a trailing comment
+                        if let Some(block) = block.and_then(|i| blocks.get_mut(i)) {
+                            block.insert_str(block.len() - 1, &format!(" // {text}"));
+                        }
+                        continue;
+                    }
+                }
+                if let Some(t) = section_title(raw) {
+                    title = Some(t);
+                    comments.clear();
+                } else if !text.is_empty() && !text.starts_with("/* $") {
+                    comments.push(text);
+                }
+                last = None;
+            }
+            ItemKind::Quote(c_code) => {
+                if !is_glue(c_code) {
+                    tr.problems.push(format!(
+                        "line {}: C code in `quote` can not be translated",
+                        item.line
+                    ));
+                }
+                (title, last) = (None, Some((item.end_line, None)));
+                comments.clear();
+            }
+            ItemKind::Decl { .. } => {
+                (title, last) = (None, Some((item.end_line, None)));
+                comments.clear();
+            }
+            ItemKind::Def { kind, name, value } => {
+                if let Some(t) = title.take() {
+                    blocks.push(section(t));
+                }
+                let (ours, ty) = match target.controls.iter().find(|c| c.0 == name) {
+                    Some(&(_, ours, ty, _)) => (ours, ty),
+                    None => (name.as_str(), if kind == "bool" { "bool" } else { "u64" }),
+                };
+                let mut block: String = comments.drain(..).map(|c| format!("// {c}\n")).collect();
+                block += &tr.def(ty, ours, value, item.line);
+                blocks.push(block);
+                last = Some((item.end_line, Some(blocks.len() - 1)));
+            }
+        }
+    }
+
+    let mut output = target.template.replace("{control}", &blocks.join("\n"));
+    if !defaults.is_empty() {
+        output += "\n// Signals not defined in the CS:APP file\n";
+        output += &defaults;
+    }
+    Ok(Translation {
+        output,
+        problems: tr.problems,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::translate;
+    use crate::{architectures::create_sim, framework::MemData};
+
+    const SEQ_FULL: &str = include_str!("samples/seq-full.hcl");
+    const PIPE_FULL: &str = include_str!("samples/pipe-full.hcl");
+
+    /// The translated samples behave as the builtin architectures.
+    #[test]
+    fn test_translate_samples() -> anyhow::Result<()> {
+        for (src, arch) in [(SEQ_FULL, "seq_std"), (PIPE_FULL, "pipe_std")] {
+            let t = translate(src)?;
+            assert!(t.problems.is_empty(), "{:?}", t.problems);
+            let a = crate::assemble(crate::asm::tests::RSUM_YS, Default::default())?;
+            let init = || MemData::init(a.obj.init_mem());
+            let mut expected = create_sim(arch.to_string(), init(), false).unwrap();
+            let mut actual = crate::hcl::load_str(&t.output, init(), false)?;
+            while !expected.is_terminate() {
+                expected.step();
+                actual.step();
+                assert_eq!(expected.program_counter(), actual.program_counter());
+                assert_eq!(expected.registers(), actual.registers());
+                assert_eq!(expected.data_accesses(), actual.data_accesses());
+            }
+            assert!(actual.is_terminate());
+            assert_eq!(expected.cycle_count(), actual.cycle_count());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_translate_expr() -> anyhow::Result<()> {
+        let src = PIPE_FULL.replace(
+            "bool W_bubble = 0;",
+            "bool W_bubble = !W_stat == SAOK && (W_icode in { IHALT, 3 } || !W_stat in { SADR });",
+        );
+        let t = translate(&src)?;
+        assert!(t.output.contains(
+            "bool w_bubble = !(W.stat == Aok) && (W.icode == HALT || W.icode == 3 || \
+             !(W.stat in { Adr }));"
+        ));
+        assert!(t.output.contains("bool f_bubble = false;"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rust_module() -> anyhow::Result<()> {
+        let t = translate(SEQ_FULL)?;
+        let module = t.rust_module();
+        assert!(module.starts_with("// Translated from a CS:APP HCL file by hcl2rs."));
+        assert!(module.contains(
+            "crate::define_stages! {\n    SEQstage s { pc: u64 = 0 }\n}\n\n\
+             sim_macro::hcl! {\n#![hardware = crate::architectures::hardware_seq]\n"
+        ));
+        assert!(module.ends_with("    pc: new_pc,\n});\n}\n"));
+        // the body is the same as the runtime one
+        let body = t.output.split_once("#![hardware").unwrap().1;
+        assert!(module.contains(body.trim_end()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_translate_problems() {
+        let src = PIPE_FULL
+            .replace(
+                "word e_valA = E_valA;",
+                "word e_valA = [ M_ifun == 0 : foo; 1 : [ 1 : 0; ]; ];",
+            )
+            .replace("bool W_bubble = 0;", "quote 'int x = 1;'")
+            .replace("word m_stat = [", "word e_valE = [");
+        let t = translate(&src).expect("fail to translate");
+        let problems = t.problems.join("\n");
+        for p in [
+            "`M_ifun` is bound to `ex_mem_curr->ifun`, which has no counterpart",
+            "`foo` is not declared",
+            "case expressions are only supported as the value of a definition",
+            "C code in `quote` can not be translated",
+            "`e_valE` is computed by the datapath of `hardware_pipe`",
+            "missing definition of `m_stat`",
+            "missing definition of `W_bubble`",
+        ] {
+            assert!(problems.contains(p), "{p}\n{problems}");
+        }
+        assert!(problems.contains("line 261: `M_ifun`"), "{problems}");
+
+        let err = translate("word pc = 0;").unwrap_err();
+        assert!(format!("{err:#}").contains("SEQ+"));
+        let err = translate("word f_pc = [ 1 : 0;").unwrap_err();
+        assert!(format!("{err:#}").contains("fail to parse"));
+    }
+}
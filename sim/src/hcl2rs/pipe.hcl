@@ -0,0 +1,196 @@
+// Translated from a CS:APP HCL file by hcl2rs. The pipeline registers and the
+// wiring of the hardware units are the same as `pipe_std`.
+
+define_stages! {
+    FetchStage f {
+        pred_pc: u64 = 0
+    }
+    DecodeStage d {
+        stat: Stat = Bub, icode: u8 = NOP, ifun: u8 = 0,
+        rA: u8 = RNONE, rB: u8 = RNONE,
+        valC: u64 = 0, valP: u64 = 0
+    }
+    ExecuteStage e {
+        stat: Stat = Bub, icode: u8 = NOP, ifun: u8 = 0,
+        valC: u64 = 0,
+        valA: u64 = 0, valB: u64 = 0,
+        dstE: u8 = RNONE, dstM: u8 = RNONE,
+        srcA: u8 = RNONE, srcB: u8 = RNONE
+    }
+    MemoryStage m {
+        stat: Stat = Bub, icode: u8 = NOP, cnd: bool = false,
+        valE: u64 = 0, valA: u64 = 0,
+        dstE: u8 = RNONE, dstM: u8 = RNONE
+    }
+    WritebackStage w {
+        stat: Stat = Bub, icode: u8 = NOP, valE: u64 = 0,
+        valM: u64 = 0, dstE: u8 = RNONE, dstM: u8 = RNONE
+    }
+}
+
+#![hardware = crate::architectures::hardware_pipe]
+#![program_counter = f_pc]
+#![termination = prog_term]
+#![stage_alias(F => f, D => d, E => e, M => m, W => w)]
+
+use Stat::*;
+
+{control}
+:=================================: Datapath :=================================:
+
+@set_input(imem, {
+    pc: f_pc
+});
+
+@set_input(pc_inc, {
+    need_valC: need_valC,
+    need_regids: need_regids,
+    old_pc: f_pc,
+});
+
+u64 f_valP = pc_inc.new_pc;
+
+[u8; 9] f_align = imem.align;
+
+@set_input(ialign, {
+    align: f_align,
+    need_regids: need_regids,
+});
+
+u64 f_valC = ialign.valC;
+u8 f_rA = ialign.rA;
+u8 f_rB = ialign.rB;
+
+@set_stage(f, {
+    pred_pc: f_pred_pc,
+});
+
+@set_stage(d, {
+    icode: f_icode,
+    ifun: f_ifun,
+    stat: f_stat,
+    valC: f_valC,
+    valP: f_valP,
+    rA: f_rA,
+    rB: f_rB,
+});
+
+u64 d_rvalA = reg_file.valA;
+u64 d_rvalB = reg_file.valB;
+u64 d_valC = D.valC;
+u8 d_icode = D.icode;
+u8 d_ifun = D.ifun;
+Stat d_stat = D.stat;
+
+@set_stage(e, {
+    icode: d_icode,
+    ifun: d_ifun,
+    stat: d_stat,
+    valC: d_valC,
+    srcA: d_srcA,
+    srcB: d_srcB,
+    valA: d_valA,
+    valB: d_valB,
+    dstE: d_dstE,
+    dstM: d_dstM,
+});
+
+@set_input(alu, {
+    a: aluA,
+    b: aluB,
+    fun: alufun,
+});
+
+u64 e_valE = alu.e;
+
+@set_input(reg_cc, {
+    a: aluA,
+    b: aluB,
+    e: e_valE,
+    opfun: alufun,
+    set_cc: set_cc,
+});
+
+ConditionCode cc = reg_cc.cc;
+u8 e_ifun = E.ifun;
+
+@set_input(cond, {
+    cc: cc,
+    condfun: e_ifun,
+});
+
+bool e_cnd = cond.cnd;
+u8 e_dstM = E.dstM;
+u8 e_icode = E.icode;
+Stat e_stat = E.stat;
+
+@set_stage(m, {
+    stat: e_stat,
+    dstM: e_dstM,
+    icode: e_icode,
+    dstE: e_dstE,
+    cnd: e_cnd,
+    valE: e_valE,
+    valA: e_valA,
+});
+
+@set_input(dmem, {
+    read: mem_read,
+    write: mem_write,
+    addr: mem_addr,
+    datain: mem_data,
+});
+
+u8 m_icode = M.icode;
+u64 m_valM = dmem.dataout;
+u64 m_valE = M.valE;
+u8 m_dstE = M.dstE;
+u8 m_dstM = M.dstM;
+
+@set_stage(w, {
+    stat: m_stat,
+    icode: m_icode,
+    valE: m_valE,
+    valM: m_valM,
+    dstE: m_dstE,
+    dstM: m_dstM,
+});
+
+@set_input(reg_file, {
+    srcA: d_srcA,
+    srcB: d_srcB,
+    dstE: w_dstE,
+    dstM: w_dstM,
+    valM: w_valM,
+    valE: w_valE,
+});
+
+bool prog_term = [
+    prog_stat in { Aok, Bub } : false;
+    1 : true
+];
+
+@set_stage(f, {
+    bubble: f_bubble,
+    stall: f_stall,
+});
+
+@set_stage(d, {
+    stall: d_stall,
+    bubble: d_bubble,
+});
+
+@set_stage(e, {
+    stall: e_stall,
+    bubble: e_bubble,
+});
+
+@set_stage(m, {
+    stall: m_stall,
+    bubble: m_bubble,
+});
+
+@set_stage(w, {
+    stall: w_stall,
+    bubble: w_bubble,
+});
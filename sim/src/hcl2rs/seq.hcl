@@ -0,0 +1,89 @@
+// Translated from a CS:APP HCL file by hcl2rs. The pipeline registers and the
+// wiring of the hardware units are the same as `seq_std`.
+
+define_stages! {
+    SEQstage s { pc: u64 = 0 }
+}
+
+#![hardware = crate::architectures::hardware_seq]
+#![program_counter = pc]
+#![termination = prog_term]
+#![stage_alias(S => s)]
+
+use Stat::*;
+
+{control}
+:=================================: Datapath :=================================:
+
+u64 pc = S.pc;
+
+@set_input(imem, {
+    pc: pc
+});
+
+@set_input(pc_inc, {
+    need_valC: need_valC,
+    need_regids: need_regids,
+    old_pc: pc,
+});
+
+[u8; 9] align = imem.align;
+
+@set_input(ialign, {
+    align: align,
+    need_regids: need_regids,
+});
+
+u64 valP = pc_inc.new_pc;
+
+@set_input(reg_read, {
+    srcA: srcA,
+    srcB: srcB,
+});
+
+@set_input(alu, {
+    a: aluA,
+    b: aluB,
+    fun: alufun,
+});
+
+u64 valE = alu.e;
+
+@set_input(reg_cc, {
+    a: aluA,
+    b: aluB,
+    e: valE,
+    opfun: alufun,
+    set_cc: set_cc,
+});
+
+ConditionCode cc = reg_cc.cc;
+
+@set_input(cond, {
+    cc: cc,
+    condfun: ifun,
+});
+
+bool cnd = cond.cnd;
+
+@set_input(dmem, {
+    read: mem_read,
+    write: mem_write,
+    addr: mem_addr,
+    datain: mem_data,
+});
+
+u64 valM = dmem.dataout;
+
+@set_input(reg_write, {
+    dstE: dstE,
+    dstM: dstM,
+    valM: valM,
+    valE: valE,
+});
+
+bool prog_term = stat in { Hlt, Adr, Ins };
+
+@set_stage(s, {
+    pc: new_pc,
+});
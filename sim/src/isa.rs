@@ -20,6 +20,14 @@ macro_rules! define_code {
                     _ => "no name"
                 }
             }
+            /// Inverse of `name_of`. The name is matched case-insensitively.
+            #[allow(unused)]
+            pub fn code_of(name: &str) -> Option<$typ> {
+                $(if name.eq_ignore_ascii_case(stringify!($cname)) {
+                    return Some($cname);
+                })*
+                None
+            }
         }
     };
 }
@@ -185,6 +193,21 @@ pub enum Stat {
     Ins = 4,
 }
 
+impl Stat {
+    /// The status of the given code, e.g. the value of a `stat` field.
+    pub fn from_code(code: u64) -> Option<Self> {
+        Some(match code {
+            0 => Stat::Aok,
+            1 => Stat::Bub,
+            2 => Stat::Hlt,
+            3 => Stat::Adr,
+            4 => Stat::Ins,
+            _ => return None,
+        })
+    }
+}
+
+#[allow(clippy::derivable_impls)]
 impl Default for Stat {
     fn default() -> Self {
         Self::Aok
@@ -497,3 +520,100 @@ pub fn simulate(mut bin: [u8; BIN_SIZE], tty_out: bool) -> anyhow::Result<Standa
         n_insts,
     })
 }
+
+/// Decode the instruction at `pc` and render it in the assembly syntax.
+///
+/// Returns the length of the instruction and its text, or `None` if the bytes
+/// at `pc` do not form a valid instruction. Immediate values and jump targets
+/// are printed in hex.
+pub fn disassemble(bin: &[u8], pc: usize) -> Option<(usize, String)> {
+    let byte = |i: usize| bin.get(pc + i).copied();
+    let word = |i: usize| bin.get(pc + i..pc + i + 8).map(get_u64);
+    let regs = || byte(1).map(|b| (b >> 4, b & 0xf));
+    let reg =
+        |r: u8| (r != reg_code::RNONE).then(|| format!("%{}", reg_code::name_of(r).to_lowercase()));
+    let addr = |d: u64, rb: u8| {
+        let rb = reg(rb)?;
+        Some(if d == 0 {
+            format!("({rb})")
+        } else {
+            format!("{}({rb})", d as i64)
+        })
+    };
+    let cond = |ifun: u8| match ifun {
+        cond_fn::YES => None,
+        cond_fn::LE..=cond_fn::G => Some(cond_fn::name_of(ifun).to_lowercase()),
+        _ => None,
+    };
+    let op = |ifun: u8| match ifun {
+        op_code::ADD..=op_code::XOR => Some(op_code::name_of(ifun).to_lowercase()),
+        _ => None,
+    };
+
+    let icode = byte(0)? >> 4;
+    let ifun = byte(0)? & 0xf;
+    let inst = match icode {
+        inst_code::HALT if ifun == 0 => (1, "halt".to_string()),
+        inst_code::NOP if ifun == 0 => (1, "nop".to_string()),
+        inst_code::CMOVX => {
+            let (ra, rb) = regs()?;
+            let name = match ifun {
+                cond_fn::YES => "rrmovq".to_string(),
+                _ => format!("cmov{}", cond(ifun)?),
+            };
+            (2, format!("{name} {}, {}", reg(ra)?, reg(rb)?))
+        }
+        inst_code::IRMOVQ if ifun == 0 => {
+            let (ra, rb) = regs()?;
+            if ra != reg_code::RNONE {
+                return None;
+            }
+            (10, format!("irmovq ${:#x}, {}", word(2)?, reg(rb)?))
+        }
+        inst_code::RMMOVQ if ifun == 0 => {
+            let (ra, rb) = regs()?;
+            (10, format!("rmmovq {}, {}", reg(ra)?, addr(word(2)?, rb)?))
+        }
+        inst_code::MRMOVQ if ifun == 0 => {
+            let (ra, rb) = regs()?;
+            (10, format!("mrmovq {}, {}", addr(word(2)?, rb)?, reg(ra)?))
+        }
+        inst_code::OPQ => {
+            let (ra, rb) = regs()?;
+            (2, format!("{}q {}, {}", op(ifun)?, reg(ra)?, reg(rb)?))
+        }
+        inst_code::JX => {
+            let name = match ifun {
+                cond_fn::YES => "jmp".to_string(),
+                _ => format!("j{}", cond(ifun)?),
+            };
+            (9, format!("{name} {:#x}", word(1)?))
+        }
+        inst_code::CALL if ifun == 0 => (9, format!("call {:#x}", word(1)?)),
+        inst_code::RET if ifun == 0 => (1, "ret".to_string()),
+        inst_code::PUSHQ | inst_code::POPQ if ifun == 0 => {
+            let (ra, rb) = regs()?;
+            if rb != reg_code::RNONE {
+                return None;
+            }
+            let name = if icode == inst_code::PUSHQ {
+                "pushq"
+            } else {
+                "popq"
+            };
+            (2, format!("{name} {}", reg(ra)?))
+        }
+        inst_code::IOPQ => {
+            let (ra, rb) = regs()?;
+            if ra != reg_code::RNONE {
+                return None;
+            }
+            (
+                10,
+                format!("i{}q ${:#x}, {}", op(ifun)?, word(2)?, reg(rb)?),
+            )
+        }
+        _ => return None,
+    };
+    Some(inst)
+}
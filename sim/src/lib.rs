@@ -1,10 +1,17 @@
 pub mod architectures;
 mod asm;
+pub mod coverage;
+pub mod datapath;
 mod dsl;
+pub mod eval;
 pub mod framework;
+pub mod hcl;
+pub mod hcl2rs;
 pub mod isa;
 mod object;
+pub mod repl;
 pub mod test;
+pub mod tui;
 pub mod utils;
 
 pub use asm::{assemble, AssembleOption};
@@ -22,6 +29,36 @@ mod tests {
         eprintln!("{}", r);
     }
 
+    #[test]
+    fn test_disassemble() {
+        use crate::isa::disassemble;
+
+        let r =
+            assemble(crate::asm::tests::RSUM_YS, AssembleOption::default()).expect("test failed");
+        let mem = r.obj.init_mem();
+        let insts: Vec<_> = [0x0, 0xa, 0x14, 0x1d, 0x1e, 0x20, 0x2a, 0x2c, 0x2e, 0x37]
+            .into_iter()
+            .map(|pc| disassemble(&mem, pc).map(|(_, s)| s).unwrap_or_default())
+            .collect();
+        assert_eq!(
+            insts,
+            [
+                "irmovq $0x200, %rsp",
+                "irmovq $0x60, %rdi",
+                "call 0x1e",
+                "halt",
+                "pushq %rbx",
+                "irmovq $0x0, %rax",
+                "rrmovq %rdi, %rbx",
+                "andq %rdi, %rbx",
+                "je 0x5c",
+                "mrmovq (%rdi), %rbx",
+            ]
+        );
+        // the data section is not a valid instruction
+        assert_eq!(disassemble(&mem, 0x60), None);
+    }
+
     /// in visualization of the architecture of pipeline, each tunnel
     /// starts from one ore more start points, may split to multiple heads,
     /// reaching various destination. What we concern is
@@ -56,44 +93,12 @@ mod tests {
     /// For better readability, we maintain the condition separately.
     #[test]
     fn test_draw() {
-        println!(
-            r#"
-                     ┏━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┓
-                     ┃      ┏━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┓┃
-W stat icode       valE   valM      dstE dstM              ┃┃
-   │     │           ┃      ┣━━━━━━━━│━━━━│━━━━━━━━━━━━━━━┓┃┃
-   │     ├───#Mem.##┄┃┄┄┄┄┄Data##    │    │               ┃┃┃ 
-   │     ├───Control┄┃┄┄┄┄┄memory    │    │               ┃┃┃
-   │     │           ┃  Addr┛  ┃     │    │               ┃┃┃
-   │     │           ┃  ┃ ┗━━━━┃━━━━━│━━━━│━━━━━━━━━━━━━━┓┃┃┃
-   │     │           ┗━━╋━━━━━━┃━━━━━│━━━━│━━━━━━━━━━━━━┓┃┃┃┃
-M stat icode    Cnd   valE   valA   dstE dstM           ┃┃┃┃┃
-   │     │       │      ┣━━━━━━┃━━━━━│━━━━│━━━━━━━━━━━━┓┃┃┃┃┃
-   │     │       CC─────ALU ┏━━┛     │    │            ┃┃┃┃┃┃
-   │     │          AluA┛ ┗━┃━━AluB  │    │            ┃┃┃┃┃┃
-   │     │           ┃┗━━━━━┫    ┃   │    │            ┃┃┃┃┃┃
-E stat icode   ifun valC  valA valB dstE dstM srcA srcB┃┃┃┃┃┃
-   │     │       │   ┃      ┃    ┃                     ┃┃┃┃┃┃
-   │     │       │   ┃  #######━###━━━━━━━━━━━━━━━━━━━━┛┃┃┃┃┃
-   │     │       │   ┃  #######━###━━━━━━━━━━━━━━━━━━━━━┛┃┃┃┃
-   │     │       │   ┃  Sel+Fwd━Fwd━━━━━━━━━━━━━━━━━━━━━━┃┛┃┃
-   │     │       │   ┃  ###A###━#B#━━━━━━━━━━━━━━━━━━━━━━┃━┫┃
-   │     │       │   ┃  #######━###━━━━━━━━━━━━━━━━━━━━━━┃━┃┫
-   │     │       │   ┃      ┃ ┃  ┃                       ┃ ┃┃
-   │     │       │   ┃      ┃ ┗Register━━━━━━━━━━━━━━━━━━┃━┫┃
-   │     │       │   ┃      ┗┓ ##file##━━━━━━━━━━━━━━━━━━┃━┃┛
-   │     │       │   ┗━━━━━┓ ┗━━━┓                       ┃ ┃
-D stat icode   ifun rA rB valC  valP                     ┃ ┃
-   │     │       │   │ │   ┣━━━━━┃━━━━━━━━━━Predict      ┃ ┃
-  Stat───┴───┐   │   │ │   ┃     ┣━━━━━━━━━━##PC###      ┃ ┃
-             Instruction━━━┛  ###PC####        ┃         ┃ ┃
-             ##memory###      increment        ┃         ┃ ┃
-                  ┣━━━━━━━━━━━━━━┛             ┃         ┃ ┃
-                Select━━━━━━━━━━━━━━━━━━━━━━━━━┃━━━━━━━━━┛ ┃
-                ##PC##━━━━━━━━━━━━━━━━━━━━━━━━━┃━━━━━━━━━━━┛
-F        predPC━┛                              ┃
-            ┗━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┛
-"#
-        )
+        use crate::{
+            architectures::create_sim,
+            framework::{MemData, MEM_SIZE},
+        };
+
+        let sim = create_sim("pipe_std".to_string(), MemData::init([0; MEM_SIZE]), false).unwrap();
+        println!("{}", sim.datapath().unwrap().drawing)
     }
 }
@@ -0,0 +1,461 @@
+//! A gdb-like command line debugger, which is started by `ysim --interactive`.
+//!
+//! Like the debug server, the current location is the next instruction to
+//! retire, and a breakpoint stops before that instruction retires. `step`
+//! runs by retired instructions (one instruction per source line) while
+//! `stepi` runs by CPU cycles.
+
+use std::io::Write;
+
+use anyhow::{bail, Context};
+
+use crate::{
+    architectures::create_sim,
+    eval::EvalContext,
+    framework::{next_to_retire, CpuSim, MemData, MEM_SIZE},
+    isa::{disassemble, reg_code},
+    ObjectExt,
+};
+
+const HELP: &str = "\
+step [n]             run until n instructions retire (s)
+stepi [n]            run n CPU cycles (si)
+continue             run until a breakpoint or watchpoint is hit (c)
+break <label|line|addr>
+                     stop before the instruction retires (b)
+watch <expr>         stop when the value of the expression changes
+delete [id]          delete a breakpoint or watchpoint, or all of them (d)
+print <expr>         evaluate an expression, e.g. `%rax + 8`, `*stack`, `M.valE` (p)
+x/<n><size><fmt> <addr>
+                     examine memory. size: b, h, w, g; fmt: x, d, u
+info registers       show registers (i r)
+info stages          show the instruction in each stage
+info breakpoints     show breakpoints and watchpoints
+help                 show this message
+quit                 exit (q)
+An empty line repeats the last command.";
+
+enum Point {
+    Break { addr: u64 },
+    Watch { expr: String, value: u64 },
+}
+
+/// How far to run the program
+enum Until {
+    Cycles(u64),
+    Retired(u64),
+    Stopped,
+}
+
+pub struct Repl {
+    sim: Box<dyn CpuSim>,
+    mem: MemData,
+    obj: ObjectExt,
+    /// Breakpoints and watchpoints with their ids
+    points: Vec<(usize, Point)>,
+    next_id: usize,
+    last_retired: Option<u64>,
+    last_command: String,
+    max_cpu_cycle: u64,
+}
+
+impl Repl {
+    pub fn new(arch: &str, obj: ObjectExt, max_cpu_cycle: u64) -> anyhow::Result<Self> {
+        let mem = MemData::init(obj.obj.init_mem());
+        let sim = create_sim(arch.to_string(), mem.clone(), false)?;
+        Ok(Self {
+            sim,
+            mem,
+            obj,
+            points: Vec::new(),
+            next_id: 1,
+            last_retired: None,
+            last_command: String::new(),
+            max_cpu_cycle,
+        })
+    }
+
+    /// Execute a command line. Return `false` if the session should end.
+    pub fn execute(&mut self, line: &str, out: &mut impl Write) -> anyhow::Result<bool> {
+        let line = match line.trim() {
+            "" => self.last_command.clone(),
+            line => {
+                self.last_command = line.to_string();
+                line.to_string()
+            }
+        };
+        let (cmd, arg) = line.split_once(char::is_whitespace).unwrap_or((&line, ""));
+        let arg = arg.trim();
+
+        if let Some(fmt) = cmd.strip_prefix("x/").or((cmd == "x").then_some("")) {
+            return self.examine(fmt, arg, out).map(|_| true);
+        }
+        match cmd {
+            "" => {}
+            "step" | "s" => self.run(Until::Retired(parse_count(arg)?), out)?,
+            "stepi" | "si" => self.run(Until::Cycles(parse_count(arg)?), out)?,
+            "continue" | "c" => self.run(Until::Stopped, out)?,
+            "break" | "b" => {
+                let addr = self.break_addr(arg)?;
+                let id = self.add_point(Point::Break { addr });
+                writeln!(out, "Breakpoint {id} at {}", self.describe_addr(addr))?;
+            }
+            "watch" => {
+                let value = self.eval(arg)?;
+                let id = self.add_point(Point::Watch {
+                    expr: arg.to_string(),
+                    value,
+                });
+                writeln!(out, "Watchpoint {id}: {arg} = {}", format_value(value))?;
+            }
+            "delete" | "d" => {
+                if arg.is_empty() {
+                    self.points.clear();
+                } else {
+                    let id: usize = arg.parse().context("invalid breakpoint id")?;
+                    let len = self.points.len();
+                    self.points.retain(|(i, _)| *i != id);
+                    if self.points.len() == len {
+                        bail!("no breakpoint number {id}");
+                    }
+                }
+            }
+            "print" | "p" => {
+                let value = self.eval(arg)?;
+                writeln!(out, "{arg} = {}", format_value(value))?;
+            }
+            "info" | "i" => self.info(arg, out)?,
+            "help" | "h" => writeln!(out, "{HELP}")?,
+            "quit" | "q" => return Ok(false),
+            _ => bail!("unknown command `{cmd}`, try `help`"),
+        }
+        Ok(true)
+    }
+
+    fn add_point(&mut self, point: Point) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.points.push((id, point));
+        id
+    }
+
+    fn eval(&self, expr: &str) -> anyhow::Result<u64> {
+        if expr.is_empty() {
+            bail!("expect an expression");
+        }
+        EvalContext {
+            sim: self.sim.as_ref(),
+            mem: &self.mem,
+            symbols: &self.obj.obj.symbols,
+        }
+        .eval(expr)
+    }
+
+    /// A decimal number is a line number, otherwise the argument is an
+    /// expression of the address (e.g. a label).
+    fn break_addr(&self, arg: &str) -> anyhow::Result<u64> {
+        if let Ok(line) = arg.parse::<i64>() {
+            return self
+                .obj
+                .source
+                .get_line(line.max(1))
+                .filter(|_| line > 0)
+                .and_then(|ln| ln.addr)
+                .with_context(|| format!("no instruction at line {line}"));
+        }
+        self.eval(arg)
+    }
+
+    /// Line number of the instruction at the address. Unlike
+    /// [`SourceInfo::get_line_number_by_addr`], lines with only a label are
+    /// skipped.
+    ///
+    /// [`SourceInfo::get_line_number_by_addr`]: crate::SourceInfo::get_line_number_by_addr
+    fn source_line(&self, addr: u64) -> Option<i64> {
+        let source = &self.obj.source;
+        let first = source.get_line_number_by_addr(addr)?;
+        let line = (first..)
+            .map_while(|line| Some((line, source.get_line(line)?)))
+            .take_while(|(_, ln)| ln.addr.is_none_or(|a| a == addr))
+            .find(|(_, ln)| ln.inst.is_some())
+            .map_or(first, |(line, _)| line);
+        Some(line)
+    }
+
+    fn describe_addr(&self, addr: u64) -> String {
+        let source = &self.obj.source;
+        match self.source_line(addr) {
+            Some(line) => {
+                let src = source.get_line(line).map(|ln| ln.src.trim()).unwrap_or("");
+                format!("{addr:#x}, line {line}: {src}")
+            }
+            None => {
+                let inst = disassemble(&self.mem.read()[..], addr as usize)
+                    .map(|(_, s)| s)
+                    .unwrap_or_default();
+                format!("{addr:#x}: {inst}")
+            }
+        }
+    }
+
+    /// Address of the next instruction to retire.
+    fn next_to_retire(&self) -> Option<u64> {
+        next_to_retire(self.sim.as_ref(), &self.mem, self.last_retired)
+    }
+
+    fn print_location(&self, out: &mut impl Write) -> anyhow::Result<()> {
+        let sim = &self.sim;
+        let addr = self.next_to_retire().unwrap_or(sim.program_counter());
+        writeln!(out, "{}", self.describe_addr(addr))?;
+        writeln!(
+            out,
+            "pc = {:#x}, cycle count = {}",
+            sim.program_counter(),
+            sim.cycle_count()
+        )?;
+        Ok(())
+    }
+
+    fn run(&mut self, until: Until, out: &mut impl Write) -> anyhow::Result<()> {
+        if self.sim.is_terminate() {
+            bail!("the program has terminated");
+        }
+        let mut left = match until {
+            Until::Cycles(n) | Until::Retired(n) => n,
+            Until::Stopped => u64::MAX,
+        };
+        loop {
+            self.sim.initiate_next_cycle();
+            self.sim.propagate_signals();
+            let retired = self.sim.retired_instruction();
+            if retired.is_some() {
+                self.last_retired = retired;
+            }
+
+            if let Some(msg) = self.sim.failed_assertion() {
+                writeln!(out, "{msg}")?;
+                return self.print_location(out);
+            }
+            if self.sim.is_terminate() {
+                writeln!(out, "Program terminated")?;
+                return self.print_location(out);
+            }
+            if let Some(id) = retired.and_then(|_| self.next_to_retire()).and_then(|a| {
+                self.points.iter().find_map(|(id, p)| match p {
+                    Point::Break { addr } => (*addr == a).then_some(*id),
+                    Point::Watch { .. } => None,
+                })
+            }) {
+                writeln!(out, "Breakpoint {id}")?;
+                return self.print_location(out);
+            }
+            if self.check_watchpoints(out)? {
+                return self.print_location(out);
+            }
+
+            if match until {
+                Until::Cycles(_) => true,
+                Until::Retired(_) => retired.is_some(),
+                Until::Stopped => false,
+            } {
+                left -= 1;
+                if left == 0 {
+                    return self.print_location(out);
+                }
+            }
+            if self.sim.cycle_count() >= self.max_cpu_cycle {
+                writeln!(out, "CPU max cycle reached")?;
+                return self.print_location(out);
+            }
+        }
+    }
+
+    /// Report the watchpoints whose values have changed. Return whether
+    /// there is any.
+    fn check_watchpoints(&mut self, out: &mut impl Write) -> anyhow::Result<bool> {
+        let mut hit = false;
+        for index in 0..self.points.len() {
+            let (id, Point::Watch { expr, value }) = &self.points[index] else {
+                continue;
+            };
+            // the expression may become invalid, e.g. dereferencing a
+            // register out of the memory
+            let Ok(new) = self.eval(expr) else {
+                continue;
+            };
+            if new != *value {
+                writeln!(out, "Watchpoint {id}: {expr}")?;
+                writeln!(out, "Old value = {}", format_value(*value))?;
+                writeln!(out, "New value = {}", format_value(new))?;
+                if let (_, Point::Watch { value, .. }) = &mut self.points[index] {
+                    *value = new;
+                }
+                hit = true;
+            }
+        }
+        Ok(hit)
+    }
+
+    fn info(&self, arg: &str, out: &mut impl Write) -> anyhow::Result<()> {
+        match arg {
+            "registers" | "r" => {
+                for (reg, val) in self.sim.registers().iter().enumerate() {
+                    let name = reg_code::name_of(reg as u8).to_lowercase();
+                    writeln!(out, "%{name:<4} {}", format_value(*val))?;
+                }
+            }
+            "stages" | "s" => {
+                for (stage, addr) in self.sim.stage_instructions() {
+                    match addr {
+                        Some(addr) => writeln!(out, "{stage}: {}", self.describe_addr(addr))?,
+                        None => writeln!(out, "{stage}: (bubble)")?,
+                    }
+                }
+            }
+            "breakpoints" | "b" => {
+                if self.points.is_empty() {
+                    writeln!(out, "No breakpoints or watchpoints.")?;
+                }
+                for (id, point) in &self.points {
+                    match point {
+                        Point::Break { addr } => {
+                            writeln!(out, "{id}: breakpoint at {}", self.describe_addr(*addr))?
+                        }
+                        Point::Watch { expr, value } => {
+                            writeln!(out, "{id}: watchpoint {expr} = {}", format_value(*value))?
+                        }
+                    }
+                }
+            }
+            _ => bail!("unknown info command `{arg}`, expect registers, stages or breakpoints"),
+        }
+        Ok(())
+    }
+
+    /// Examine memory like gdb's `x/8gx addr`.
+    fn examine(&self, fmt: &str, arg: &str, out: &mut impl Write) -> anyhow::Result<()> {
+        let digits = fmt.find(|c: char| !c.is_ascii_digit()).unwrap_or(fmt.len());
+        let count = match &fmt[..digits] {
+            "" => 1,
+            n => n.parse::<usize>().context("invalid count")?,
+        };
+        let (mut size, mut radix) = (8, 'x');
+        for c in fmt[digits..].chars() {
+            match c {
+                'b' => size = 1,
+                'h' => size = 2,
+                'w' => size = 4,
+                'g' => size = 8,
+                'x' | 'd' | 'u' => radix = c,
+                _ => bail!("invalid format letter `{c}`"),
+            }
+        }
+
+        let start = self.eval(arg)? as usize;
+        let end = count
+            .checked_mul(size)
+            .and_then(|len| start.checked_add(len))
+            .filter(|end| *end <= MEM_SIZE)
+            .with_context(|| format!("cannot access memory at address {start:#x}"))?;
+        let mem = self.mem.read();
+        let per_line = 16 / size;
+        for (i, chunk) in mem[start..end].chunks(size).enumerate() {
+            if i % per_line == 0 {
+                if i > 0 {
+                    writeln!(out)?;
+                }
+                write!(out, "{:#06x}:", start + i * size)?;
+            }
+            let mut bytes = [0u8; 8];
+            bytes[..size].copy_from_slice(chunk);
+            let val = u64::from_le_bytes(bytes);
+            match radix {
+                'x' => write!(out, "\t{val:#0w$x}", w = size * 2 + 2)?,
+                'd' => {
+                    // sign extend
+                    let shift = 64 - size * 8;
+                    write!(out, "\t{}", ((val << shift) as i64) >> shift)?
+                }
+                _ => write!(out, "\t{val}")?,
+            }
+        }
+        writeln!(out)?;
+        Ok(())
+    }
+}
+
+fn parse_count(arg: &str) -> anyhow::Result<u64> {
+    if arg.is_empty() {
+        return Ok(1);
+    }
+    match arg.parse() {
+        Ok(0) | Err(_) => bail!("invalid count `{arg}`"),
+        Ok(n) => Ok(n),
+    }
+}
+
+fn format_value(val: u64) -> String {
+    format!("{:#x} ({})", val, val as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Repl;
+    use crate::{assemble, AssembleOption};
+
+    #[test]
+    fn test_repl() {
+        let a = assemble(crate::asm::tests::RSUM_YS, AssembleOption::default()).unwrap();
+        let mut repl = Repl::new("pipe_std", a, 10000).unwrap();
+        let mut run = |line: &str| {
+            let mut out = Vec::new();
+            repl.execute(line, &mut out)
+                .map(|_| String::from_utf8(out).unwrap())
+        };
+
+        // line 5 is the first `call sum_list`
+        assert_eq!(
+            run("b 5").unwrap(),
+            "Breakpoint 1 at 0x14, line 5: call sum_list\n"
+        );
+        assert!(run("b 7").is_err());
+        assert!(run("break sum_list_ret")
+            .unwrap()
+            .starts_with("Breakpoint 2 at 0x5c"));
+        assert!(run("c").unwrap().starts_with("Breakpoint 1\n0x14, line 5"));
+        // the call has not retired yet
+        assert_eq!(run("p %rsp").unwrap(), "%rsp = 0x200 (512)\n");
+        assert_eq!(run("x/4bd ele1").unwrap(), "0x0060:\t10\t0\t0\t0\n");
+        assert!(run("info stages").unwrap().starts_with("F: "));
+        assert!(run("info registers").unwrap().contains("%rsp  0x200 (512)"));
+
+        assert!(run("s").unwrap().starts_with("0x1e, line 9: pushq %rbx"));
+        assert_eq!(run("p %rsp").unwrap(), "%rsp = 0x1f8 (504)\n");
+        assert_eq!(
+            run("x/2gx %rsp").unwrap(),
+            "0x01f8:\t0x000000000000001d\t0x0000000000000000\n"
+        );
+        assert!(run("s")
+            .unwrap()
+            .starts_with("0x20, line 10: irmovq $0, %rax"));
+        // an empty line repeats the last command
+        assert!(run("")
+            .unwrap()
+            .starts_with("0x2a, line 12: rrmovq %rdi, %rbx"));
+        assert!(run("stepi 3").unwrap().contains("cycle count = "));
+
+        run("delete 2").unwrap();
+        assert!(run("watch %rax")
+            .unwrap()
+            .starts_with("Watchpoint 3: %rax = "));
+        let out = run("c").unwrap();
+        assert!(out.contains("Watchpoint 3: %rax\n"));
+        run("d").unwrap();
+        assert_eq!(run("info b").unwrap(), "No breakpoints or watchpoints.\n");
+        assert!(run("c").unwrap().starts_with("Program terminated"));
+        assert_eq!(run("p %rax").unwrap(), "%rax = 0xcba (3258)\n");
+        assert!(run("s").is_err());
+        assert!(run("foo").is_err());
+        assert!(!repl.execute("q", &mut Vec::new()).unwrap());
+    }
+}
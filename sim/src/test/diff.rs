@@ -43,6 +43,7 @@ impl SimTester {
         let a = super::make_obj(src).context("assemble")?;
         let answer = crate::isa::simulate(a.obj.init_mem(), false)?;
         let (sim, sim_mem) = SimTester::simulate_arch(self.arch.clone(), src)?;
+        self.coverage.borrow_mut().merge(sim.coverage());
 
         let r = TestIsaResult {
             answer,
@@ -3,20 +3,32 @@
 
 mod diff;
 mod inst;
+mod signal;
 
-use crate::framework::{CpuSim, MemData};
+pub use signal::SignalTester;
+
+use std::cell::RefCell;
+
+use crate::{
+    coverage::Coverage,
+    framework::{CpuSim, MemData},
+};
 
 pub struct SimTester {
     arch: String,
+    /// Coverage of all programs tested, see [`SimTester::coverage`]
+    coverage: RefCell<Coverage>,
 }
 
 impl SimTester {
+    /// `arch` is either the name of a builtin architecture or the path of a
+    /// `.hcl` file.
     pub fn new(arch: &str) -> Option<Self> {
-        if crate::architectures::arch_names()
-            .iter()
-            .any(|&a| a == arch)
-        {
-            Some(Self { arch: arch.into() })
+        if crate::architectures::check_arch(arch).is_ok() {
+            Some(Self {
+                arch: arch.into(),
+                coverage: RefCell::default(),
+            })
         } else {
             None
         }
@@ -27,15 +39,25 @@ impl SimTester {
     fn simulate_arch(arch: String, src: &str) -> anyhow::Result<(Box<dyn CpuSim>, MemData)> {
         let obj = make_obj(src)?;
         let mem = MemData::init(obj.obj.init_mem());
-        let mut pipe = crate::architectures::create_sim(arch, mem.clone(), false);
+        let mut pipe = crate::architectures::create_sim(arch, mem.clone(), false)?;
         while !pipe.is_terminate() {
             pipe.step();
             if pipe.cycle_count() > 3_000_000 {
                 anyhow::bail!("exceed maximum CPU cycle limit");
             }
         }
+        if let Some(msg) = pipe.failed_assertion() {
+            anyhow::bail!(msg);
+        }
         Ok((pipe, mem))
     }
+
+    /// How often each case of each switch signal is taken over all programs
+    /// tested so far, e.g. to check whether a test suite triggers the
+    /// load/use hazard of a pipeline.
+    pub fn coverage(&self) -> Coverage {
+        self.coverage.borrow().clone()
+    }
 }
 
 fn make_obj(src: &str) -> anyhow::Result<crate::ObjectExt> {
@@ -0,0 +1,134 @@
+//! Test the signals of an architecture in isolation
+
+use anyhow::Context;
+
+use crate::{
+    architectures::create_sim,
+    framework::{CpuSim, MemData, MEM_SIZE},
+    hcl::Signal,
+};
+
+/// Test intermediate signals without running a program. Stage registers and
+/// unit outputs are set by hand, then the signals are propagated through the
+/// circuit for one cycle and checked:
+///
+/// ```
+/// use y86_sim::{isa::{inst_code::*, reg_code::RAX}, test::SignalTester};
+///
+/// # fn main() -> anyhow::Result<()> {
+/// // a load/use hazard: `OPQ` in decode reads the register loaded by `MRMOVQ`
+/// let mut t = SignalTester::new("pipe_std")?;
+/// t.set("E.icode", MRMOVQ)?.set("E.dstM", RAX)?;
+/// t.set("D.icode", OPQ)?.set("D.rA", RAX)?;
+/// t.propagate();
+/// assert_eq!(t.signal("d_srcA"), Some(RAX as u64));
+/// assert_eq!(t.signal("f_stall"), Some(1));
+/// # Ok(())
+/// # }
+/// ```
+pub struct SignalTester {
+    sim: Box<dyn CpuSim>,
+}
+
+impl SignalTester {
+    /// `arch` is either the name of a builtin architecture or the path of a
+    /// `.hcl` file. All stage registers hold their default values and the
+    /// memory is zeroed.
+    pub fn new(arch: &str) -> anyhow::Result<Self> {
+        let sim = create_sim(arch.to_string(), MemData::init([0; MEM_SIZE]), false)?;
+        Ok(Self { sim })
+    }
+
+    /// Set a stage register field at the start of the cycle (e.g. `E.icode`),
+    /// or force an output of a unit (e.g. `cond.cnd`), see
+    /// [`CpuSim::force_signal`]. Forced unit outputs keep their values in the
+    /// following cycles.
+    pub fn set(&mut self, name: &str, value: impl Signal) -> anyhow::Result<&mut Self> {
+        self.sim
+            .force_signal(name, &value.to_value())
+            .with_context(|| format!("failed to set `{name}`"))?;
+        Ok(self)
+    }
+
+    /// Propagate the signals through the circuit for one cycle. Stage
+    /// registers are not updated, so that the next state can be checked by
+    /// [`SignalTester::signal`], e.g. `d.bubble`.
+    pub fn propagate(&mut self) -> &mut Self {
+        self.sim.propagate_signals();
+        self
+    }
+
+    /// Get the value of a signal, see [`CpuSim::signal`].
+    pub fn signal(&self, name: &str) -> Option<u64> {
+        self.sim.signal(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SignalTester;
+    use crate::isa::{inst_code::*, reg_code::*, Stat};
+
+    fn archs() -> [String; 2] {
+        [
+            "pipe_std".to_string(),
+            format!(
+                "{}/src/architectures/hcl/pipe_std.hcl",
+                env!("CARGO_MANIFEST_DIR")
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_load_use_hazard() -> anyhow::Result<()> {
+        for arch in archs() {
+            let mut t = SignalTester::new(&arch)?;
+            t.set("E.icode", MRMOVQ)?
+                .set("E.dstM", RBX)?
+                .set("D.icode", OPQ)?
+                .set("D.rA", RAX)?
+                .set("D.rB", RBX)?;
+            t.propagate();
+            assert_eq!(t.signal("d_srcB"), Some(RBX as u64), "{arch}");
+            for (name, expected) in [("f_stall", 1), ("d_stall", 1), ("e_bubble", 1)] {
+                assert_eq!(t.signal(name), Some(expected), "{name} in {arch}");
+            }
+            assert_eq!(t.signal("d.stall"), Some(1), "{arch}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_mispredicted_branch() -> anyhow::Result<()> {
+        for arch in archs() {
+            let mut t = SignalTester::new(&arch)?;
+            t.set("E.icode", JX)?.set("cond.cnd", false)?;
+            t.propagate();
+            assert_eq!(t.signal("d_bubble"), Some(1), "{arch}");
+            assert_eq!(t.signal("e_bubble"), Some(1), "{arch}");
+            // the same instruction is taken once the condition holds
+            t.set("cond.cnd", true)?;
+            t.propagate();
+            assert_eq!(t.signal("d_bubble"), Some(0), "{arch}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_errors() -> anyhow::Result<()> {
+        for arch in archs() {
+            let mut t = SignalTester::new(&arch)?;
+            t.set("W.stat", Stat::Adr)?;
+            t.propagate();
+            assert_eq!(t.signal("w_stall"), Some(1), "{arch}");
+            let mut err = |name: &str| format!("{:#}", t.set(name, 0u64).err().unwrap());
+            assert!(err("E.foo").contains("failed to set `E.foo`"), "{arch}");
+            assert!(
+                err("f_stall").contains("neither a stage field nor a unit output"),
+                "{arch}"
+            );
+            assert!(err("nope.x").contains("neither a stage field"), "{arch}");
+        }
+        Ok(())
+    }
+}
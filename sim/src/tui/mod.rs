@@ -0,0 +1,404 @@
+//! Full-screen visualizer of the pipeline, which is started by `ysim --tui`.
+//!
+//! The screen shows the datapath with the active tunnels lit up, along with
+//! the instruction in each stage, the register file, the condition codes, the
+//! source code and the memory. Stepping backward re-runs the simulation from
+//! the beginning, since the simulator is deterministic.
+
+use std::collections::HashSet;
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Paragraph},
+    Frame,
+};
+
+use crate::{
+    architectures::create_sim,
+    datapath::Datapath,
+    framework::{CpuSim, MemData, MEM_SIZE},
+    isa::{disassemble, reg_code},
+    utils::get_u64,
+    ObjectExt,
+};
+
+const KEY_HELP: &str =
+    " →/l: step  ←/h: step back  g: run to end  r: restart  j/k: scroll memory  s: stack  q: quit ";
+
+/// Number of 8-byte words shown in the memory pane
+const MEM_ROWS: usize = 12;
+
+pub struct App {
+    arch: String,
+    obj: ObjectExt,
+    max_cpu_cycle: u64,
+    sim: Box<dyn CpuSim>,
+    mem: MemData,
+    datapath: Option<Datapath>,
+    /// Address of the first word in the memory pane
+    mem_offset: usize,
+    message: String,
+}
+
+impl App {
+    pub fn new(arch: &str, obj: ObjectExt, max_cpu_cycle: u64) -> anyhow::Result<Self> {
+        let mem = MemData::init(obj.obj.init_mem());
+        let sim = create_sim(arch.to_string(), mem.clone(), false)?;
+        Ok(Self {
+            arch: arch.to_string(),
+            datapath: sim.datapath(),
+            sim,
+            mem,
+            obj,
+            max_cpu_cycle,
+            mem_offset: 0,
+            message: String::new(),
+        })
+    }
+
+    pub fn cycle_count(&self) -> u64 {
+        self.sim.cycle_count()
+    }
+
+    /// Run the next cycle.
+    pub fn step(&mut self) {
+        if self.sim.is_terminate() {
+            self.message = "the program has terminated".to_string();
+        } else if self.sim.cycle_count() >= self.max_cpu_cycle {
+            self.message = "CPU max cycle reached".to_string();
+        } else {
+            self.sim.initiate_next_cycle();
+            self.sim.propagate_signals();
+            self.message.clear();
+            if let Some(msg) = self.sim.failed_assertion() {
+                // the signal values are left out of the title bar
+                self.message = msg.lines().next().unwrap_or_default().to_string();
+            }
+        }
+    }
+
+    /// Go back to the state after the given number of cycles by running the
+    /// simulation from the beginning.
+    pub fn goto(&mut self, cycle: u64) {
+        let mem = MemData::init(self.obj.obj.init_mem());
+        // a `.hcl` file may have changed since it was loaded
+        match create_sim(self.arch.clone(), mem.clone(), false) {
+            Ok(sim) => (self.sim, self.mem) = (sim, mem),
+            Err(e) => {
+                self.message = format!("{:#}", e);
+                return;
+            }
+        }
+        while self.sim.cycle_count() < cycle && !self.sim.is_terminate() {
+            self.step();
+        }
+        self.message.clear();
+    }
+
+    pub fn step_back(&mut self) {
+        match self.sim.cycle_count() {
+            0 => self.message = "already at the beginning".to_string(),
+            n => self.goto(n - 1),
+        }
+    }
+
+    pub fn run_to_end(&mut self) {
+        while !self.sim.is_terminate() && self.sim.cycle_count() < self.max_cpu_cycle {
+            self.step();
+        }
+    }
+
+    /// Handle a key. Return `false` to quit.
+    pub fn handle_key(&mut self, key: KeyCode) -> bool {
+        match key {
+            KeyCode::Right | KeyCode::Char('l') | KeyCode::Char(' ') => self.step(),
+            KeyCode::Left | KeyCode::Char('h') => self.step_back(),
+            KeyCode::Char('g') => self.run_to_end(),
+            KeyCode::Char('r') => self.goto(0),
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.mem_offset = (self.mem_offset + 8).min(MEM_SIZE - MEM_ROWS * 8)
+            }
+            KeyCode::Char('k') | KeyCode::Up => self.mem_offset = self.mem_offset.saturating_sub(8),
+            KeyCode::PageDown => {
+                self.mem_offset = (self.mem_offset + MEM_ROWS * 8).min(MEM_SIZE - MEM_ROWS * 8)
+            }
+            KeyCode::PageUp => self.mem_offset = self.mem_offset.saturating_sub(MEM_ROWS * 8),
+            KeyCode::Char('s') => {
+                let rsp = self.sim.registers()[reg_code::RSP as usize] as usize;
+                self.mem_offset = (rsp & !7).min(MEM_SIZE - MEM_ROWS * 8);
+            }
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            _ => {}
+        }
+        true
+    }
+
+    pub fn draw(&self, frame: &mut Frame) {
+        let [title, body, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let status = if self.sim.is_terminate() {
+            " (terminated)"
+        } else {
+            ""
+        };
+        frame.render_widget(
+            Line::from(format!(
+                " ysim --tui | {} | cycle {}{} | pc = {:#x} {}",
+                self.arch,
+                self.sim.cycle_count(),
+                status,
+                self.sim.program_counter(),
+                self.message,
+            ))
+            .style(Style::new().add_modifier(Modifier::REVERSED)),
+            title,
+        );
+        frame.render_widget(Line::from(KEY_HELP), help);
+
+        let [left, right] =
+            Layout::horizontal([Constraint::Length(64), Constraint::Min(0)]).areas(body);
+        self.draw_datapath(frame, left);
+
+        let [top, bottom] =
+            Layout::vertical([Constraint::Length(11), Constraint::Min(0)]).areas(right);
+        let [stages, regs] =
+            Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)]).areas(top);
+        let [source, memory] =
+            Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)])
+                .areas(bottom);
+        self.draw_stages(frame, stages);
+        self.draw_registers(frame, regs);
+        self.draw_source(frame, source);
+        self.draw_memory(frame, memory);
+    }
+
+    fn draw_datapath(&self, frame: &mut Frame, area: Rect) {
+        let tunnels = self.sim.active_tunnels();
+        let block = Block::bordered().title(format!(" Datapath: {} ", tunnels.join(" ")));
+        let Some(datapath) = &self.datapath else {
+            let text = format!("no datapath drawing for {}", self.arch);
+            frame.render_widget(Paragraph::new(text).block(block), area);
+            return;
+        };
+        let lit = datapath.wire_cells(&tunnels);
+        let lit_style = Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+        let lines: Vec<Line> = datapath
+            .grid()
+            .into_iter()
+            .enumerate()
+            .map(|(row, chars)| {
+                chars
+                    .into_iter()
+                    .enumerate()
+                    .map(|(col, c)| {
+                        if lit.contains(&(row, col)) {
+                            Span::styled(c.to_string(), lit_style)
+                        } else {
+                            Span::raw(c.to_string())
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    /// Describe the instruction at the address by its source line.
+    fn describe_inst(&self, addr: u64) -> String {
+        self.source_line(addr)
+            .and_then(|line| self.obj.source.get_line(line))
+            .map(|ln| ln.src.trim().to_string())
+            .or_else(|| disassemble(&self.mem.read()[..], addr as usize).map(|(_, s)| s))
+            .unwrap_or_default()
+    }
+
+    /// Line number of the instruction at the address
+    fn source_line(&self, addr: u64) -> Option<i64> {
+        (1..)
+            .map_while(|line| Some((line, self.obj.source.get_line(line)?)))
+            .find(|(_, ln)| ln.addr == Some(addr) && ln.inst.is_some())
+            .map(|(line, _)| line)
+    }
+
+    fn draw_stages(&self, frame: &mut Frame, area: Rect) {
+        let lines: Vec<Line> = self
+            .sim
+            .stage_instructions()
+            .into_iter()
+            .map(|(stage, addr)| match addr {
+                Some(addr) => Line::from(vec![
+                    Span::styled(format!("{stage:>2} "), Style::new().bold()),
+                    Span::raw(format!("{addr:#06x} {}", self.describe_inst(addr))),
+                ]),
+                None => Line::from(vec![
+                    Span::styled(format!("{stage:>2} "), Style::new().bold()),
+                    Span::styled("(bubble)", Style::new().dim()),
+                ]),
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" Stages ")),
+            area,
+        );
+    }
+
+    fn draw_registers(&self, frame: &mut Frame, area: Rect) {
+        let regs = self.sim.registers();
+        // the last one is RNONE
+        let mut lines: Vec<Line> = regs[..regs.len() - 1]
+            .chunks(2)
+            .enumerate()
+            .map(|(i, pair)| {
+                let spans = pair.iter().enumerate().flat_map(|(j, val)| {
+                    let name = reg_code::name_of((i * 2 + j) as u8).to_lowercase();
+                    [
+                        Span::styled(format!("%{name:<4}"), Style::new().bold()),
+                        Span::raw(format!("{val:<8x} ")),
+                    ]
+                });
+                Line::from(spans.collect::<Vec<_>>())
+            })
+            .collect();
+        let cc = self.sim.condition_code();
+        let cc = format!("sf {}, of {}, zf {}", cc.sf, cc.of, cc.zf);
+        lines.push(Line::from(vec![
+            Span::styled("cc ", Style::new().bold()),
+            Span::raw(cc),
+        ]));
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" Registers ")),
+            area,
+        );
+    }
+
+    fn draw_source(&self, frame: &mut Frame, area: Rect) {
+        let stages = self.sim.stage_instructions();
+        // center the view at the instruction being fetched
+        let center = stages
+            .first()
+            .and_then(|(_, addr)| self.source_line((*addr)?))
+            .unwrap_or(1);
+        let height = area.height.saturating_sub(2) as i64;
+        let first = (center - height / 2).max(1);
+        let lines: Vec<Line> = (first..first + height)
+            .map_while(|line| Some((line, self.obj.source.get_line(line)?)))
+            .map(|(line, ln)| {
+                let marks: Vec<_> = stages
+                    .iter()
+                    .filter(|(_, addr)| addr.is_some() && ln.inst.is_some() && *addr == ln.addr)
+                    .map(|(stage, _)| *stage)
+                    .collect();
+                let style = if marks.is_empty() {
+                    Style::new()
+                } else {
+                    Style::new().fg(Color::Cyan)
+                };
+                Line::from(vec![
+                    Span::styled(format!("{:>6} ", marks.join("")), Style::new().bold()),
+                    Span::styled(format!("{line:>3} "), Style::new().dim()),
+                    Span::styled(ln.src.clone(), style),
+                ])
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" Source ")),
+            area,
+        );
+    }
+
+    fn draw_memory(&self, frame: &mut Frame, area: Rect) {
+        let init = self.obj.obj.init_mem();
+        let mem = self.mem.read();
+        let rsp = self.sim.registers()[reg_code::RSP as usize] as usize;
+        let changed: HashSet<usize> = (self.mem_offset..self.mem_offset + MEM_ROWS * 8)
+            .step_by(8)
+            .filter(|addr| mem[*addr..*addr + 8] != init[*addr..*addr + 8])
+            .collect();
+        let lines: Vec<Line> = (self.mem_offset..self.mem_offset + MEM_ROWS * 8)
+            .step_by(8)
+            .map(|addr| {
+                let style = if changed.contains(&addr) {
+                    Style::new().fg(Color::Yellow)
+                } else {
+                    Style::new()
+                };
+                Line::from(vec![
+                    Span::styled(format!("{addr:#06x} "), Style::new().dim()),
+                    Span::styled(format!("{:016x}", get_u64(&mem[addr..addr + 8])), style),
+                    Span::raw(if addr == rsp { " <- %rsp" } else { "" }),
+                ])
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" Memory ")),
+            area,
+        );
+    }
+}
+
+/// Start the visualizer in the terminal.
+pub fn run(arch: &str, obj: ObjectExt, max_cpu_cycle: u64) -> anyhow::Result<()> {
+    let mut app = App::new(arch, obj, max_cpu_cycle)?;
+    let mut terminal = ratatui::init();
+    let result = (|| loop {
+        terminal.draw(|frame| app.draw(frame))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !app.handle_key(key.code) {
+                return Ok(());
+            }
+        }
+    })();
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{backend::TestBackend, Terminal};
+
+    use super::App;
+    use crate::{assemble, AssembleOption};
+
+    fn screen(app: &App) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(140, 40)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content()
+            .chunks(buffer.area.width as usize)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>() + "\n")
+            .collect()
+    }
+
+    #[test]
+    fn test_tui() {
+        let a = assemble(crate::asm::tests::RSUM_YS, AssembleOption::default()).unwrap();
+        let mut app = App::new("pipe_std", a, 10000).unwrap();
+        for _ in 0..6 {
+            app.step();
+        }
+        let forward = screen(&app);
+        assert!(forward.contains("cycle 6"));
+        assert!(forward.contains("Predict"));
+        assert!(forward.contains("call sum_list"));
+
+        app.step();
+        app.step_back();
+        assert_eq!(app.cycle_count(), 6);
+        assert_eq!(screen(&app), forward);
+
+        app.run_to_end();
+        let end = screen(&app);
+        assert!(end.contains("(terminated)"));
+        // %rax = 0xcba
+        assert!(end.contains("cba"));
+    }
+}
@@ -104,6 +104,59 @@ pub fn format_icode(icode: u8) -> String {
     }
 }
 
+/// Format the status of the pipeline stages as a table, with a column for
+/// each stage and a row for each of the `fields`. `value(i, field)` gives the
+/// field of the `i`-th stage, or `None` if the stage has no such field, and a
+/// row is omitted if no stage has the field. `stat` and `icode` are printed by
+/// name, other fields in hexadecimal. If there are several stages, the last
+/// row shows the control given by `ctrl(i)`, i.e. `(bubble, stall)`.
+pub fn format_stage_status(
+    stages: &[&str],
+    fields: &[&str],
+    value: impl Fn(usize, &str) -> Option<u64>,
+    ctrl: impl Fn(usize) -> (bool, bool),
+) -> String {
+    let mut rows = Vec::new();
+    for field in fields {
+        let cells: Vec<_> = (0..stages.len()).map(|i| value(i, field)).collect();
+        if cells.iter().all(Option::is_none) {
+            continue;
+        }
+        let cells = cells.into_iter().map(|val| match (*field, val) {
+            (_, None) => " ".repeat(6),
+            ("stat", Some(val)) => match crate::isa::Stat::from_code(val) {
+                Some(stat) => format!("{stat}   "),
+                None => format!("{val:<6}"),
+            },
+            ("icode", Some(val)) => format_icode(val as u8),
+            (_, Some(val)) => format!("{:<6}", format!("{val:#x}")),
+        });
+        rows.push((field.to_string(), cells.collect::<Vec<_>>()));
+    }
+    if stages.len() > 1 {
+        let cells = (0..stages.len())
+            .map(|i| {
+                let (bubble, stall) = ctrl(i);
+                format_ctrl(bubble, stall)
+            })
+            .collect();
+        rows.push(("Control".to_string(), cells));
+    }
+    let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    rows.iter()
+        .map(|(name, cells)| {
+            let cells = stages
+                .iter()
+                .zip(cells)
+                .map(|(stage, cell)| format!("{stage} {cell}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!("{name:width$} {}", cells.trim_end())
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 pub fn format_reg_file(reg_file: RegFile) -> String {
     fn format_reg_val(val: u64) -> String {
         if val == 0 {
@@ -198,3 +251,37 @@ pub fn render_arch_dependency_graph(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stage_status() {
+        let stages = ["D", "E"];
+        let status = |fields: &[&str]| {
+            format_stage_status(
+                &stages,
+                fields,
+                |i, field| match (i, field) {
+                    (0, "valC") => Some(0x10),
+                    (1, "valE") => Some(0x20),
+                    _ => None,
+                },
+                |i| (i == 0, false),
+            )
+        };
+        let ctrl = format!(
+            "Control D {} E {}",
+            format_ctrl(true, false),
+            format_ctrl(false, false)
+        );
+        assert_eq!(
+            status(&["valC", "foo", "valE"]),
+            format!("valC    D 0x10   E\nvalE    D        E 0x20\n{ctrl}")
+        );
+        assert_eq!(status(&[]), ctrl);
+        let single = format_stage_status(&["S"], &["pc"], |_, _| None, |_| (false, false));
+        assert_eq!(single, "");
+    }
+}
@@ -1,4 +1,23 @@
 //! Expression in CS:APP HCL language
+//!
+//! Operators from the highest precedence to the lowest (as in C):
+//!
+//! | operators          | description                         |
+//! |--------------------|-------------------------------------|
+//! | `!` `~` `-`        | logical not, bitwise not, negation  |
+//! | `*`                | multiplication                      |
+//! | `+` `-`            | addition, subtraction               |
+//! | `<<` `>>`          | shift                               |
+//! | `<` `<=` `>` `>=`  | ordering                            |
+//! | `==` `!=` `in {..}`| equality, set membership            |
+//! | `&`                | bitwise and                         |
+//! | `^`                | bitwise xor                         |
+//! | `\|`               | bitwise or                          |
+//! | `&&`               | logical and                         |
+//! | `\|\|`             | logical or                          |
+//!
+//! Binary operators are left associative. Arithmetic wraps around on overflow
+//! like the hardware does.
 
 use quote::ToTokens;
 use syn::{parse::Parse, punctuated::Punctuated, Token};
@@ -15,22 +34,142 @@ impl Parse for LValue {
     }
 }
 
+impl std::fmt::Display for LValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<_> = self.0.iter().map(|id| id.to_string()).collect();
+        write!(f, "{}", names.join("."))
+    }
+}
+
 impl ToTokens for LValue {
     fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
         self.0.to_tokens(tokens)
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    /// `!`
+    Not,
+    /// `~`
+    BitNot,
+    /// `-`
+    Neg,
+}
+
+impl UnaryOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Not => "!",
+            Self::BitNot => "~",
+            Self::Neg => "-",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Mul,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    NotEq,
+    BitAnd,
+    BitXor,
+    BitOr,
+    And,
+    Or,
+}
+
+impl BinaryOp {
+    /// Parse the operator if the input starts with one. Longer operators are
+    /// checked first since `peek(Token![<])` also matches `<<` and `<=`.
+    fn parse_if_any(input: syn::parse::ParseStream) -> syn::Result<Option<Self>> {
+        macro_rules! ops {
+            ($($tok:tt => $op:ident),*) => {
+                $(if input.peek(Token![$tok]) {
+                    input.parse::<Token![$tok]>()?;
+                    return Ok(Some(Self::$op));
+                })*
+            };
+        }
+        // `->` ends the expression in a signal definition
+        if input.peek(Token![->]) {
+            return Ok(None);
+        }
+        ops! {
+            && => And, || => Or, << => Shl, >> => Shr, <= => Le, >= => Ge,
+            == => Eq, != => NotEq, * => Mul, + => Add, - => Sub, < => Lt,
+            > => Gt, & => BitAnd, ^ => BitXor, | => BitOr
+        }
+        Ok(None)
+    }
+
+    /// Peek the operator without consuming it.
+    fn peek(input: syn::parse::ParseStream) -> Option<Self> {
+        Self::parse_if_any(&input.fork()).ok().flatten()
+    }
+
+    fn precedence(self) -> u8 {
+        match self {
+            Self::Or => 1,
+            Self::And => 2,
+            Self::BitOr => 3,
+            Self::BitXor => 4,
+            Self::BitAnd => 5,
+            Self::Eq | Self::NotEq => 6,
+            Self::Lt | Self::Le | Self::Gt | Self::Ge => 7,
+            Self::Shl | Self::Shr => 8,
+            Self::Add | Self::Sub => 9,
+            Self::Mul => 10,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Mul => "*",
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Shl => "<<",
+            Self::Shr => ">>",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::Eq => "==",
+            Self::NotEq => "!=",
+            Self::BitAnd => "&",
+            Self::BitXor => "^",
+            Self::BitOr => "|",
+            Self::And => "&&",
+            Self::Or => "||",
+        }
+    }
+}
+
+/// Precedence of `in {..}`, the same as `==`.
+const IN_PRECEDENCE: u8 = 6;
+
 #[derive(Debug, Clone)]
-pub enum PrimaryExpr {
+pub enum Expr {
     LVal(LValue),
     LitInt(syn::LitInt),
     LitBool(syn::LitBool),
     Paren(Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+    /// `expr in { a, b, c }`
+    In(Box<Expr>, Punctuated<LValue, Token![,]>),
 }
 
-impl Parse for PrimaryExpr {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+impl Expr {
+    fn parse_primary(input: syn::parse::ParseStream) -> syn::Result<Self> {
         let lookahead = input.lookahead1();
         if lookahead.peek(syn::LitInt) {
             Ok(Self::LitInt(input.parse()?))
@@ -45,150 +184,112 @@ impl Parse for PrimaryExpr {
             Ok(Self::LVal(input.parse()?))
         }
     }
-}
 
-#[derive(Debug, Clone)]
-pub enum UnaryExpr {
-    Primary(PrimaryExpr),
-    Not(Box<UnaryExpr>),
-}
-
-impl Parse for UnaryExpr {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let lookahead = input.lookahead1();
-        if lookahead.peek(Token![!]) {
-            let _ = input.parse::<Token![!]>()?;
-            let expr = input.parse()?;
-            Ok(Self::Not(Box::new(expr)))
+    fn parse_unary(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let op = if input.peek(Token![!]) {
+            input.parse::<Token![!]>()?;
+            UnaryOp::Not
+        } else if input.peek(Token![~]) {
+            input.parse::<Token![~]>()?;
+            UnaryOp::BitNot
+        } else if input.peek(Token![-]) {
+            input.parse::<Token![-]>()?;
+            UnaryOp::Neg
         } else {
-            Ok(Self::Primary(input.parse()?))
-        }
+            return Self::parse_primary(input);
+        };
+        let expr = Self::parse_unary(input)?;
+        Ok(Self::Unary(op, Box::new(expr)))
     }
-}
-
-#[derive(Debug, Clone)]
-pub enum RelExpr {
-    Unary(UnaryExpr),
-    Eq(UnaryExpr, UnaryExpr),
-    NotEq(UnaryExpr, UnaryExpr),
-    In(UnaryExpr, Punctuated<LValue, Token![,]>),
-}
 
-impl Parse for RelExpr {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let lhs = input.parse::<UnaryExpr>()?;
-        let lookahead = input.lookahead1();
-        if lookahead.peek(Token![==]) {
-            let _ = input.parse::<Token![==]>()?;
-            let rhs = input.parse()?;
-            Ok(Self::Eq(lhs, rhs))
-        } else if lookahead.peek(Token![!=]) {
-            let _ = input.parse::<Token![!=]>()?;
-            let rhs = input.parse()?;
-            Ok(Self::NotEq(lhs, rhs))
-        } else if lookahead.peek(Token![in]) {
-            let _ = input.parse::<Token![in]>()?;
-            let items;
-            let _ = syn::braced!(items in input);
-            let values = items.parse_terminated(LValue::parse, Token![,])?;
-            Ok(Self::In(lhs, values))
-        } else {
-            Ok(Self::Unary(lhs))
-        }
-    }
-}
-
-#[derive(Debug, Clone)]
-pub enum LAndExpr {
-    Rel(RelExpr),
-
-    /// `expr && expr`
-    RelLAnd(RelExpr, Box<LAndExpr>),
-}
-
-impl Parse for LAndExpr {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let lhs = input.parse::<RelExpr>()?;
-        let lookahead = input.lookahead1();
-        if lookahead.peek(Token![&&]) {
-            let _ = input.parse::<Token![&&]>()?;
-            let rhs = input.parse()?;
-            Ok(Self::RelLAnd(lhs, Box::new(rhs)))
-        } else {
-            Ok(Self::Rel(lhs))
-        }
-    }
-}
-
-#[derive(Debug, Clone)]
-pub enum LOrExpr {
-    LAnd(LAndExpr),
-
-    /// `expr || expr`
-    LAndOr(LAndExpr, Box<LOrExpr>),
-}
-
-impl Parse for LOrExpr {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let lhs = input.parse::<LAndExpr>()?;
-        let lookahead = input.lookahead1();
-        if lookahead.peek(Token![||]) {
-            let _ = input.parse::<Token![||]>()?;
-            let rhs = input.parse()?;
-            Ok(Self::LAndOr(lhs, Box::new(rhs)))
-        } else {
-            Ok(Self::LAnd(lhs))
+    /// Precedence climbing: parse an expression whose binary operators have
+    /// a precedence of at least `min_prec`.
+    fn parse_binary(input: syn::parse::ParseStream, min_prec: u8) -> syn::Result<Self> {
+        let mut lhs = Self::parse_unary(input)?;
+        loop {
+            if min_prec <= IN_PRECEDENCE && input.peek(Token![in]) {
+                let _ = input.parse::<Token![in]>()?;
+                let items;
+                let _ = syn::braced!(items in input);
+                let values = items.parse_terminated(LValue::parse, Token![,])?;
+                lhs = Self::In(Box::new(lhs), values);
+                continue;
+            }
+            match BinaryOp::peek(input) {
+                Some(op) if op.precedence() >= min_prec => {
+                    BinaryOp::parse_if_any(input)?;
+                    let rhs = Self::parse_binary(input, op.precedence() + 1)?;
+                    lhs = Self::Binary(Box::new(lhs), op, Box::new(rhs));
+                }
+                _ => return Ok(lhs),
+            }
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Expr(LOrExpr);
-
 impl Parse for Expr {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let expr = input.parse::<LOrExpr>()?;
-        Ok(Self(expr))
-    }
-}
-
-impl ToTokens for Expr {
-    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
-        self.0.to_tokens(tokens)
-    }
-}
-
-impl ToTokens for LOrExpr {
-    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
-        match self {
-            Self::LAnd(expr) => expr.to_tokens(tokens),
-            Self::LAndOr(expr, next) => {
-                tokens.extend(quote::quote! { #expr || #next });
-            }
-        }
+        Self::parse_binary(input, 0)
     }
 }
 
-impl ToTokens for LAndExpr {
-    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Rel(expr) => expr.to_tokens(tokens),
-            Self::RelLAnd(expr, next) => {
-                tokens.extend(quote::quote! { #expr && #next });
+            Self::LVal(lval) => write!(f, "{lval}"),
+            Self::LitInt(lit) => write!(f, "{}", lit),
+            Self::LitBool(lit) => write!(f, "{}", lit.value),
+            Self::Paren(expr) => write!(f, "({expr})"),
+            Self::Unary(op, expr) => write!(f, "{}{expr}", op.symbol()),
+            Self::Binary(lhs, op, rhs) => write!(f, "{lhs} {} {rhs}", op.symbol()),
+            Self::In(lhs, values) => {
+                let values: Vec<_> = values.iter().map(LValue::to_string).collect();
+                write!(f, "{lhs} in {{ {} }}", values.join(", "))
             }
         }
     }
 }
 
-impl ToTokens for RelExpr {
+impl ToTokens for Expr {
     fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
         match self {
-            Self::Unary(expr) => expr.to_tokens(tokens),
-            Self::Eq(lhs, rhs) => {
-                tokens.extend(quote::quote! { #lhs == #rhs });
+            Self::LVal(lval) => lval.to_tokens(tokens),
+            Self::LitInt(lit) => lit.to_tokens(tokens),
+            Self::LitBool(lit) => lit.to_tokens(tokens),
+            Self::Paren(expr) => {
+                tokens.extend(quote::quote! { ( #expr ) });
             }
-            Self::NotEq(lhs, rhs) => {
-                tokens.extend(quote::quote! { #lhs != #rhs });
+            Self::Unary(op, expr) => tokens.extend(match op {
+                UnaryOp::Not => quote::quote! { crate::dsl::not(#expr) },
+                UnaryOp::BitNot => quote::quote! { !#expr },
+                UnaryOp::Neg => quote::quote! { crate::dsl::neg(#expr) },
+            }),
+            Self::Binary(lhs, op, rhs) => {
+                // operands are parenthesized since Rust has different precedence
+                // for bitwise operators
+                let paren = |e: &Expr| match e {
+                    Self::Binary(..) => quote::quote! { (#e) },
+                    _ => e.to_token_stream(),
+                };
+                let (lhs, rhs) = (paren(lhs), paren(rhs));
+                tokens.extend(match op {
+                    BinaryOp::Mul => quote::quote! { crate::dsl::mul(#lhs, #rhs) },
+                    BinaryOp::Add => quote::quote! { crate::dsl::add(#lhs, #rhs) },
+                    BinaryOp::Sub => quote::quote! { crate::dsl::sub(#lhs, #rhs) },
+                    BinaryOp::Shl => quote::quote! { crate::dsl::shl(#lhs, #rhs) },
+                    BinaryOp::Shr => quote::quote! { crate::dsl::shr(#lhs, #rhs) },
+                    BinaryOp::Lt => quote::quote! { crate::dsl::lt(#lhs, #rhs) },
+                    BinaryOp::Le => quote::quote! { crate::dsl::le(#lhs, #rhs) },
+                    BinaryOp::Gt => quote::quote! { crate::dsl::gt(#lhs, #rhs) },
+                    BinaryOp::Ge => quote::quote! { crate::dsl::ge(#lhs, #rhs) },
+                    BinaryOp::Eq => quote::quote! { #lhs == #rhs },
+                    BinaryOp::NotEq => quote::quote! { #lhs != #rhs },
+                    BinaryOp::BitAnd => quote::quote! { #lhs & #rhs },
+                    BinaryOp::BitXor => quote::quote! { #lhs ^ #rhs },
+                    BinaryOp::BitOr => quote::quote! { #lhs | #rhs },
+                    BinaryOp::And => quote::quote! { #lhs && #rhs },
+                    BinaryOp::Or => quote::quote! { #lhs || #rhs },
+                });
             }
             Self::In(lhs, values) => {
                 tokens.extend(quote::quote! { crate::dsl::mtc(#lhs, [#values]) });
@@ -197,98 +298,155 @@ impl ToTokens for RelExpr {
     }
 }
 
-impl ToTokens for UnaryExpr {
-    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
-        match self {
-            Self::Primary(expr) => expr.to_tokens(tokens),
-            Self::Not(expr) => {
-                tokens.extend(quote::quote! { !#expr });
+/// Type of an expression, as far as the macro knows. The types of unit
+/// signals and stage fields are not known to the macro, which are checked by
+/// the Rust compiler after expansion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ty {
+    Bool,
+    Int,
+    Unknown,
+}
+
+impl Ty {
+    pub fn of(typ: &syn::Type) -> Self {
+        let syn::Type::Path(path) = typ else {
+            return Self::Unknown;
+        };
+        match path.path.get_ident().map(|id| id.to_string()).as_deref() {
+            Some("bool") => Self::Bool,
+            Some("u8" | "u16" | "u32" | "u64" | "usize" | "i8" | "i16" | "i32" | "i64") => {
+                Self::Int
             }
+            _ => Self::Unknown,
         }
     }
-}
 
-impl ToTokens for PrimaryExpr {
-    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
-        match self {
-            Self::LVal(lval) => lval.to_tokens(tokens),
-            Self::LitInt(lit) => lit.to_tokens(tokens),
-            Self::LitBool(lit) => lit.to_tokens(tokens),
-            Self::Paren(expr) => {
-                tokens.extend(quote::quote! { ( #expr ) });
-            }
-        }
+    /// Whether a value of this type can be used as `other`
+    pub fn fits(self, other: Ty) -> bool {
+        self == other || self == Self::Unknown || other == Self::Unknown
     }
-}
 
-impl PrimaryExpr {
-    fn lvalues(&self) -> Punctuated<LValue, Token![,]> {
+    pub fn name(self) -> &'static str {
         match self {
-            PrimaryExpr::LVal(v) => std::iter::once(v.clone()).collect(),
-            PrimaryExpr::LitInt(_) => Default::default(),
-            PrimaryExpr::LitBool(_) => Default::default(),
-            PrimaryExpr::Paren(e) => e.lvalues(),
+            Self::Bool => "bool",
+            Self::Int => "integer",
+            Self::Unknown => "unknown",
         }
     }
 }
 
-impl UnaryExpr {
-    fn lvalues(&self) -> Punctuated<LValue, Token![,]> {
+impl Expr {
+    /// Span of the leftmost token of the expression, where errors about it
+    /// are reported.
+    pub fn span(&self) -> proc_macro2::Span {
         match self {
-            UnaryExpr::Primary(p) => p.lvalues(),
-            UnaryExpr::Not(e) => e.lvalues(),
+            Self::LVal(lval) => lval.0[0].span(),
+            Self::LitInt(lit) => lit.span(),
+            Self::LitBool(lit) => lit.span,
+            Self::Paren(expr) | Self::Unary(_, expr) => expr.span(),
+            Self::Binary(lhs, _, _) | Self::In(lhs, _) => lhs.span(),
         }
     }
-}
 
-impl RelExpr {
-    fn lvalues(&self) -> Punctuated<LValue, Token![,]> {
-        match self {
-            RelExpr::Unary(u) => u.lvalues(),
-            RelExpr::Eq(lhs, rhs) | RelExpr::NotEq(lhs, rhs) => {
-                let mut lvalues = lhs.lvalues();
-                lvalues.extend(rhs.lvalues());
-                lvalues
+    /// Infer the type of the expression, where `env` gives the types of
+    /// lvalues. Return an error at the operand that does not fit the operator.
+    pub fn ty(&self, env: &impl Fn(&LValue) -> Ty) -> syn::Result<Ty> {
+        let expect = |e: &Expr, ty: Ty, op: &str| -> syn::Result<()> {
+            let t = e.ty(env)?;
+            if t.fits(ty) {
+                Ok(())
+            } else {
+                Err(syn::Error::new(
+                    e.span(),
+                    format!(
+                        "`{op}` expects {} operands, but `{e}` is {}",
+                        ty.name(),
+                        t.name()
+                    ),
+                ))
             }
-            RelExpr::In(lhs, values) => {
-                let mut lvalues = lhs.lvalues();
-                lvalues.extend(values.iter().cloned());
-                lvalues
+        };
+        Ok(match self {
+            Self::LVal(lval) => env(lval),
+            Self::LitInt(_) => Ty::Int,
+            Self::LitBool(_) => Ty::Bool,
+            Self::Paren(expr) => expr.ty(env)?,
+            Self::Unary(UnaryOp::Not, expr) => {
+                expect(expr, Ty::Bool, "!")?;
+                Ty::Bool
             }
-        }
+            Self::Unary(op, expr) => {
+                expect(expr, Ty::Int, op.symbol())?;
+                Ty::Int
+            }
+            Self::Binary(lhs, op, rhs) => {
+                let (operand, result) = match op {
+                    BinaryOp::And | BinaryOp::Or => (Ty::Bool, Ty::Bool),
+                    BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+                        (Ty::Int, Ty::Bool)
+                    }
+                    BinaryOp::Eq | BinaryOp::NotEq => {
+                        let (l, r) = (lhs.ty(env)?, rhs.ty(env)?);
+                        if !l.fits(r) {
+                            return Err(syn::Error::new(
+                                rhs.span(),
+                                format!(
+                                    "cannot compare {} `{lhs}` with {} `{rhs}`",
+                                    l.name(),
+                                    r.name()
+                                ),
+                            ));
+                        }
+                        return Ok(Ty::Bool);
+                    }
+                    _ => (Ty::Int, Ty::Int),
+                };
+                expect(lhs, operand, op.symbol())?;
+                expect(rhs, operand, op.symbol())?;
+                result
+            }
+            Self::In(lhs, _) => {
+                lhs.ty(env)?;
+                Ty::Bool
+            }
+        })
     }
-}
 
-impl LAndExpr {
-    fn lvalues(&self) -> Punctuated<LValue, Token![,]> {
+    /// Get all lvalues in the expression
+    pub fn lvalues(&self) -> Punctuated<LValue, Token![,]> {
         match self {
-            LAndExpr::Rel(r) => r.lvalues(),
-            LAndExpr::RelLAnd(lhs, rhs) => {
+            Self::LVal(v) => std::iter::once(v.clone()).collect(),
+            Self::LitInt(_) | Self::LitBool(_) => Default::default(),
+            Self::Paren(e) | Self::Unary(_, e) => e.lvalues(),
+            Self::Binary(lhs, _, rhs) => {
                 let mut lvalues = lhs.lvalues();
                 lvalues.extend(rhs.lvalues());
                 lvalues
             }
-        }
-    }
-}
-
-impl LOrExpr {
-    fn lvalues(&self) -> Punctuated<LValue, Token![,]> {
-        match self {
-            LOrExpr::LAnd(l) => l.lvalues(),
-            LOrExpr::LAndOr(lhs, rhs) => {
+            Self::In(lhs, values) => {
                 let mut lvalues = lhs.lvalues();
-                lvalues.extend(rhs.lvalues());
+                lvalues.extend(values.iter().cloned());
                 lvalues
             }
         }
     }
-}
 
-impl Expr {
-    /// Get all lvalues in the expression
-    pub fn lvalues(&self) -> Punctuated<LValue, Token![,]> {
-        self.0.lvalues()
+    /// Map all lvalues in the expression
+    pub fn map(self, f: impl Fn(LValue) -> LValue + Clone) -> Self {
+        match self {
+            Self::LVal(lval) => Self::LVal(lval.map(f)),
+            Self::Paren(expr) => Self::Paren(Box::new(expr.map(f))),
+            Self::Unary(op, expr) => Self::Unary(op, Box::new(expr.map(f))),
+            Self::Binary(lhs, op, rhs) => {
+                Self::Binary(Box::new(lhs.map(f.clone())), op, Box::new(rhs.map(f)))
+            }
+            Self::In(lhs, values) => Self::In(
+                Box::new(lhs.map(f.clone())),
+                values.into_iter().map(f).collect(),
+            ),
+            other => other,
+        }
     }
 }
 
@@ -298,61 +456,94 @@ impl LValue {
     }
 }
 
-impl PrimaryExpr {
-    fn map(self, f: impl Fn(LValue) -> LValue + Clone) -> Self {
-        match self {
-            PrimaryExpr::LVal(lval) => PrimaryExpr::LVal(lval.map(f)),
-            PrimaryExpr::Paren(expr) => PrimaryExpr::Paren(Box::new(expr.map(f))),
-            other => other,
-        }
-    }
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-impl UnaryExpr {
-    fn map(self, f: impl Fn(LValue) -> LValue + Clone) -> Self {
-        match self {
-            UnaryExpr::Primary(p) => UnaryExpr::Primary(p.map(f)),
-            UnaryExpr::Not(e) => UnaryExpr::Not(Box::new(e.map(f))),
-        }
+    fn parse(s: &str) -> Expr {
+        syn::parse_str(s).unwrap()
     }
-}
 
-impl RelExpr {
-    fn map(self, f: impl Fn(LValue) -> LValue + Clone) -> Self {
-        match self {
-            RelExpr::Unary(u) => RelExpr::Unary(u.map(f)),
-            RelExpr::Eq(lhs, rhs) => RelExpr::Eq(lhs.map(f.clone()), rhs.map(f)),
-            RelExpr::NotEq(lhs, rhs) => RelExpr::NotEq(lhs.map(f.clone()), rhs.map(f)),
-            RelExpr::In(lhs, values) => {
-                RelExpr::In(lhs.map(f.clone()), values.into_iter().map(f).collect())
+    #[test]
+    fn test_precedence() {
+        let cases = [
+            ("a + b * c", "a + (b * c)"),
+            ("a - b - c", "(a - b) - c"),
+            ("x & 0xf == 3", "x & (0xf == 3)"),
+            ("a < b == c > d", "(a < b) == (c > d)"),
+            ("x >> 4 + 1", "x >> (4 + 1)"),
+            ("!a && b || c && d", "(!a && b) || (c && d)"),
+            ("a | b ^ c & d", "a | (b ^ (c & d))"),
+            ("-x + ~y", "-x + ~y"),
+            ("a + 1 in { B, C } && d", "((a + 1) in { B, C }) && d"),
+        ];
+        // fully parenthesize binary operators
+        fn grouped(e: &Expr) -> String {
+            match e {
+                Expr::Paren(e) => grouped(e),
+                Expr::Unary(op, e) => format!("{}{}", op.symbol(), grouped(e)),
+                Expr::Binary(lhs, op, rhs) => {
+                    format!("({} {} {})", grouped(lhs), op.symbol(), grouped(rhs))
+                }
+                Expr::In(lhs, values) => format!("({} in {})", grouped(lhs), values.len()),
+                e => e.to_string(),
             }
         }
-    }
-}
-
-impl LAndExpr {
-    fn map(self, f: impl Fn(LValue) -> LValue + Clone) -> Self {
-        match self {
-            LAndExpr::Rel(r) => LAndExpr::Rel(r.map(f)),
-            LAndExpr::RelLAnd(lhs, rhs) => {
-                LAndExpr::RelLAnd(lhs.map(f.clone()), Box::new(rhs.map(f)))
-            }
+        for (src, expected) in cases {
+            assert_eq!(grouped(&parse(src)), grouped(&parse(expected)), "{src}");
         }
+        // parentheses are kept in the generated code
+        assert_eq!(
+            parse("x & 0xf == 3").to_token_stream().to_string(),
+            "x & (0xf == 3)"
+        );
+        assert_eq!(
+            parse("valP + 8").to_token_stream().to_string(),
+            "crate :: dsl :: add (valP , 8)"
+        );
     }
-}
 
-impl LOrExpr {
-    fn map(self, f: impl Fn(LValue) -> LValue + Clone) -> Self {
-        match self {
-            LOrExpr::LAnd(l) => LOrExpr::LAnd(l.map(f)),
-            LOrExpr::LAndOr(lhs, rhs) => LOrExpr::LAndOr(lhs.map(f.clone()), Box::new(rhs.map(f))),
-        }
+    #[test]
+    fn test_stop_at_arrow() {
+        let input = quote::quote! { valP - 8 -> d.x };
+        let parser = |input: syn::parse::ParseStream| {
+            let expr: Expr = input.parse()?;
+            input.parse::<Token![->]>()?;
+            let dest: LValue = input.parse()?;
+            Ok((expr, dest))
+        };
+        let (expr, dest) = syn::parse::Parser::parse2(parser, input).unwrap();
+        assert_eq!(expr.to_string(), "valP - 8");
+        assert_eq!(dest.to_string(), "d.x");
     }
-}
 
-impl Expr {
-    /// Map all lvalues in the expression
-    pub fn map(self, f: impl Fn(LValue) -> LValue + Clone) -> Self {
-        Self(self.0.map(f))
+    #[test]
+    fn test_type_check() {
+        let env = |lv: &LValue| match lv.to_string().as_str() {
+            "flag" => Ty::Bool,
+            "x" => Ty::Int,
+            _ => Ty::Unknown,
+        };
+        let ty = |s: &str| parse(s).ty(&env).map_err(|e| e.to_string());
+        assert_eq!(ty("x + 8"), Ok(Ty::Int));
+        assert_eq!(ty("x < 8 && flag"), Ok(Ty::Bool));
+        assert_eq!(ty("D.icode == x"), Ok(Ty::Bool));
+        assert_eq!(ty("-D.valC"), Ok(Ty::Int));
+        assert_eq!(
+            ty("flag + 1"),
+            Err("`+` expects integer operands, but `flag` is bool".to_string())
+        );
+        assert_eq!(
+            ty("x && flag"),
+            Err("`&&` expects bool operands, but `x` is integer".to_string())
+        );
+        assert_eq!(
+            ty("flag == x"),
+            Err("cannot compare bool `flag` with integer `x`".to_string())
+        );
+        assert_eq!(
+            ty("!(x >> 2)"),
+            Err("`!` expects bool operands, but `(x >> 2)` is integer".to_string())
+        );
     }
 }
@@ -1,5 +1,5 @@
 use expr::LValue;
-use syn::{parse::Parse, punctuated::Punctuated, Token};
+use syn::{ext::IdentExt, parse::Parse, punctuated::Punctuated, Token};
 
 use crate::expr;
 
@@ -28,16 +28,13 @@ impl Parse for StageDecl {
         let _ = input.parse::<StageTitleBoundary>()?;
         let mut name = Vec::new();
         while !input.peek(Token![:]) || !input.peek2(Token![=]) {
-            let ident = input
-                .parse::<syn::Ident>()
-                .map(|id| id.to_string())
-                .unwrap_or_else(|_| {
-                    input
-                        .parse::<syn::LitStr>()
-                        .expect("invalid section title")
-                        .value()
-                });
-            name.push(ident);
+            if input.peek(syn::LitStr) {
+                name.push(input.parse::<syn::LitStr>()?.value());
+            } else if input.peek(syn::Ident::peek_any) {
+                name.push(input.call(syn::Ident::parse_any)?.to_string());
+            } else {
+                return Err(input.error("expected a word or a string in the section title"));
+            }
         }
         let _ = input.parse::<StageTitleBoundary>()?;
         Ok(Self {
@@ -74,6 +71,15 @@ impl Parse for StageAlias {
     }
 }
 
+/// The tunnel given by `#[tunnel(name)]` among the attributes
+fn tunnel_of(attrs: &[syn::Attribute]) -> syn::Result<Option<syn::Ident>> {
+    attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("tunnel"))
+        .map(|attr| attr.parse_args())
+        .transpose()
+}
+
 /// e.g. `imem.error => NOP`
 #[derive(Debug)]
 pub struct Case {
@@ -97,14 +103,7 @@ impl Parse for Case {
         let _ = input.parse::<syn::Token![:]>()?;
         let value: expr::Expr = input.parse()?;
 
-        let tunnel = attr.iter().find_map(|attr| {
-            if attr.path().is_ident("tunnel") {
-                let tunnel_name: syn::Ident = attr.parse_args().expect("invalid tunnel name");
-                Some(tunnel_name)
-            } else {
-                None
-            }
-        });
+        let tunnel = tunnel_of(&attr)?;
 
         Ok(Self {
             tunnel,
@@ -136,14 +135,7 @@ impl Parse for SignalSourceExpr {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
         let attr = input.call(syn::Attribute::parse_outer)?;
         let expr: expr::Expr = input.parse()?;
-        let tunnel = attr.iter().find_map(|attr| {
-            if attr.path().is_ident("tunnel") {
-                let tunnel_name: syn::Ident = attr.parse_args().expect("invalid tunnel name");
-                Some(tunnel_name)
-            } else {
-                None
-            }
-        });
+        let tunnel = tunnel_of(&attr)?;
         Ok(Self { tunnel, expr })
     }
 }
@@ -189,14 +181,7 @@ impl Parse for SignalDest {
         let attr = input.call(syn::Attribute::parse_outer)?;
         let dest: LValue = input.parse()?;
 
-        let tunnel = attr.iter().find_map(|attr| {
-            if attr.path().is_ident("tunnel") {
-                let tunnel_name: syn::Ident = attr.parse_args().expect("invalid tunnel name");
-                Some(tunnel_name)
-            } else {
-                None
-            }
-        });
+        let tunnel = tunnel_of(&attr)?;
 
         Ok(Self {
             tunnel,
@@ -267,6 +252,36 @@ impl Parse for SignalDef {
     }
 }
 
+impl SignalDef {
+    /// Branches counted by the coverage report, as the source and the line
+    /// of each case. A boolean signal defined by an expression counts as the
+    /// switch `[expr : true; 1 : false]`, unless the expression is a constant.
+    pub fn branches(&self) -> Option<Vec<(String, usize)>> {
+        match &self.source {
+            SignalSource::Switch(SignalSwitch(cases)) if cases.is_empty() => None,
+            SignalSource::Switch(SignalSwitch(cases)) => Some(
+                cases
+                    .iter()
+                    .map(|case| {
+                        let line = case.condition.span().start().line;
+                        (format!("{} : {}", case.condition, case.value), line)
+                    })
+                    .collect(),
+            ),
+            SignalSource::Expr(SignalSourceExpr { expr, .. })
+                if expr::Ty::of(&self.typ) == expr::Ty::Bool && !expr.lvalues().is_empty() =>
+            {
+                let line = expr.span().start().line;
+                Some(vec![
+                    (format!("{expr} : true"), line),
+                    ("1 : false".to_string(), line),
+                ])
+            }
+            SignalSource::Expr(_) => None,
+        }
+    }
+}
+
 /// a: b
 pub struct FieldAssign(pub syn::Ident, pub syn::Ident);
 
@@ -298,3 +313,105 @@ impl Parse for ComponentInputs {
         Ok(Self { name, fields })
     }
 }
+
+/// e.g.
+/// ```plain
+/// @tunnel(W_valM, {
+///     from: W.valM,
+///     to: [f_pc, d_valA, d_valB],
+///     at: [(1, 28)],
+/// });
+/// ```
+///
+/// `from` is the value carried by the tunnel, and `to` are the signals (or
+/// destinations) whose `#[tunnel(..)]` attributes trigger it. `at` is optional
+/// and gives the (row, column) of a cell on each wire of the tunnel in the
+/// datapath drawing.
+pub struct TunnelDecl {
+    pub name: syn::Ident,
+    pub from: LValue,
+    pub to: Vec<LValue>,
+    pub seeds: Vec<(syn::LitInt, syn::LitInt)>,
+}
+
+struct Seed(syn::LitInt, syn::LitInt);
+
+impl Parse for Seed {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let args;
+        let _ = syn::parenthesized!(args in input);
+        let row = args.parse()?;
+        let _ = args.parse::<Token![,]>()?;
+        let col = args.parse()?;
+        Ok(Self(row, col))
+    }
+}
+
+impl Parse for TunnelDecl {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let args;
+        let _ = syn::parenthesized!(args in input);
+
+        let name = args.parse::<syn::Ident>()?;
+        let _ = args.parse::<Token![,]>()?;
+
+        let fields;
+        let _ = syn::braced!(fields in args);
+        let mut from = None;
+        let mut to = Vec::new();
+        let mut seeds = Vec::new();
+        while !fields.is_empty() {
+            let key = fields.parse::<syn::Ident>()?;
+            let _ = fields.parse::<Token![:]>()?;
+            if key == "from" {
+                from = Some(fields.parse()?);
+            } else if key == "to" {
+                let items;
+                let _ = syn::bracketed!(items in fields);
+                to = Punctuated::<LValue, Token![,]>::parse_terminated(&items)?
+                    .into_iter()
+                    .collect();
+            } else if key == "at" {
+                let items;
+                let _ = syn::bracketed!(items in fields);
+                seeds = Punctuated::<Seed, Token![,]>::parse_terminated(&items)?
+                    .into_iter()
+                    .map(|Seed(row, col)| (row, col))
+                    .collect();
+            } else {
+                return Err(syn::Error::new(key.span(), "unknown tunnel field"));
+            }
+            if !fields.is_empty() {
+                let _ = fields.parse::<Token![,]>()?;
+            }
+        }
+        let Some(from) = from else {
+            return Err(syn::Error::new(name.span(), "missing `from` of the tunnel"));
+        };
+        Ok(Self {
+            name,
+            from,
+            to,
+            seeds,
+        })
+    }
+}
+
+/// `@assert(expr, "message")`: an invariant on the signals, checked after the
+/// signals are propagated in every cycle.
+pub struct AssertDecl {
+    pub expr: expr::Expr,
+    pub message: syn::LitStr,
+}
+
+impl Parse for AssertDecl {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let args;
+        let _ = syn::parenthesized!(args in input);
+        let expr = args.parse()?;
+        let _ = args.parse::<Token![,]>()?;
+        let message = args.parse()?;
+        let _ = args.parse::<Option<Token![,]>>()?;
+        Ok(Self { expr, message })
+    }
+}
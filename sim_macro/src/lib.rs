@@ -1,6 +1,6 @@
 use std::{env, path::PathBuf};
 
-use expr::LValue;
+use expr::{LValue, Ty};
 use items::{SignalDef, SignalSourceExpr, SignalSwitch};
 use quote::{format_ident, quote, ToTokens};
 use syn::{parse::Parse, parse_quote, punctuated::Punctuated, Token};
@@ -16,98 +16,81 @@ struct HclData {
     stage_decls: Vec<items::StageDecl>,
     use_items: Vec<syn::ItemUse>,
     intermediate_signals: Vec<items::SignalDef>,
-    rval_names: Vec<syn::Ident>,
+    /// (unit, index of the section where its inputs are set)
+    unit_sections: Vec<(syn::Ident, usize)>,
+    /// drawing of the datapath, see [`items::TunnelDecl`]
+    datapath: Option<syn::ExprPath>,
+    /// stage fields shown in the status table, `#![status(stat, icode)]`
+    status: Vec<syn::Ident>,
+    tunnels: Vec<items::TunnelDecl>,
+    asserts: Vec<items::AssertDecl>,
+}
+
+/// Find the inner attribute `#![name = path]`.
+fn path_attr(attrs: &[syn::Attribute], name: &str) -> syn::Result<Option<syn::ExprPath>> {
+    let Some(attr) = attrs.iter().find(|attr| attr.path().is_ident(name)) else {
+        return Ok(None);
+    };
+    match &attr.meta.require_name_value()?.value {
+        syn::Expr::Path(path) => Ok(Some(path.clone())),
+        value => Err(syn::Error::new_spanned(
+            value,
+            format!("`{name}` attribute must be a path"),
+        )),
+    }
+}
+
+/// Like [`path_attr`], but the attribute is required.
+fn required_path_attr(attrs: &[syn::Attribute], name: &str) -> syn::Result<syn::ExprPath> {
+    path_attr(attrs, name)?.ok_or_else(|| {
+        syn::Error::new(
+            proc_macro2::Span::call_site(),
+            format!("missing `#![{name} = ...]` attribute"),
+        )
+    })
+}
+
+/// Merge the errors into one, so that all of them are reported.
+fn combine_errors(errors: impl IntoIterator<Item = syn::Error>) -> syn::Result<()> {
+    match errors.into_iter().reduce(|mut a, b| {
+        a.combine(b);
+        a
+    }) {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
 }
 
 impl Parse for HclData {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
         // Parse inner attributes
         let attrs = syn::Attribute::parse_inner(input)?;
-        // find the hardware attribute
-        let hardware = attrs
-            .iter()
-            .find_map(|attr| {
-                if attr.path().is_ident("hardware") {
-                    let value = &attr
-                        .meta
-                        .require_name_value()
-                        .expect("invalid #[hardware = ...] attribute")
-                        .value;
-                    // parse value as path
-                    let syn::Expr::Path(path) = value else {
-                        panic!("hardware attribute must be a path");
-                    };
-
-                    Some(path)
-                } else {
-                    None
-                }
-            })
-            .cloned()
-            .expect("missing #[hardware = ...] attribute");
-
+        let hardware = required_path_attr(&attrs, "hardware")?;
         let stage_alias = attrs
             .iter()
-            .find_map(|attr| {
-                if attr.path().is_ident("stage_alias") {
-                    let stage_alias = attr
-                        .parse_args::<items::StageAlias>()
-                        .expect("invalid #[stage_alias(...)] attribute");
-
-                    Some(stage_alias)
-                } else {
-                    None
-                }
-            })
+            .find(|attr| attr.path().is_ident("stage_alias"))
+            .map(|attr| attr.parse_args::<items::StageAlias>())
+            .transpose()?
             .unwrap_or_default();
-
-        let program_counter = attrs
-            .iter()
-            .find_map(|attr| {
-                if attr.path().is_ident("program_counter") {
-                    let value = &attr
-                        .meta
-                        .require_name_value()
-                        .expect("invalid #[program_counter = ...] attribute")
-                        .value;
-                    // parse value as path
-                    let syn::Expr::Path(path) = value else {
-                        panic!("program_counter attribute must be a path");
-                    };
-
-                    Some(parse_quote!(#path))
-                } else {
-                    None
-                }
-            })
-            .expect("missing #[program_counter = ...] attribute");
-
-        let termination = attrs
-            .iter()
-            .find_map(|attr| {
-                if attr.path().is_ident("termination") {
-                    let value = &attr
-                        .meta
-                        .require_name_value()
-                        .expect("invalid #[termination = ...] attribute")
-                        .value;
-                    // parse value as path
-                    let syn::Expr::Path(path) = value else {
-                        panic!("program_counter attribute must be a path");
-                    };
-
-                    Some(parse_quote!(#path))
-                } else {
-                    None
-                }
-            })
-            .expect("missing #[termination = ...] attribute");
+        let program_counter = required_path_attr(&attrs, "program_counter")?;
+        let termination = required_path_attr(&attrs, "termination")?;
+        let datapath = path_attr(&attrs, "datapath")?;
+        let status = match attrs.iter().find(|attr| attr.path().is_ident("status")) {
+            Some(attr) => attr
+                .parse_args_with(Punctuated::<syn::Ident, Token![,]>::parse_terminated)?
+                .into_iter()
+                .collect(),
+            None => vec![format_ident!("stat"), format_ident!("icode")],
+        };
 
         let mut use_items = Vec::new();
         let mut intermediate_signals = Vec::new();
+        let mut tunnels = Vec::new();
         let mut stage_decls = Vec::new();
         let mut set_inputs = Vec::new();
+        let mut unit_sections = Vec::new();
         let mut set_stages = Vec::new();
+        let mut asserts = Vec::new();
 
         // repeatly parse the rest of the input
         loop {
@@ -125,14 +108,29 @@ impl Parse for HclData {
                 let fn_name = input.parse::<syn::Ident>()?;
                 if fn_name == "set_input" {
                     let unit_input = input.parse::<items::ComponentInputs>()?;
+                    if let Some(index) = stage_decls.len().checked_sub(1) {
+                        unit_sections.push((unit_input.name.clone(), index));
+                    }
                     set_inputs.push(unit_input);
                     let _ = input.parse::<Token![;]>()?;
                 } else if fn_name == "set_stage" {
                     let unit_input = input.parse::<items::ComponentInputs>()?;
                     set_stages.push(unit_input);
                     let _ = input.parse::<Token![;]>()?;
+                } else if fn_name == "tunnel" {
+                    tunnels.push(input.parse::<items::TunnelDecl>()?);
+                    let _ = input.parse::<Token![;]>()?;
+                } else if fn_name == "assert" {
+                    asserts.push(input.parse::<items::AssertDecl>()?);
+                    let _ = input.parse::<Token![;]>()?;
                 } else {
-                    panic!("unknown directive: {}", fn_name);
+                    return Err(syn::Error::new(
+                        fn_name.span(),
+                        format!(
+                            "unknown directive `@{fn_name}`, expected `@set_input`, \
+                             `@set_stage`, `@tunnel` or `@assert`"
+                        ),
+                    ));
                 }
             } else {
                 let mut item = input.parse::<items::SignalDef>()?;
@@ -148,56 +146,400 @@ impl Parse for HclData {
             }
         }
 
-        // These names are to be checked for existence
-        let mut rval_names = Vec::new();
+        let mut errors = Vec::new();
+        for (i, signal) in intermediate_signals.iter().enumerate() {
+            if intermediate_signals[..i]
+                .iter()
+                .any(|s| s.name == signal.name)
+            {
+                errors.push(syn::Error::new(
+                    signal.name.span(),
+                    format!("signal `{}` is defined more than once", signal.name),
+                ));
+            }
+        }
+        for set_stage in &set_stages {
+            if !stage_alias.0.iter().any(|(cur, _)| cur == &set_stage.name) {
+                errors.push(syn::Error::new(
+                    set_stage.name.span(),
+                    format!(
+                        "undeclared stage `{}`, stages are declared by \
+                         `#![stage_alias(..)]`",
+                        set_stage.name
+                    ),
+                ));
+            }
+        }
 
-        for unit_input in set_inputs {
-            let uname = unit_input.name;
-            for items::FieldAssign(lval, rval) in unit_input.fields {
-                rval_names.push(rval.clone());
-                // We have rval_check_fn to check if the rval exists
-                // If not, compiler will report an friendly error
-                let Some(sig) = intermediate_signals.iter_mut().find(|s| s.name == rval) else {
-                    continue;
-                };
-                sig.destinations.push(items::SignalDest {
-                    dest: LValue([uname.clone(), lval].into_iter().collect()),
-                    tunnel: None,
-                    is_stage_field: false,
-                });
+        for (directive, is_stage_field) in [(set_inputs, false), (set_stages, true)] {
+            for unit_input in directive {
+                let uname = unit_input.name;
+                for items::FieldAssign(lval, rval) in unit_input.fields {
+                    let Some(sig) = intermediate_signals.iter_mut().find(|s| s.name == rval) else {
+                        errors.push(syn::Error::new(
+                            rval.span(),
+                            format!("unknown signal `{rval}`"),
+                        ));
+                        continue;
+                    };
+                    sig.destinations.push(items::SignalDest {
+                        dest: LValue([uname.clone(), lval].into_iter().collect()),
+                        tunnel: None,
+                        is_stage_field,
+                    });
+                }
             }
         }
-        for stage_input in set_stages {
-            let sname = stage_input.name;
-            for items::FieldAssign(lval, rval) in stage_input.fields {
-                rval_names.push(rval.clone());
-                // We have rval_check_fn to check if the rval exists
-                // If not, compiler will report an friendly error
-                let Some(sig) = intermediate_signals.iter_mut().find(|s| s.name == rval) else {
-                    continue;
+
+        // every stage field or unit input is set by exactly one signal
+        let mut dests: Vec<&items::SignalDest> = Vec::new();
+        for dest in intermediate_signals.iter().flat_map(|s| &s.destinations) {
+            let name = dest.dest.to_string();
+            if dests.iter().any(|d| d.dest.to_string() == name) {
+                let kind = if dest.is_stage_field {
+                    "stage field"
+                } else {
+                    "unit input"
                 };
-                sig.destinations.push(items::SignalDest {
-                    dest: LValue([sname.clone(), lval].into_iter().collect()),
-                    tunnel: None,
-                    is_stage_field: true,
-                });
+                errors.push(syn::Error::new(
+                    dest.dest.0[0].span(),
+                    format!("{kind} `{name}` is set more than once"),
+                ));
+            } else {
+                dests.push(dest);
             }
         }
 
+        let signal_of = |path: syn::ExprPath, attr: &str| -> syn::Result<LValue> {
+            match path.path.get_ident() {
+                Some(ident) if intermediate_signals.iter().any(|s| &s.name == ident) => {
+                    Ok(LValue(std::iter::once(ident.clone()).collect()))
+                }
+                _ => Err(syn::Error::new_spanned(
+                    &path,
+                    format!(
+                        "`{}` given by `{attr}` is not a signal",
+                        path.to_token_stream()
+                    ),
+                )),
+            }
+        };
+        let program_counter = signal_of(program_counter, "program_counter");
+        let termination = signal_of(termination, "termination");
+        errors.extend(program_counter.as_ref().err().cloned());
+        errors.extend(termination.as_ref().err().cloned());
+        combine_errors(errors)?;
+
         Ok(Self {
             stage_alias,
             hardware,
-            program_counter,
-            termination,
+            program_counter: program_counter?,
+            termination: termination?,
             use_items,
             intermediate_signals,
             stage_decls,
-            rval_names,
+            unit_sections,
+            datapath,
+            status,
+            tunnels,
+            asserts,
         })
     }
 }
 
 impl HclData {
+    /// Run all static checks on the HCL, reporting every error found.
+    fn check(&self) -> syn::Result<()> {
+        combine_errors(
+            [
+                self.check_lvalues(),
+                self.check_cycles(),
+                self.check_tunnels(),
+                self.check_types(),
+            ]
+            .into_iter()
+            .filter_map(Result::err),
+        )
+    }
+
+    /// Stage fields are read from the current state (`D.icode`) and written
+    /// to the next state (`d.icode`), never the other way around.
+    fn check_lvalues(&self) -> syn::Result<()> {
+        let mut errors = Vec::new();
+        for signal in &self.intermediate_signals {
+            for lv in signal.source.lvalues() {
+                if lv.0.len() < 2 {
+                    continue;
+                }
+                if let Some((cur, pre)) = self.stage_alias.0.iter().find(|(cur, _)| cur == &lv.0[0])
+                {
+                    errors.push(syn::Error::new_spanned(
+                        &lv,
+                        format!(
+                            "`{cur}` is the next state of stage `{pre}` and can not be read, \
+                             use `{pre}.{}` instead",
+                            lv.0[1]
+                        ),
+                    ));
+                }
+            }
+            for dest in &signal.destinations {
+                let lv = &dest.dest;
+                if let Some((cur, pre)) = self.stage_alias.0.iter().find(|(_, pre)| pre == &lv.0[0])
+                {
+                    errors.push(syn::Error::new_spanned(
+                        lv,
+                        format!(
+                            "`{pre}` is the current state of stage `{cur}` and can not be set, \
+                             use `{cur}.{}` instead",
+                            lv.0.iter()
+                                .nth(1)
+                                .map(ToString::to_string)
+                                .unwrap_or_default()
+                        ),
+                    ));
+                }
+            }
+        }
+        combine_errors(errors)
+    }
+
+    /// Intermediate signals can not depend on each other in a cycle. Cycles
+    /// through units are only found when the circuit is built, since the
+    /// ports of units are not known here.
+    fn check_cycles(&self) -> syn::Result<()> {
+        let signals = &self.intermediate_signals;
+        // indices of the signals that `signals[i]` depends on
+        let deps: Vec<Vec<usize>> = signals
+            .iter()
+            .map(|signal| {
+                signal
+                    .source
+                    .lvalues()
+                    .iter()
+                    .filter(|lv| lv.0.len() == 1)
+                    .filter_map(|lv| signals.iter().position(|s| s.name == lv.0[0]))
+                    .collect()
+            })
+            .collect();
+        // remove the signals whose dependencies are all removed
+        let mut left = vec![true; signals.len()];
+        while let Some(i) =
+            (0..signals.len()).find(|&i| left[i] && deps[i].iter().all(|&d| !left[d]))
+        {
+            left[i] = false;
+        }
+        // every signal left depends on another one left, follow them until a
+        // signal repeats
+        let Some(start) = left.iter().position(|&l| l) else {
+            return Ok(());
+        };
+        let mut path = vec![start];
+        loop {
+            let last = path[path.len() - 1];
+            let prev = *deps[last].iter().find(|&&d| left[d]).unwrap();
+            if let Some(pos) = path.iter().position(|&i| i == prev) {
+                let cycle = std::iter::once(&prev)
+                    .chain(path[pos..].iter().rev())
+                    .map(|&i| signals[i].name.to_string())
+                    .collect::<Vec<_>>();
+                return Err(syn::Error::new(
+                    signals[prev].name.span(),
+                    format!("circular dependency: {}", cycle.join(" -> ")),
+                ));
+            }
+            path.push(prev);
+        }
+    }
+
+    /// Validate the tunnel map against the `#[tunnel(..)]` attributes. A
+    /// tunnel must be declared, and its destinations are exactly the places it
+    /// is attached to, each selecting the value in `from`.
+    fn check_tunnels(&self) -> syn::Result<()> {
+        // (tunnel, attached to, lvalues of the selected value)
+        let mut attached: Vec<(&syn::Ident, String, Option<Vec<String>>)> = Vec::new();
+        for signal in &self.intermediate_signals {
+            match &signal.source {
+                items::SignalSource::Switch(SignalSwitch(cases)) => {
+                    for case in cases {
+                        if let Some(tunnel) = &case.tunnel {
+                            let vals = case.value.lvalues().iter().map(LValue::to_string).collect();
+                            attached.push((tunnel, signal.name.to_string(), Some(vals)));
+                        }
+                    }
+                }
+                items::SignalSource::Expr(SignalSourceExpr { tunnel, expr }) => {
+                    if let Some(tunnel) = tunnel {
+                        let vals = expr.lvalues().iter().map(LValue::to_string).collect();
+                        attached.push((tunnel, signal.name.to_string(), Some(vals)));
+                    }
+                }
+            }
+            for dest in &signal.destinations {
+                if let Some(tunnel) = &dest.tunnel {
+                    attached.push((tunnel, dest.dest.to_string(), None));
+                }
+            }
+        }
+
+        let mut errors = Vec::new();
+        for (i, decl) in self.tunnels.iter().enumerate() {
+            if self.tunnels[..i].iter().any(|d| d.name == decl.name) {
+                errors.push(syn::Error::new(
+                    decl.name.span(),
+                    format!("tunnel `{}` is declared more than once", decl.name),
+                ));
+            }
+        }
+        for (tunnel, place, vals) in &attached {
+            let Some(decl) = self.tunnels.iter().find(|d| &d.name == *tunnel) else {
+                errors.push(syn::Error::new(
+                    tunnel.span(),
+                    format!("tunnel `{tunnel}` used by `{place}` is not declared by @tunnel"),
+                ));
+                continue;
+            };
+            if !decl.to.iter().any(|lv| &lv.to_string() == place) {
+                errors.push(syn::Error::new(
+                    tunnel.span(),
+                    format!("`{place}` is not a destination of tunnel `{tunnel}`"),
+                ));
+                continue;
+            }
+            let from = decl.from.to_string();
+            if vals.as_ref().is_some_and(|vals| !vals.contains(&from)) {
+                errors.push(syn::Error::new(
+                    tunnel.span(),
+                    format!(
+                        "tunnel `{tunnel}` carries `{from}`, which is not selected by `{place}`"
+                    ),
+                ));
+            }
+        }
+        for decl in &self.tunnels {
+            for dest in &decl.to {
+                let name = dest.to_string();
+                if !attached
+                    .iter()
+                    .any(|(tunnel, place, _)| *tunnel == &decl.name && place == &name)
+                {
+                    errors.push(syn::Error::new_spanned(
+                        dest,
+                        format!(
+                            "tunnel `{}` never reaches `{name}`: no #[tunnel({})] is found there",
+                            decl.name, decl.name
+                        ),
+                    ));
+                }
+            }
+        }
+        combine_errors(errors)
+    }
+
+    /// Check that operators in expressions receive operands of the right
+    /// types, and that the value of a signal matches its declared type. Only
+    /// the types of intermediate signals are known here.
+    fn check_types(&self) -> syn::Result<()> {
+        let env = |lv: &LValue| match self
+            .intermediate_signals
+            .iter()
+            .find(|s| lv.0.len() == 1 && s.name == lv.0[0])
+        {
+            Some(signal) => Ty::of(&signal.typ),
+            None => Ty::Unknown,
+        };
+        let mut errors = Vec::new();
+        for signal in &self.intermediate_signals {
+            let expected = Ty::of(&signal.typ);
+            let mut check = |expr: &expr::Expr, expected: Ty| {
+                let ty = match expr.ty(&env) {
+                    Ok(ty) => ty,
+                    Err(e) => {
+                        errors.push(syn::Error::new(
+                            e.span(),
+                            format!("type error in `{}`: {e}", signal.name),
+                        ));
+                        return;
+                    }
+                };
+                if !ty.fits(expected) {
+                    errors.push(syn::Error::new(
+                        expr.span(),
+                        format!(
+                            "type error in `{}`: expected {}, found {} `{expr}`",
+                            signal.name,
+                            signal.typ.to_token_stream(),
+                            ty.name()
+                        ),
+                    ));
+                }
+            };
+            match &signal.source {
+                items::SignalSource::Switch(SignalSwitch(cases)) => {
+                    for case in cases {
+                        check(&case.condition, Ty::Unknown);
+                        check(&case.value, expected);
+                    }
+                }
+                items::SignalSource::Expr(SignalSourceExpr { expr, .. }) => check(expr, expected),
+            }
+        }
+        for assert in &self.asserts {
+            let expr = &assert.expr;
+            match expr.ty(&env) {
+                Ok(ty) if ty.fits(Ty::Bool) => {}
+                Ok(ty) => errors.push(syn::Error::new(
+                    expr.span(),
+                    format!(
+                        "type error in assertion: expected bool, found {} `{expr}`",
+                        ty.name()
+                    ),
+                )),
+                Err(e) => errors.push(syn::Error::new(
+                    e.span(),
+                    format!("type error in assertion: {e}"),
+                )),
+            }
+        }
+        combine_errors(errors)
+    }
+
+    fn render_tunnel_map(&self) -> proc_macro2::TokenStream {
+        let tunnels = self.tunnels.iter().map(|decl| {
+            let name = decl.name.to_string();
+            let from = decl.from.to_string();
+            let to = decl.to.iter().map(LValue::to_string);
+            let seeds = decl.seeds.iter().map(|(row, col)| quote! { (#row, #col) });
+            quote! {
+                crate::datapath::Tunnel {
+                    name: #name,
+                    from: #from,
+                    to: &[#(#to),*],
+                    seeds: &[#(#seeds),*],
+                }
+            }
+        });
+        let datapath = match &self.datapath {
+            Some(drawing) => quote! {
+                Some(crate::datapath::Datapath {
+                    drawing: #drawing,
+                    tunnels: self.tunnel_map(),
+                })
+            },
+            None => quote! { None },
+        };
+        quote! {
+            fn tunnel_map(&self) -> &'static [crate::datapath::Tunnel] {
+                const TUNNELS: &[crate::datapath::Tunnel] = &[#(#tunnels),*];
+                TUNNELS
+            }
+
+            fn datapath(&self) -> Option<crate::datapath::Datapath> {
+                #datapath
+            }
+        }
+    }
+
     fn render_intermediate_signal_struct(&self) -> proc_macro2::TokenStream {
         let signal_fields: Punctuated<syn::Field, Token![,]> = self
             .intermediate_signals
@@ -221,6 +563,7 @@ impl HclData {
 
     fn render_signal_updater(
         signal: &SignalDef,
+        switch: Option<usize>,
         expr_mapper: impl Fn(LValue) -> LValue + Clone,
         lval_mapper: impl Fn(LValue) -> LValue + Clone,
     ) -> proc_macro2::TokenStream {
@@ -228,9 +571,11 @@ impl HclData {
 
         let source_stmts = match &signal.source {
             items::SignalSource::Switch(SignalSwitch(cases)) => {
+                let switch = switch.expect("index of the switch signal");
                 let case_stmts = cases
                     .iter()
-                    .map(|case| {
+                    .enumerate()
+                    .map(|(index, case)| {
                         let cond = case.condition.clone().map(expr_mapper.clone());
                         let val = case.value.clone().map(expr_mapper.clone());
                         let tunnel_stmts = case.tunnel.as_ref().cloned().map(|tunnel| {
@@ -244,6 +589,7 @@ impl HclData {
                         quote! {
                             if (u8::from(#cond)) != 0 {
                                 c_.#name = #val;
+                                tracer.take_case(#switch, #index);
                                 #tunnel_stmts
                             }
                         }
@@ -265,8 +611,12 @@ impl HclData {
                     }
                 });
 
+                let case_stmt = switch.map(|switch| {
+                    quote! { tracer.take_case(#switch, if c_.#name { 0 } else { 1 }); }
+                });
                 quote! {
                     c_.#name = #expr;
+                    #case_stmt
                     #tunnel_stmts
                 }
             }
@@ -350,10 +700,14 @@ impl HclData {
             lv
         };
 
+        let mut switches = 0..;
         let updaters_stmt = self
             .intermediate_signals
             .iter()
-            .map(|s| HclData::render_signal_updater(s, expr_mapper, lval_mapper))
+            .map(|s| {
+                let switch = s.branches().and_then(|_| switches.next());
+                HclData::render_signal_updater(s, switch, expr_mapper, lval_mapper)
+            })
             .reduce(|a, b| quote! { #a #b })
             .unwrap_or_default();
 
@@ -378,9 +732,14 @@ impl HclData {
                     .reduce(|a, b| quote! { #a #b }).unwrap_or_default();
                     // .collect::<Punctuated<LValue, Token![,]>>();
 
+                    let section_stmt = signal.stage_index.map(|index| {
+                        let section = &self.stage_decls[index].name;
+                        quote! { g.add_to_section(stringify!(#name), #section); }
+                    });
                     let update_stmts = quote! {
                         #update_stmts
                         g.add_intermediate(stringify!(#name));
+                        #section_stmt
                     };
                     let rev_deps_stmts = signal
                         .destinations
@@ -414,8 +773,14 @@ impl HclData {
                 .reduce(|a, b| quote! { #a #b })
                 .unwrap_or_default();
 
+        let unit_section_stmts = self.unit_sections.iter().map(|(unit, index)| {
+            let section = &self.stage_decls[*index].name;
+            quote! { g.add_to_section(stringify!(#unit), #section); }
+        });
+        let unit_section_stmts = quote! { #(#unit_section_stmts)* };
+
         quote! {
-            fn build_circuit() -> crate::framework::PropCircuit<Arch> {
+            fn build_circuit() -> anyhow::Result<crate::framework::PropCircuit<Arch>> {
                 use crate::framework::*;
 
                 // cur: o, nex: i
@@ -424,7 +789,8 @@ impl HclData {
                     // hardware setup
                     hardware_setup(&mut g);
                     #stmts
-                    g.build()
+                    #unit_section_stmts
+                    g.build()?
                 };
 
                 use crate::isa::inst_code::*;
@@ -434,7 +800,7 @@ impl HclData {
 
                 let mut circuit = PropCircuit::new(order);
                 #updaters_stmt
-                circuit
+                Ok(circuit)
             }
         }
     }
@@ -455,10 +821,16 @@ impl HclData {
                     &mut self.cur_inter
                 );
                 let units = &mut self.units;
+                let forced = &self.forced_outputs;
                 for item in &self.circuit.order.order {
                     if item.is_unit {
                         rcd.run_unit(|unit_in, unit_out| {
                             units.run(item.name, (unit_in, unit_out));
+                            for (unit, field, value) in forced {
+                                if unit == item.name {
+                                    unit_out.set(unit, field, value);
+                                }
+                            }
                         });
                     } else { // combinatorial logics do not change output (cur)
                         rcd.run_combinatorial_logic(item.name);
@@ -471,6 +843,77 @@ impl HclData {
         }
     }
 
+    /// Evaluate the `@assert` invariants on the signals of the current cycle.
+    /// Unlike the sources of signals, assertions can read the next state of
+    /// stages, e.g. `d.bubble`.
+    fn render_check_assertions(&self) -> proc_macro2::TokenStream {
+        let inter_names = self
+            .intermediate_signals
+            .iter()
+            .map(|s| &s.name)
+            .collect::<Vec<_>>();
+        let stage_alias = &self.stage_alias.0;
+        let mapper = |mut lv: LValue| -> LValue {
+            if inter_names.contains(&&lv.0[0]) {
+                lv.0.insert(0, format_ident!("c_"));
+            } else if let Some((cur, _)) = stage_alias.iter().find(|(_, pre)| &lv.0[0] == pre) {
+                lv.0[0] = cur.clone();
+                lv.0.insert(0, format_ident!("p_"));
+            } else if stage_alias.iter().any(|(cur, _)| &lv.0[0] == cur) {
+                lv.0.insert(0, format_ident!("n_"));
+            } else if lv.0.len() > 1 {
+                lv.0.insert(0, format_ident!("o_"));
+            }
+            lv
+        };
+        let checks = self.asserts.iter().map(|assert| {
+            let text = assert.expr.to_string();
+            let message = &assert.message;
+            // the signals in the assertion, constants excluded
+            let mut names = Vec::new();
+            let mut values = Vec::new();
+            for lv in assert.expr.lvalues() {
+                let name = lv.to_string();
+                let mapped = mapper(lv);
+                if mapped.0.len() > 1 && !names.contains(&name) {
+                    names.push(name);
+                    values.push(mapped);
+                }
+            }
+            let expr = assert.expr.clone().map(mapper);
+            quote! {
+                if !(#expr) {
+                    let mut msg = format!(
+                        "assertion `{}` failed in cycle {}: {}",
+                        #text,
+                        self.cycle_count,
+                        #message
+                    );
+                    #(msg += &format!("\n    {} = {:x?}", #names, #values);)*
+                    return Some(msg);
+                }
+            }
+        });
+        quote! {
+            /// Check the `@assert` invariants, returning the failure message
+            /// of the first one that does not hold.
+            #[allow(unused, non_snake_case)]
+            fn check_assertions(&self) -> Option<String> {
+                use crate::isa::inst_code::*;
+                use crate::isa::reg_code::*;
+                use crate::isa::op_code::*;
+                let (c_, p_, n_, o_) = (
+                    &self.cur_inter,
+                    &self.cur_state,
+                    &self.nex_state,
+                    &self.cur_unit_out,
+                );
+                #(#checks)*
+                None
+            }
+        }
+    }
+
     fn render_get_stage_info(&self) -> proc_macro2::TokenStream {
         let mut stage_items = self
             .stage_decls
@@ -542,22 +985,6 @@ impl HclData {
         }
     }
 
-    fn render_rval_check_fn(&self) -> proc_macro2::TokenStream {
-        let rval_names = &self.rval_names;
-        let stmts = rval_names
-            .iter()
-            .map(|name| quote! { let _ = c_.#name; })
-            .reduce(|a, b| quote! { #a #b })
-            .unwrap_or_default();
-        quote! {
-            #[allow(unused)]
-            fn check_rval_names(c_: IntermediateSignal) -> ! {
-                #stmts
-                panic!("this function should not be called");
-            }
-        }
-    }
-
     fn render(&self) -> proc_macro2::TokenStream {
         let hardware = &self.hardware;
         let use_stmts = self
@@ -571,9 +998,72 @@ impl HclData {
         let build_circuit_fn = self.render_build_circuit();
         let update_fn = self.render_update();
         let get_stage_info_fn = self.render_get_stage_info();
+        let tunnel_map_fn = self.render_tunnel_map();
+        let check_assertions_fn = self.render_check_assertions();
         let pc_name = &self.program_counter;
         let termination = &self.termination;
-        let rval_check_fn = self.render_rval_check_fn();
+        let stage_names = self
+            .stage_alias
+            .0
+            .iter()
+            .map(|(_, pre)| pre.to_string())
+            .collect::<Vec<_>>();
+        let stage_names = if stage_names.is_empty() {
+            vec!["(default)".to_string()]
+        } else {
+            stage_names
+        };
+        let switches = self.intermediate_signals.iter().filter_map(|signal| {
+            let name = &signal.name;
+            let cases = signal.branches()?.into_iter().map(|(source, line)| {
+                // the line is 0 if the compiler does not tell
+                let line = match line {
+                    0 => quote! { None },
+                    line => quote! { Some(#line) },
+                };
+                quote! { (#source, #line) }
+            });
+            Some(quote! { (stringify!(#name), &[#(#cases),*]) })
+        });
+        // the lines of the switches refer to the file of the `hcl!` block,
+        // which `include_str!` finds relative to itself
+        let file = proc_macro2::Span::call_site().local_file();
+        let source = match file.as_deref().and_then(|path| path.file_name()) {
+            Some(name) => {
+                let name = name.to_string_lossy().to_string();
+                quote! { Some(include_str!(#name)) }
+            }
+            None => quote! { None },
+        };
+        let stage_setters = self.stage_alias.0.iter().map(|(cur, pre)| {
+            quote! {
+                stringify!(#pre) => {
+                    if !self.cur_state.set(stringify!(#cur), field, value) {
+                        anyhow::bail!("can not set `{name}` to {value:?}");
+                    }
+                    return Ok(());
+                }
+            }
+        });
+        let stage_lookups = self.stage_alias.0.iter().map(|(cur, pre)| {
+            quote! {
+                stringify!(#pre) => self.cur_state.field(stringify!(#cur), field),
+                stringify!(#cur) => self.nex_state.field(stringify!(#cur), field),
+            }
+        });
+        let signal_lookups = self.intermediate_signals.iter().map(|sig| {
+            let name = &sig.name;
+            quote! { stringify!(#name) => self.cur_inter.#name.as_word(), }
+        });
+        let stage_ctrls = self
+            .stage_alias
+            .0
+            .iter()
+            .map(|(cur, _)| quote! { (self.nex_state.#cur.bubble, self.nex_state.#cur.stall) })
+            .collect::<Vec<_>>();
+        let stage_curs = self.stage_alias.0.iter().map(|(cur, _)| cur);
+        let stage_pres = self.stage_alias.0.iter().map(|(_, pre)| pre);
+        let status = &self.status;
 
         quote! {
             use #hardware::*;
@@ -593,12 +1083,31 @@ impl HclData {
 
             impl crate::framework::CpuArch for Arch {
                 type Units = Units;
+                const STAGES: &'static [&'static str] = &[#(#stage_names),*];
+                const SWITCHES: &'static crate::coverage::Switches<'static> = &[#(#switches),*];
                 #build_circuit_fn
             }
 
             impl crate::framework::PipeSim<Arch> {
                 #update_fn
-                #rval_check_fn
+
+                #check_assertions_fn
+
+                /// Print the status of the stages at the beginning of a cycle.
+                /// The control of a stage is the one computed in this cycle.
+                fn print_state(&self) {
+                    let curs: &[&str] = &[#(stringify!(#stage_curs)),*];
+                    let ctrls: &[(bool, bool)] = &[#(#stage_ctrls),*];
+                    let table = crate::utils::format_stage_status(
+                        &[#(stringify!(#stage_pres)),*],
+                        &[#(stringify!(#status)),*],
+                        |i, field| self.cur_state.field(curs[i], field),
+                        |i| ctrls[i],
+                    );
+                    if !table.is_empty() {
+                        println!("{table}");
+                    }
+                }
             }
             impl std::fmt::Display for crate::framework::PipeSim<Arch> {
                 fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -607,15 +1116,27 @@ impl HclData {
             }
             impl crate::framework::CpuSim for crate::framework::PipeSim<Arch> {
                 fn initiate_next_cycle(&mut self) {
+                    self.stages.shift(&[#(#stage_ctrls),*]);
                     self.cur_state.mux(&self.nex_state);
                 }
                 fn propagate_signals(&mut self) {
-                    self.update();
+                    use crate::framework::HardwareUnits;
+                    self.units.access_log().clear();
+                    let tracer = self.update();
+                    self.coverage.record(&tracer.cases);
+                    self.tunnels = tracer.tunnel;
+                    self.tunnels.sort_unstable();
+                    self.tunnels.dedup();
                     self.cycle_count += 1;
+                    self.stages.fetch(self.cur_inter.#pc_name);
 
                     if self.cur_inter.#termination {
                         self.terminate = true;
                     }
+                    if let Some(msg) = self.check_assertions() {
+                        self.failed_assertion = Some(msg);
+                        self.terminate = true;
+                    }
                 }
                 fn program_counter(&self) -> u64 {
                     self.cur_inter.#pc_name
@@ -627,15 +1148,92 @@ impl HclData {
                     self.cycle_count
                 }
                 fn cycle_cost(&self) -> u64 {
-                    self.circuit.order.max_dist as u64
+                    self.circuit.order.clock_period()
                 }
                 fn registers(&self) -> crate::isa::RegFile {
                     use crate::framework::HardwareUnits;
                     self.units.register_file()
                 }
+                fn set_register(&mut self, reg: u8, val: u64) {
+                    use crate::framework::HardwareUnits;
+                    self.units.set_register(reg, val)
+                }
+                fn condition_code(&self) -> crate::isa::ConditionCode {
+                    use crate::framework::HardwareUnits;
+                    self.units.condition_code()
+                }
 
                 #get_stage_info_fn
 
+                fn data_accesses(&self) -> Vec<crate::framework::DataAccess> {
+                    use crate::framework::HardwareUnits;
+                    self.units.access_log().accesses()
+                }
+
+                fn stage_instructions(&self) -> Vec<(&'static str, Option<u64>)> {
+                    use crate::framework::CpuArch;
+                    self.stages.instructions(Arch::STAGES)
+                }
+
+                fn retired_instruction(&self) -> Option<u64> {
+                    self.stages.retired
+                }
+
+                fn active_tunnels(&self) -> Vec<&'static str> {
+                    self.tunnels.clone()
+                }
+
+                fn failed_assertion(&self) -> Option<String> {
+                    self.failed_assertion.clone()
+                }
+                fn coverage(&self) -> &crate::coverage::Coverage {
+                    &self.coverage
+                }
+
+                fn source(&self) -> Option<&'static str> {
+                    #source
+                }
+
+                #tunnel_map_fn
+
+                #[allow(unreachable_patterns)]
+                fn signal(&self, name: &str) -> Option<u64> {
+                    use crate::framework::AsWord;
+                    if let Some((stage, field)) = name.split_once('.') {
+                        return match stage {
+                            #(#stage_lookups)*
+                            _ => None,
+                        };
+                    }
+                    match name {
+                        #(#signal_lookups)*
+                        _ => None,
+                    }
+                }
+
+                fn force_signal(
+                    &mut self,
+                    name: &str,
+                    value: &crate::hcl::Value,
+                ) -> anyhow::Result<()> {
+                    let Some((unit, field)) = name.split_once('.') else {
+                        anyhow::bail!("`{name}` is neither a stage field nor a unit output");
+                    };
+                    match unit {
+                        #(#stage_setters)*
+                        _ => {}
+                    }
+                    if self.cur_unit_out.get(unit, field).is_none() {
+                        anyhow::bail!("`{name}` is neither a stage field nor a unit output");
+                    }
+                    if !self.cur_unit_out.set(unit, field, value) {
+                        anyhow::bail!("can not set `{name}` to {value:?}");
+                    }
+                    self.forced_outputs.retain(|(u, f, _)| (u.as_str(), f.as_str()) != (unit, field));
+                    self.forced_outputs.push((unit.to_string(), field.to_string(), value.clone()));
+                    Ok(())
+                }
+
                 fn step(&mut self) {
                     use binutils::clap::builder::styling::*;
                     let title_style = Style::new().bold();
@@ -696,7 +1294,10 @@ impl HclData {
 /// inputs of units through Boolean expressions.
 #[proc_macro]
 pub fn hcl(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let data: HclData = syn::parse(item).expect("parse HCL error");
+    let data = syn::parse_macro_input!(item as HclData);
+    if let Err(e) = data.check() {
+        return e.to_compile_error().into();
+    }
     data.render().into()
 }
 
@@ -774,3 +1375,193 @@ pub fn extra_pipelines(_item: proc_macro::TokenStream) -> proc_macro::TokenStrea
     }
     .into()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse and check the HCL, returning the error messages.
+    fn errors(input: proc_macro2::TokenStream) -> Vec<String> {
+        match syn::parse2::<HclData>(input).and_then(|data| data.check()) {
+            Ok(()) => Vec::new(),
+            Err(e) => e.into_iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_valid() {
+        let errs = errors(quote! {
+            #![hardware = crate::architectures::hardware_seq]
+            #![program_counter = pc]
+            #![termination = term]
+            #![stage_alias(S => s)]
+
+            u64 pc = S.pc -> imem.pc;
+            bool term = pc == 0;
+            @set_stage(s, { pc: pc });
+        });
+        assert_eq!(errs, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_attribute_errors() {
+        assert_eq!(
+            errors(quote! {
+                #![program_counter = pc]
+                #![termination = term]
+            }),
+            ["missing `#![hardware = ...]` attribute"]
+        );
+        assert_eq!(
+            errors(quote! {
+                #![hardware = 1]
+            }),
+            ["`hardware` attribute must be a path"]
+        );
+        assert_eq!(
+            errors(quote! {
+                #![hardware = hw]
+                #![program_counter = pc]
+                #![termination = done]
+                u64 pc = 0;
+            }),
+            ["`done` given by `termination` is not a signal"]
+        );
+    }
+
+    #[test]
+    fn test_unknown_names() {
+        let errs = errors(quote! {
+            #![hardware = hw]
+            #![program_counter = pc]
+            #![termination = term]
+            #![stage_alias(S => s)]
+
+            u64 pc = S.pc;
+            bool term = false;
+            @set_input(imem, { pc: pcc });
+            @set_stage(t, { pc: pc });
+            @frobnicate(x);
+        });
+        assert_eq!(
+            errs,
+            ["unknown directive `@frobnicate`, expected `@set_input`, `@set_stage`, `@tunnel` or `@assert`"]
+        );
+
+        let errs = errors(quote! {
+            #![hardware = hw]
+            #![program_counter = pc]
+            #![termination = term]
+            #![stage_alias(S => s)]
+
+            u64 pc = S.pc;
+            bool term = false;
+            @set_input(imem, { pc: pcc });
+            @set_stage(t, { pc: pc });
+        });
+        assert_eq!(
+            errs,
+            [
+                "undeclared stage `t`, stages are declared by `#![stage_alias(..)]`",
+                "unknown signal `pcc`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let errs = errors(quote! {
+            #![hardware = hw]
+            #![program_counter = pc]
+            #![termination = term]
+            #![stage_alias(S => s)]
+
+            u64 pc = S.pc -> s.pc;
+            u64 pc = 0;
+            bool term = false;
+            @set_stage(s, { pc: pc });
+        });
+        assert_eq!(
+            errs,
+            [
+                "signal `pc` is defined more than once",
+                "stage field `s.pc` is set more than once",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stage_direction() {
+        let errs = errors(quote! {
+            #![hardware = hw]
+            #![program_counter = pc]
+            #![termination = term]
+            #![stage_alias(S => s)]
+
+            u64 pc = s.pc -> S.pc;
+            bool term = false;
+        });
+        assert_eq!(
+            errs,
+            [
+                "`s` is the next state of stage `S` and can not be read, use `S.pc` instead",
+                "`S` is the current state of stage `s` and can not be set, use `s.pc` instead",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_switch_type_mismatch() {
+        let errs = errors(quote! {
+            #![hardware = hw]
+            #![program_counter = pc]
+            #![termination = term]
+
+            bool term = false;
+            u64 pc = [
+                term : 0;
+                1 : term;
+            ];
+        });
+        assert_eq!(
+            errs,
+            ["type error in `pc`: expected u64, found bool `term`"]
+        );
+    }
+
+    #[test]
+    fn test_assertion_errors() {
+        let errs = errors(quote! {
+            #![hardware = hw]
+            #![program_counter = pc]
+            #![termination = term]
+
+            u64 pc = 0;
+            bool term = false;
+            @assert(pc, "not a condition");
+            @assert(term + 1 == 2, "bad operand");
+        });
+        assert_eq!(
+            errs,
+            [
+                "type error in assertion: expected bool, found integer `pc`",
+                "type error in assertion: `+` expects integer operands, but `term` is bool",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cycle() {
+        let errs = errors(quote! {
+            #![hardware = hw]
+            #![program_counter = a]
+            #![termination = term]
+
+            u64 a = c + 1;
+            u64 b = a;
+            u64 c = [ term : b; 1 : 0; ];
+            bool term = false;
+        });
+        assert_eq!(errs, ["circular dependency: a -> b -> c -> a"]);
+    }
+}